@@ -4,10 +4,13 @@ use pmusic::{
     musicgeneration::{
         chord_progression_generator::chord_progression_generation, random_scale::{get_random_base_note, get_random_scale}, rhythm_pattern_generator::rhythm_pattern_generation_for_chord, sheet_from_binary::sheet_from_binary_file, sheet_generator::sheet_generation
     }, 
+    musicfile::{abc::{sheet_from_abc_file, write_abc_file}, scala::{keyboard_mapping_from_kbm_file, scala_tuning_from_scl_file}, lilypond::write_lilypond_file, midi::{sheet_from_midi_file, write_midi_file}, musicxml::write_musicxml_file, text_sheet::{sheet_from_text_file, write_text_file}, wav::{write_wav_file, WavConfig, WavSampleFormat}, flac::write_flac_file, render::{align_and_mix_stems, get_stem_path, render_source}, pcm::write_pcm_stream, piano_roll::write_piano_roll_file, audio_image::{write_spectrogram_file, write_waveform_file}}, 
     musicsource::{chord_music_maker::ChordMusicMaker, sheet_music_maker::SheetMusicMaker}, 
-    musictheory::{chord_progression::ChordProgression, key::Key, piano_key::PianoKey, scale::Scale, time_signature::TimeSignature}, signal::adsr_envelop::AdsrEnvelop
+    musictheory::{chord_progression::ChordProgression, hertz::Hertz, key::Key, note_value::NoteValue, piano_key::PianoKey, scale::Scale, scala_tuning::KeyboardMapping, spelling::get_key_tonic, tempo::Tempo, time_signature::TimeSignature, tuning::{Tuning, TuningSystem}}, signal::adsr_envelop::AdsrEnvelop
 };
-use rodio::{dynamic_mixer, OutputStream, Sink, Source};
+#[cfg(feature = "tui")]
+use pmusic::tui::{run_playback_tui, TrackFactory, TuiAction, TuiPlayback};
+use rodio::{dynamic_mixer, OutputStream, OutputStreamHandle, Sink, Source};
 use rand::{rngs::SmallRng, RngCore, SeedableRng};
 use structopt::StructOpt;
 
@@ -19,17 +22,74 @@ struct Opt {
     chord_mode: bool,
     #[structopt(short, long, default_value="C4")]
     base_note: PianoKey,
+    /// Diatonic modes (major, minor, dorian...), pentatonic, harmonic-minor, melodic-minor and their modes, whole-tone,
+    /// half-whole, whole-half, blues, bebop, hirajoshi, in, hungarian-minor, double-harmonic, chromatic or tetratonic,
+    /// or a custom scale given by its steps ("2-1-2-2-1-3-1" or "W-H-W-W-H-W+H-H") or its notes ("C D Eb F# G")
     #[structopt(short, long, default_value = "Ionian")]
     scale: Scale,
+    /// Time signature of the generated music, with an optional beat grouping (e.g. 3/4, 6/8, 2+2+3/8)
+    #[structopt(long, default_value = "4/4")]
+    time_signature: TimeSignature,
     #[structopt(short, long, default_value = "1")]
     octaves: u8,
     #[structopt(short, long, default_value = "60")]
     tempo: u16,
     #[structopt(short, long, default_value = "10")]
     duration: u64,
-    /// Instead of playing the track, output the result in ./output/output.wav
+    /// Instead of playing the track, output the result in a WAV file (see --wav-path)
     #[structopt(short, long)]
     file_out: bool,
+    /// Path of the WAV file written with --file-out
+    #[structopt(long, default_value = "./output/output.wav")]
+    wav_path: String,
+    /// Sample format of the WAV file: 16, 24 (PCM) or float
+    #[structopt(long, default_value = "float")]
+    wav_format: WavSampleFormat,
+    /// Number of channels of the rendered audio: 1 (mono) or 2 (stereo)
+    #[structopt(long, default_value = "2")]
+    channels: u16,
+    /// Sample rate of the rendered audio
+    #[structopt(long, default_value = "44100")]
+    sample_rate: u32,
+    /// With --file-out, also write each track (melody, chords) to its own WAV next to the mixdown
+    #[structopt(long)]
+    stems: bool,
+    /// Stream raw interleaved PCM to stdout until interrupted (ignores the duration), e.g. `| aplay -f S16_LE -c 2 -r 44100`
+    #[structopt(long)]
+    pcm_stdout: bool,
+    /// Sample format of the PCM stream: 16 (s16le), 24 (s24_3le) or float (f32le)
+    #[structopt(long, default_value = "16")]
+    pcm_format: WavSampleFormat,
+    /// Export the rendered track to a FLAC file (lossless, tagged with seed, scale, tempo and chord progression)
+    #[structopt(long, default_value = "")]
+    flac_out: String,
+    /// Sample format of the FLAC file: 16 or 24 (PCM)
+    #[structopt(long, default_value = "16")]
+    flac_format: WavSampleFormat,
+    /// Show a terminal UI during playback (current note and chord, pause, skip pattern, regenerate)
+    #[structopt(long)]
+    tui: bool,
+    /// Draw the waveform of the rendered track (.png or .svg)
+    #[structopt(long, default_value = "")]
+    waveform_out: String,
+    /// Draw the spectrogram of the rendered track (.png or .svg)
+    #[structopt(long, default_value = "")]
+    spectrogram_out: String,
+    /// Disable the TPDF dithering applied when exporting to 16 or 24 bit PCM (WAV or FLAC)
+    #[structopt(long)]
+    no_dither: bool,
+    /// Tuning of the keys: 12tet, just, pythagorean, meantone (pure from the base note) or <N>edo (e.g. 19edo, each key on the nearest of the N steps)
+    #[structopt(long, default_value = "12tet")]
+    tuning: TuningSystem,
+    /// Frequency of A4 in Hz
+    #[structopt(long, default_value = "440")]
+    reference_pitch: f64,
+    /// Play in the tuning of a Scala scale file (.scl), by default its tonic is C4 and A4 is at the reference pitch
+    #[structopt(long, default_value = "")]
+    scl_in: String,
+    /// Keyboard mapping file (.kbm) used with the Scala scale
+    #[structopt(long, default_value = "")]
+    kbm_in: String,
     /// WARNING: this flag can produce some very high pitch sound
     #[structopt(short, long)]
     instrument_debug: bool,
@@ -51,18 +111,87 @@ struct Opt {
     /// Parse the file half a byte at a time to generate the sheet (only with source binary file)
     #[structopt(short="h", long)]
     half_byte_parsing: bool,
+    /// Export the sheet (and the chord progression in chord mode) to a Standard MIDI File
+    #[structopt(long, default_value = "")]
+    midi_out: String,
+    /// Source MIDI file to read the sheet from, the tempo of the file replaces the tempo argument
+    #[structopt(long, default_value = "")]
+    midi_in: String,
+    /// Export the sheet (and the chord progression in chord mode) to a MusicXML file
+    #[structopt(long, default_value = "")]
+    musicxml_out: String,
+    /// Export the sheet (and the chord progression in chord mode) to a LilyPond file
+    #[structopt(long, default_value = "")]
+    lilypond_out: String,
+    /// Source ABC file to read the sheet from, the tempo of the file replaces the tempo argument
+    #[structopt(long, default_value = "")]
+    abc_in: String,
+    /// Export the sheet (and the chord progression in chord mode) to an ABC file
+    #[structopt(long, default_value = "")]
+    abc_out: String,
+    /// Source text file to read the sheet from (ASCII notation like "C4q D#4e. E4e | C4e:3 D4e:3 E4e:3 F4h.", e:3 is a triplet eighth)
+    #[structopt(long, default_value = "")]
+    text_in: String,
+    /// Export the sheet to a text file (ASCII notation)
+    #[structopt(long, default_value = "")]
+    text_out: String,
+    /// Draw the sheet (and the chord progression in chord mode) as a piano roll in a SVG file
+    #[structopt(long, default_value = "")]
+    piano_roll_out: String,
+}
+
+// Informational output goes to stderr when stdout carries the PCM stream
+macro_rules! info {
+    ($to_stderr:expr, $($arg:tt)*) => {
+        if $to_stderr {
+            eprintln!($($arg)*);
+        } else {
+            println!($($arg)*);
+        }
+    };
+}
+
+fn limit_duration<S>(source: S, duration: Option<Duration>) -> Box<dyn Source<Item = f32> + Send>
+where
+    S: Source<Item = f32> + Send + 'static,
+{
+    match duration {
+        Some(duration) => Box::new(source.take_duration(duration)),
+        None => Box::new(source),
+    }
+}
+
+// Only needed in play mode, so exports keep working on machines without a sound card
+fn open_audio_output() -> Result<(OutputStream, OutputStreamHandle, Sink), Error> {
+    let no_device_error = |e: String| Error::new(
+        std::io::ErrorKind::NotFound,
+        format!("Cannot open the audio output device ({}), use --file-out, --flac-out or --pcm-stdout to render without a sound card", e),
+    );
+    let (stream, stream_handle) = OutputStream::try_default().map_err(|e| no_device_error(e.to_string()))?;
+    let sink = Sink::try_new(&stream_handle).map_err(|e| no_device_error(e.to_string()))?;
+    Ok((stream, stream_handle, sink))
 }
 
 fn main() -> Result<(), Error> {
-    let now = Instant::now();
     let opt = Opt::from_args();
+    let mut requested_seed = opt.seed;
+    // Regenerating from the terminal UI always picks a new random seed
+    while run(&opt, requested_seed)? {
+        requested_seed = 0;
+    }
+    Ok(())
+}
+
+// True when the terminal UI asks for a new piece
+fn run(opt: &Opt, requested_seed: u64) -> Result<bool, Error> {
+    let now = Instant::now();
     
     let seed: u64;
-    if opt.seed == 0 {
+    if requested_seed == 0 {
         let mut seed_gen = SmallRng::from_entropy();
         seed = seed_gen.next_u64();
     } else {
-        seed = opt.seed;
+        seed = requested_seed;
     }
     
     let mut rng_seed = SmallRng::seed_from_u64(seed);
@@ -73,91 +202,256 @@ fn main() -> Result<(), Error> {
         amplify_value = 0.2;
     }
     let mut nb_measures = 4;
+    let time_signature = opt.time_signature;
+    let mut chord_part: Option<(ChordProgression, Vec<NoteValue>)> = None;
+    let mut tempo = opt.tempo;
+    let mut imported_sheet = None;
+    let mut tracks: Vec<(&str, Box<dyn Source<Item = f32> + Send>)> = Vec::new();
+    #[cfg(feature = "tui")]
+    let mut track_factories: Vec<TrackFactory> = Vec::new();
+    if !opt.midi_in.is_empty() {
+        let (sheet, midi_tempo) = sheet_from_midi_file(&opt.midi_in)?;
+        tempo = u16::from(midi_tempo);
+        imported_sheet = Some(sheet);
+    } else if !opt.abc_in.is_empty() {
+        let (sheet, abc_tempo) = sheet_from_abc_file(&opt.abc_in)?;
+        tempo = u16::from(abc_tempo);
+        imported_sheet = Some(sheet);
+    } else if !opt.text_in.is_empty() {
+        imported_sheet = Some(sheet_from_text_file(&opt.text_in)?);
+    }
 
-    let (controller, mixer) = dynamic_mixer::mixer::<f32>(2, 44_100);
-    let (_stream, stream_handle) = OutputStream::try_default().unwrap();
-    let sink = Sink::try_new(&stream_handle).unwrap();
+    let wav_config = WavConfig::new(opt.wav_format, opt.channels, opt.sample_rate).set_dither(!opt.no_dither);
+    wav_config.validate()?;
+    let flac_config = wav_config.set_sample_format(opt.flac_format);
+    if !opt.flac_out.is_empty() && flac_config.sample_format == WavSampleFormat::Float32 {
+        return Err(Error::new(std::io::ErrorKind::InvalidInput, "FLAC only supports 16 and 24 bit PCM"));
+    }
+    let track_duration = if opt.pcm_stdout { None } else { Some(Duration::from_secs(opt.duration)) };
+    let mut tuning_system = opt.tuning.clone();
+    if !opt.scl_in.is_empty() {
+        let mapping = if opt.kbm_in.is_empty() {
+            KeyboardMapping::default().set_reference(69, Hertz(opt.reference_pitch))
+        } else {
+            keyboard_mapping_from_kbm_file(&opt.kbm_in)?
+        };
+        tuning_system = TuningSystem::Scala(scala_tuning_from_scl_file(&opt.scl_in)?.set_mapping(mapping));
+    } else if !opt.kbm_in.is_empty() {
+        return Err(Error::new(std::io::ErrorKind::InvalidInput, "A keyboard mapping needs a Scala scale (--scl-in)"));
+    }
+    #[cfg(not(feature = "tui"))]
+    if opt.tui {
+        return Err(Error::new(std::io::ErrorKind::InvalidInput, "The terminal UI needs the tui feature"));
+    }
 
+    // The melody, the chords and the exports all use the scale and base note picked here
     let scale: Scale;
     let base_note: PianoKey;
     if opt.full_random {
         scale = get_random_scale(&mut rng_seed);
-        base_note = get_random_base_note(&mut rng_seed)
+        base_note = get_key_tonic(get_random_base_note(&mut rng_seed), scale)
     } else {
         scale = opt.scale;
         base_note = opt.base_note;
     }
+    let tuning = Tuning::new(tuning_system, Hertz(opt.reference_pitch), base_note.note);
+    tuning.validate()?;
 
-    println!("Seed: {}", seed);
-    if opt.file_in == "" {
-        println!("Scale: {} {} {}", base_note, scale, Key::new(opt.scale, opt.base_note, opt.octaves));
+    info!(opt.pcm_stdout, "Seed: {}", seed);
+    if tuning != Tuning::default() {
+        info!(opt.pcm_stdout, "Tuning: {}", tuning);
+    }
+    if opt.file_in == "" && imported_sheet.is_none() {
+        info!(opt.pcm_stdout, "Scale: {} {} {}", base_note, scale, Key::new(scale, base_note, opt.octaves));
     }
     if opt.chord_mode {
-        let time_signature = TimeSignature::default();
-        let mut chord_base_note = opt.base_note;
+        let mut chord_base_note = base_note;
         chord_base_note.octave = 2;
         let chord_progression = ChordProgression::from_scale_and_str(
-            opt.scale,
+            scale,
             chord_base_note,
-            &chord_progression_generation(opt.scale, time_signature.clone(), opt.random_chord_progression, &mut rng_seed)
+            &chord_progression_generation(scale, time_signature, opt.random_chord_progression, &mut rng_seed)
         );
-        let rhythm_pattern = rhythm_pattern_generation_for_chord(time_signature.clone(), &mut rng_seed);
+        let rhythm_pattern = rhythm_pattern_generation_for_chord(time_signature, &mut rng_seed);
         let chords = ChordMusicMaker::new(
             chord_progression.clone(),
             rhythm_pattern.clone(),
-            opt.tempo,
+            tempo,
             opt.instrument_debug,
         )
         //.set_adsr_envelop(AdsrEnvelop::new(0.1, 0.2, 4.0, 1.0));
-        .set_adsr_envelop(AdsrEnvelop::default());
+        .set_adsr_envelop(AdsrEnvelop::default())
+        .set_tuning(tuning.clone());
 
         nb_measures = chord_progression.clone().chords.len();
-        println!("Chord progression: {}", chord_progression);
+        info!(opt.pcm_stdout, "Chord progression: {}", chord_progression);
 
         // By removing the .amplify at the end, we can make the sound saturate
-        controller.add(chords.take_duration(Duration::from_secs(opt.duration)).amplify(amplify_value - 0.05));
+        let chords = chords.amplify(amplify_value - 0.05);
+        tracks.push(("chords", limit_duration(chords.clone(), track_duration)));
+        #[cfg(feature = "tui")]
+        track_factories.push(Box::new(move || Box::new(chords.clone())));
+        chord_part = Some((chord_progression, rhythm_pattern));
     }
 
     let sheet;
-    if opt.file_in != "" {
+    if let Some(imported_sheet) = imported_sheet {
+        sheet = imported_sheet;
+    } else if opt.file_in != "" {
         sheet = sheet_from_binary_file::<Error>(
-            opt.base_note, 
+            base_note, 
             &opt.file_in,
             opt.half_byte_parsing,
         )?;
     } else {
         sheet = sheet_generation(
-            opt.base_note, 
-            opt.scale, 
+            base_note, 
+            scale, 
             opt.octaves, 
+            time_signature,
             nb_measures as i32,
             opt.use_common_pattern,
             &mut rng_seed
         )
     }
+    if !opt.midi_out.is_empty() {
+        info!(opt.pcm_stdout, "Export MIDI to {}", opt.midi_out);
+        write_midi_file(
+            &opt.midi_out,
+            &sheet,
+            chord_part.as_ref().map(|(cp, rp)| (cp, rp.as_slice())),
+            Tempo::from(tempo),
+            time_signature,
+        )?;
+    }
+    if !opt.musicxml_out.is_empty() {
+        info!(opt.pcm_stdout, "Export MusicXML to {}", opt.musicxml_out);
+        write_musicxml_file(
+            &opt.musicxml_out,
+            &sheet,
+            chord_part.as_ref().map(|(cp, rp)| (cp, rp.as_slice())),
+            Key::new(scale, base_note, opt.octaves),
+            Tempo::from(tempo),
+        )?;
+    }
+    if !opt.lilypond_out.is_empty() {
+        info!(opt.pcm_stdout, "Export LilyPond to {}", opt.lilypond_out);
+        write_lilypond_file(
+            &opt.lilypond_out,
+            &sheet,
+            chord_part.as_ref().map(|(cp, rp)| (cp, rp.as_slice())),
+            Key::new(scale, base_note, opt.octaves),
+            Tempo::from(tempo),
+        )?;
+    }
+    if !opt.text_out.is_empty() {
+        info!(opt.pcm_stdout, "Export text sheet to {}", opt.text_out);
+        write_text_file(&opt.text_out, &sheet)?;
+    }
+    if !opt.piano_roll_out.is_empty() {
+        info!(opt.pcm_stdout, "Export piano roll to {}", opt.piano_roll_out);
+        write_piano_roll_file(
+            &opt.piano_roll_out,
+            &sheet,
+            chord_part.as_ref().map(|(cp, rp)| (cp, rp.as_slice())),
+        )?;
+    }
+    if !opt.abc_out.is_empty() {
+        info!(opt.pcm_stdout, "Export ABC to {}", opt.abc_out);
+        write_abc_file(
+            &opt.abc_out,
+            &sheet,
+            chord_part.as_ref().map(|(cp, rp)| (cp, rp.as_slice())),
+            Key::new(scale, base_note, opt.octaves),
+            Tempo::from(tempo),
+        )?;
+    }
+
     let music = SheetMusicMaker::new(
-        sheet, 
-        opt.tempo, 
+        sheet.clone(), 
+        tempo, 
         opt.instrument_debug)
                 // .set_adsr_envelop(AdsrEnvelop::new(0.1, 0.2, 1.5, 0.4));
-                .set_adsr_envelop(AdsrEnvelop::default());
-    println!("{}", music);
-    if opt.file_out {
-        let filepath = "./output/output.wav";
-        println!("Export to {}", filepath);
-        controller.add(music.take_duration(Duration::from_secs(opt.duration)).amplify(amplify_value));
-        let head = wav_io::new_stereo_header();
-        let mut file_out = std::fs::File::create(filepath).unwrap();
-        wav_io::write_to_file(&mut file_out, &head, &mixer.convert_samples().into_iter().collect::<Vec<f32>>()).unwrap();
+                .set_adsr_envelop(AdsrEnvelop::default())
+                .set_tuning(tuning.clone());
+    info!(opt.pcm_stdout, "{}", music);
+    let music = music.amplify(amplify_value);
+    tracks.push(("melody", limit_duration(music.clone(), track_duration)));
+    #[cfg(feature = "tui")]
+    track_factories.push(Box::new(move || Box::new(music.clone())));
+    if opt.pcm_stdout {
+        let pcm_config = wav_config.set_sample_format(opt.pcm_format);
+        eprintln!("Streaming PCM to stdout ({})", pcm_config);
+        let (controller, mixer) = dynamic_mixer::mixer::<f32>(pcm_config.channels, pcm_config.sample_rate);
+        tracks.into_iter().for_each(|(_, track)| controller.add(track));
+        match write_pcm_stream(&mut std::io::stdout().lock(), mixer, pcm_config) {
+            // The reader closed the pipe, it is the normal way for the stream to end
+            Err(e) if e.kind() == std::io::ErrorKind::BrokenPipe => {},
+            result => result?,
+        }
+    } else if opt.file_out || !opt.flac_out.is_empty() || !opt.waveform_out.is_empty() || !opt.spectrogram_out.is_empty() {
+        let (track_names, tracks): (Vec<&str>, Vec<_>) = tracks.into_iter().unzip();
+        let mut stems = tracks.into_iter()
+            .map(|track| render_source(track, wav_config.channels, wav_config.sample_rate))
+            .collect::<Vec<Vec<f32>>>();
+        let samples = align_and_mix_stems(&mut stems);
+        if opt.file_out {
+            println!("Export to {} ({})", opt.wav_path, wav_config);
+            write_wav_file(&opt.wav_path, &samples, wav_config)?;
+            if opt.stems {
+                for (track_name, stem) in track_names.iter().zip(stems.iter()) {
+                    let stem_path = get_stem_path(&opt.wav_path, track_name);
+                    println!("Export {} stem to {}", track_name, stem_path);
+                    write_wav_file(&stem_path, stem, wav_config)?;
+                }
+            }
+        }
+        if !opt.flac_out.is_empty() {
+            println!("Export FLAC to {} ({})", opt.flac_out, flac_config);
+            let mut tags = vec![
+                (String::from("SEED"), seed.to_string()),
+                (String::from("SCALE"), format!("{} {}", base_note, scale)),
+                (String::from("TEMPO"), tempo.to_string()),
+                (String::from("TUNING"), tuning.to_string()),
+            ];
+            if let Some((chord_progression, _)) = &chord_part {
+                tags.push((String::from("CHORD_PROGRESSION"), chord_progression.to_string()));
+            }
+            write_flac_file(&opt.flac_out, &samples, flac_config, &tags)?;
+        }
+        if !opt.waveform_out.is_empty() {
+            println!("Export waveform to {}", opt.waveform_out);
+            write_waveform_file(&opt.waveform_out, &samples, wav_config.channels)?;
+        }
+        if !opt.spectrogram_out.is_empty() {
+            println!("Export spectrogram to {}", opt.spectrogram_out);
+            write_spectrogram_file(&opt.spectrogram_out, &samples, wav_config.channels, wav_config.sample_rate)?;
+        }
 
         // "benchmark"
         let elapsed_time = now.elapsed();
         println!("Execution took {} seconds.", elapsed_time.as_secs());
     } else {
-        controller.add(music.take_duration(Duration::from_secs(opt.duration)).amplify(amplify_value));
+        #[cfg_attr(not(feature = "tui"), allow(unused_variables))]
+        let (_stream, stream_handle, sink) = open_audio_output()?;
+        #[cfg(feature = "tui")]
+        if opt.tui {
+            let playback = TuiPlayback {
+                header: format!("Seed: {}  Scale: {} {}  Tempo: {}", seed, base_note, scale, tempo),
+                sheet: &sheet,
+                chord_part: chord_part.as_ref().map(|(cp, rp)| (cp, rp.as_slice())),
+                tempo: Tempo::from(tempo),
+                duration: Duration::from_secs(opt.duration),
+                channels: wav_config.channels,
+                sample_rate: wav_config.sample_rate,
+            };
+            return Ok(run_playback_tui(playback, &stream_handle, sink, &track_factories)? == TuiAction::Regenerate);
+        }
+        let (controller, mixer) = dynamic_mixer::mixer::<f32>(wav_config.channels, wav_config.sample_rate);
+        tracks.into_iter().for_each(|(_, track)| controller.add(track));
         sink.append(mixer);
         sink.sleep_until_end();
     }
 
-    Ok(())
+    Ok(false)
 }
\ No newline at end of file
@@ -1,7 +1,10 @@
 pub mod musicsource;
 pub mod musictheory;
 pub mod musicgeneration;
+pub mod musicfile;
 pub mod signal;
+#[cfg(feature = "tui")]
+pub mod tui;
 
 #[cfg(test)]
 mod test;
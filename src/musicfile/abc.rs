@@ -0,0 +1,566 @@
+// ABC notation import and export
+// Export writes one line per pattern (introduced by a P: field) with the chord progression as chord symbols,
+// import reads the melody back, rests are not supported by the sheet so rests extend the previous note
+// (or the next one before the first note)
+
+use std::{collections::HashMap, fs::File, io::{Error, ErrorKind, Read, Write}, str::FromStr};
+
+use num::{rational::Rational32, CheckedMul, Zero};
+
+use crate::musictheory::{
+    chord::{Chord, ChordInversion, ChordType}, chord_progression::ChordProgression, key::Key, measure::Measure,
+    mode::Mode, note::{Accidental, Note, NoteLetter}, note_value::{NoteValue, Tuplet}, pattern::Pattern, piano_key::PianoKey,
+    scale::Scale, sheet::Sheet, sheet_note::SheetNote, spelling::respell_in_key, tempo::Tempo, time_signature::TimeSignature
+};
+
+use super::{get_alters_from_fifths, get_chord_timeline, get_mode_fifths, get_note_fifths, get_note_values, get_sheet_length, get_tuplet_runs, midi::{get_midi_note_number, get_piano_key}};
+
+// The unit note length (L: field) used for the export
+const UNIT_LENGTH: u32 = 8;
+// A triple dotted sixty-fourth or a shortened one in a >>> broken rhythm
+const MAX_LENGTH_DENOMINATOR: i32 = 512;
+
+pub fn sheet_to_abc(sheet: &Sheet, chords: Option<(&ChordProgression, &[NoteValue])>, key: Key, tempo: Tempo) -> String {
+    let time_signature = sheet.patterns.iter()
+        .flat_map(|p| p.measures.iter())
+        .map(|m| m.time_signature)
+        .next()
+        .unwrap_or_default();
+    let (key_name, key_signature) = get_key_signature(key);
+
+    let mut ret = String::from("X:1\nT:pmusic\n");
+    ret.push_str(&format!("M:{}\n", time_signature));
+    ret.push_str(&format!("L:1/{}\n", UNIT_LENGTH));
+    ret.push_str(&format!("Q:1/4={}\n", u16::from(tempo)));
+    ret.push_str(&format!("K:{}\n", key_name));
+
+    let chord_timeline = match chords {
+        Some((chord_progression, rhythm_pattern)) => get_chord_timeline(chord_progression, rhythm_pattern, get_sheet_length(sheet)),
+        None => Vec::new(),
+    };
+    let mut chord_positions = chord_timeline.iter()
+        .map(|(start, _, chord)| (*start, *chord))
+        .peekable();
+
+    let mut position = Rational32::zero();
+    let mut previous_time_signature = time_signature;
+    sheet.patterns.iter().enumerate().for_each(|(i, pattern)| {
+        let name = if pattern.name.is_empty() { i.to_string() } else { pattern.name.clone() };
+        ret.push_str(&format!("P:{}\n", name));
+
+        let mut line = String::new();
+        pattern.measures.iter().for_each(|measure| {
+            if measure.time_signature != previous_time_signature {
+                line.push_str(&format!("[M:{}] ", measure.time_signature));
+                previous_time_signature = measure.time_signature;
+            }
+
+            // Accidentals last until the end of the measure
+            let mut measure_accidentals = HashMap::<(usize, u8), i8>::new();
+            let tuplet_runs = get_tuplet_runs(&measure.notes.iter().map(|sn| sn.value).collect::<Vec<NoteValue>>());
+            measure.notes.iter().enumerate().for_each(|(i, sheet_note)| {
+                let length = sheet_note.value.get_relative_duration();
+                let mut chord_names = Vec::new();
+                while let Some((_, chord)) = chord_positions.next_if(|(p, _)| *p < position + length) {
+                    chord_names.push(get_chord_name(chord));
+                }
+                if let Some(chord_name) = chord_names.last() {
+                    line.push_str(&format!("\"{}\"", chord_name));
+                }
+                // (p:q:r puts the next r notes in the tuplet
+                if let Some((_, nb_notes, tuplet)) = tuplet_runs.iter().find(|(start, _, _)| *start == i) {
+                    line.push_str(&format!("({}:{}:{}", tuplet.actual_notes, tuplet.normal_notes, nb_notes));
+                }
+
+                let piano_key = respell_in_key(sheet_note.note, key);
+                let letter = piano_key.note.letter;
+                let alter = piano_key.note.get_alter();
+                let current_alter = *measure_accidentals
+                    .get(&(letter as usize, piano_key.octave))
+                    .unwrap_or(&key_signature[letter as usize]);
+                if alter != current_alter {
+                    line.push_str(match alter {
+                        -2 => "__",
+                        -1 => "_",
+                        1 => "^",
+                        2 => "^^",
+                        _ => "=",
+                    });
+                    measure_accidentals.insert((letter as usize, piano_key.octave), alter);
+                }
+                line.push_str(&get_pitch(piano_key));
+                line.push_str(&get_length(sheet_note.value.get_written_duration()));
+                line.push(' ');
+                position += length;
+            });
+            line.push_str("| ");
+        });
+        ret.push_str(line.trim_end());
+        ret.push('\n');
+    });
+
+    if ret.ends_with("|\n") {
+        ret.truncate(ret.len() - 1);
+        ret.push_str("]\n");
+    }
+    ret
+}
+
+pub fn write_abc_file(path: &str, sheet: &Sheet, chords: Option<(&ChordProgression, &[NoteValue])>, key: Key, tempo: Tempo) -> Result<(), Error> {
+    let mut file = File::create(path)?;
+    file.write_all(sheet_to_abc(sheet, chords, key, tempo).as_bytes())
+}
+
+pub fn sheet_from_abc_file(path: &str) -> Result<(Sheet, Tempo), Error> {
+    let mut file = File::open(path)?;
+    let mut buffer = String::new();
+    file.read_to_string(&mut buffer)?;
+
+    sheet_from_abc(&buffer)
+}
+
+pub fn sheet_from_abc(s: &str) -> Result<(Sheet, Tempo), Error> {
+    let mut tempo = Tempo::from(120);
+    let mut time_signature = TimeSignature::default();
+    let mut unit_length = None;
+    let mut key_signature = [0i8; 7];
+
+    let mut sheet = Sheet::new();
+    let mut pattern = Pattern::new(String::new());
+    let mut measure = Measure::new(time_signature);
+    let mut measure_accidentals = HashMap::<(usize, u8), i8>::new();
+    let mut in_body = false;
+    let mut leading_rest = Rational32::zero();
+
+    for (line_number, line) in s.lines().enumerate() {
+        let line = line.split('%').next().unwrap_or_default().trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        // Information fields, in the header or on their own line in the body
+        if line.len() >= 2 && line.as_bytes()[1] == b':' && line.as_bytes()[0].is_ascii_alphabetic() {
+            let value = line[2..].trim();
+            match &line[0..1] {
+                "M" => {
+                    time_signature = parse_meter(value, line_number)?;
+                    push_measure(&mut pattern, &mut measure, time_signature);
+                }
+                "L" => unit_length = Some(parse_fraction(value, line_number)?),
+                "Q" => tempo = parse_tempo(value, line_number)?,
+                "K" => {
+                    key_signature = parse_key(value, line_number)?;
+                    in_body = true;
+                }
+                "P" if in_body => {
+                    push_measure(&mut pattern, &mut measure, time_signature);
+                    if !pattern.measures.is_empty() {
+                        sheet.add_pattern(pattern);
+                    }
+                    pattern = Pattern::new(value.to_string());
+                }
+                _ => {}
+            }
+            continue;
+        }
+        if !in_body {
+            continue;
+        }
+
+        // Default unit length depends on the meter
+        let unit_length = unit_length.unwrap_or(if Rational32::from(time_signature) < Rational32::new(3, 4) { (1, 16) } else { (1, 8) });
+        let chars = line.chars().collect::<Vec<char>>();
+        let mut i = 0;
+        let mut broken_rhythm: Option<(u32, u32)> = None;
+        // Tuplet of the next notes with the number of notes left in it
+        let mut tuplet: Option<(Tuplet, u32)> = None;
+        // Only the first note of a chord like [CEG] is part of the melody
+        let mut in_chord = false;
+        let mut chord_note_read = false;
+        while i < chars.len() {
+            let c = chars[i];
+            match c {
+                ']' if in_chord => {
+                    in_chord = false;
+                    i += 1;
+                }
+                '|' | ':' | ']' => {
+                    // Bar lines: | || |] [| :| |: ::
+                    push_measure(&mut pattern, &mut measure, time_signature);
+                    measure_accidentals.clear();
+                    // Measures of rests before the first note are dropped
+                    leading_rest = Rational32::zero();
+                    i += 1;
+                }
+                '[' if i + 2 < chars.len() && chars[i + 1].is_ascii_alphabetic() && chars[i + 2] == ':' => {
+                    // Inline field like [M:3/4]
+                    let end = chars[i + 3..].iter().position(|c| *c == ']').map(|p| p + i + 3).unwrap_or(chars.len());
+                    let field = chars[i + 3..end].iter().collect::<String>();
+                    if chars[i + 1] == 'M' {
+                        push_measure(&mut pattern, &mut measure, time_signature);
+                        time_signature = parse_meter(field.trim(), line_number)?;
+                        measure = Measure::new(time_signature);
+                    }
+                    i = end + 1;
+                }
+                '[' if chars.get(i + 1) != Some(&'|') => {
+                    in_chord = true;
+                    chord_note_read = false;
+                    i += 1;
+                }
+                '"' => {
+                    // Chord symbols and annotations are ignored
+                    let end = chars[i + 1..].iter().position(|c| *c == '"').map(|p| p + i + 1).unwrap_or(chars.len());
+                    i = end + 1;
+                }
+                '!' => {
+                    let end = chars[i + 1..].iter().position(|c| *c == '!').map(|p| p + i + 1).unwrap_or(chars.len());
+                    i = end + 1;
+                }
+                '(' if chars.get(i + 1).is_some_and(|c| c.is_ascii_digit()) => {
+                    // Tuplet like (3 or (5:4:5, a ( without a number starts a slur
+                    let end = chars[i + 1..].iter().position(|c| !c.is_ascii_digit() && *c != ':').map(|p| p + i + 1).unwrap_or(chars.len());
+                    tuplet = Some(parse_tuplet(&chars[i + 1..end].iter().collect::<String>(), time_signature, line_number)?);
+                    i = end;
+                }
+                '^' | '_' | '=' | 'A'..='G' | 'a'..='g' | 'z' | 'x' => {
+                    let mut alter = None;
+                    while i < chars.len() && matches!(chars[i], '^' | '_' | '=') {
+                        alter = Some(alter.unwrap_or(0) + match chars[i] {
+                            '^' => 1,
+                            '_' => -1,
+                            _ => 0,
+                        });
+                        i += 1;
+                    }
+                    if i >= chars.len() {
+                        return Err(invalid_abc("accidental without note", line_number));
+                    }
+                    let letter_char = chars[i];
+                    i += 1;
+                    let mut octave: i32 = if letter_char.is_ascii_lowercase() { 5 } else { 4 };
+                    while i < chars.len() && matches!(chars[i], '\'' | ',') {
+                        octave += if chars[i] == '\'' { 1 } else { -1 };
+                        i += 1;
+                    }
+                    let length_start = i;
+                    while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '/') {
+                        i += 1;
+                    }
+                    let (numerator, denominator) = parse_length(&chars[length_start..i].iter().collect::<String>(), line_number)?;
+                    let mut length = get_ratio(numerator.checked_mul(unit_length.0), denominator.checked_mul(unit_length.1))
+                        .ok_or_else(|| invalid_abc("note length is too long", line_number))?;
+                    if let Some((n, d)) = broken_rhythm.take() {
+                        length = length.checked_mul(&Rational32::new(n as i32, d as i32))
+                            .ok_or_else(|| invalid_abc("note length is too long", line_number))?;
+                    }
+                    while i < chars.len() && matches!(chars[i], '>' | '<') {
+                        let nb = chars[i..].iter().take_while(|c| **c == chars[i]).count() as u32;
+                        if nb > 3 {
+                            return Err(invalid_abc("broken rhythm is longer than >>>", line_number));
+                        }
+                        let factor = 1 << nb;
+                        // a>b: a is dotted, b is shortened, a<b is the opposite
+                        let (this, next) = if chars[i] == '>' {
+                            ((2 * factor - 1, factor), (1, factor))
+                        } else {
+                            ((1, factor), (2 * factor - 1, factor))
+                        };
+                        length = length.checked_mul(&Rational32::new(this.0 as i32, this.1 as i32))
+                            .ok_or_else(|| invalid_abc("note length is too long", line_number))?;
+                        broken_rhythm = Some(next);
+                        i += nb as usize;
+                    }
+                    // Notes longer than the measure or not made of sixty-fourths (with dots and broken rhythms)
+                    if length > Rational32::from(time_signature) || *length.denom() > MAX_LENGTH_DENOMINATOR {
+                        return Err(invalid_abc("invalid note length", line_number));
+                    }
+
+                    if in_chord {
+                        if chord_note_read {
+                            continue;
+                        }
+                        chord_note_read = true;
+                    }
+                    let note_tuplet = tuplet.map(|(t, _)| t);
+                    if let Some((_, nb_notes)) = tuplet.as_mut() {
+                        *nb_notes -= 1;
+                        if *nb_notes == 0 {
+                            tuplet = None;
+                        }
+                    }
+                    if letter_char == 'z' || letter_char == 'x' {
+                        // Rest: extend the previous note if there is one, as written in the same tuplet, as played otherwise
+                        let played_length = length * note_tuplet.map(|t| t.get_ratio()).unwrap_or(Rational32::from_integer(1));
+                        if let Some(previous) = measure.notes.last().copied() {
+                            measure.notes.pop();
+                            if previous.value.tuplet == note_tuplet {
+                                add_notes(&mut measure, previous.note, previous.value.get_written_duration() + length, note_tuplet, line_number)?;
+                            } else {
+                                add_notes(&mut measure, previous.note, previous.value.get_relative_duration() + played_length, None, line_number)?;
+                            }
+                        } else if let Some(previous) = get_last_note(&sheet, &pattern) {
+                            // At the start of a measure the previous note is held across the barline
+                            add_notes(&mut measure, previous.note, played_length, None, line_number)?;
+                        } else {
+                            // Before the first note the rest is given to the next note
+                            leading_rest += played_length;
+                        }
+                        continue;
+                    }
+
+                    let letter = NoteLetter::from_str(&letter_char.to_string())?;
+                    let octave = octave.clamp(0, PianoKey::max_octave() as i32) as u8;
+                    let alter = match alter {
+                        Some(a) => {
+                            measure_accidentals.insert((letter as usize, octave), a);
+                            a
+                        }
+                        None => *measure_accidentals.get(&(letter as usize, octave)).unwrap_or(&key_signature[letter as usize]),
+                    };
+                    let piano_key = get_piano_key_with_alter(letter, octave, alter);
+                    if note_tuplet.is_none() {
+                        add_notes(&mut measure, piano_key, leading_rest + length, None, line_number)?;
+                    } else {
+                        add_notes(&mut measure, piano_key, leading_rest, None, line_number)?;
+                        add_notes(&mut measure, piano_key, length, note_tuplet, line_number)?;
+                    }
+                    leading_rest = Rational32::zero();
+                }
+                _ => i += 1, // ties, slurs, spaces, decorations and so on
+            }
+        }
+    }
+
+    push_measure(&mut pattern, &mut measure, time_signature);
+    if !pattern.measures.is_empty() {
+        sheet.add_pattern(pattern);
+    }
+    if sheet.patterns.is_empty() {
+        return Err(Error::new(ErrorKind::InvalidData, "Invalid ABC: no notes found"));
+    }
+
+    Ok((sheet, tempo))
+}
+
+pub fn get_pitch(piano_key: PianoKey) -> String {
+    // C is C4 and c is C5, every ' is an octave up, every , is an octave down
+    let letter = format!("{:?}", piano_key.note.letter);
+    if piano_key.octave >= 5 {
+        format!("{}{}", letter.to_lowercase(), "'".repeat((piano_key.octave - 5) as usize))
+    } else {
+        format!("{}{}", letter, ",".repeat((4 - piano_key.octave) as usize))
+    }
+}
+
+pub fn get_chord_name(chord: &Chord) -> String {
+    use ChordType::*;
+    let suffix = match chord.chord_type {
+        MajorTriad => "",
+        MajorSixth => "6",
+        DominantSeventh => "7",
+        AugmentedTriad => "aug",
+        AugmentedSeventh => "aug7",
+        MinorTriad => "m",
+        MinorSixth => "m6",
+        MinorSeventh => "m7",
+        MinorMajorSeventh => "m(maj7)",
+        DiminishedTriad => "dim",
+        DiminishedSeventh => "dim7",
+        HalfDiminishedSeventh => "m7b5",
+        PowerDiad | PowerTriad => "5",
+        CustomChord => "",
+    };
+    let mut ret = format!("{}{}", chord.base_note.note, suffix);
+    if chord.inversion != ChordInversion::Root {
+        ret.push_str(&format!("/{}", chord.clone().get_keys()[0].note));
+    }
+    ret
+}
+
+// Returns the K: field value and the alteration of each letter (C to B)
+fn get_key_signature(key: Key) -> (String, [i8; 7]) {
+    use Mode::*;
+    let Some(mode) = key.scale.get_signature_mode() else {
+        return (String::from("C"), [0; 7]);
+    };
+
+    let mode_name = match mode {
+        Ionian => "",
+        Dorian => " dor",
+        Phrygian => " phr",
+        Lydian => " lyd",
+        Mixolydian => " mix",
+        Aeolian => "m",
+        Locrian => " loc",
+    };
+
+    let mut tonic = key.base_note.note;
+    let mut fifths = get_note_fifths(tonic) + get_mode_fifths(mode);
+    // Use the enharmonic tonic when there are more than 7 accidentals (G# major is written Ab major)
+    if fifths.abs() > 7 {
+        if fifths > 0 {
+            tonic = Note { letter: tonic.letter.inc(), accidental: Some(Accidental::Flat) };
+        } else {
+            tonic = Note { letter: (0..6).fold(tonic.letter, |l, _| l.inc()), accidental: Some(Accidental::Sharp) };
+        }
+        fifths -= 12 * fifths.signum();
+    }
+
+    (format!("{}{}", tonic, mode_name), get_alters_from_fifths(fifths))
+}
+
+fn get_piano_key_with_alter(letter: NoteLetter, octave: u8, alter: i8) -> PianoKey {
+    match Accidental::from_alter(alter) {
+        Ok(accidental) => PianoKey { note: Note { letter, accidental }, octave },
+        // More than two accidentals (^^^C), use the enharmonic note
+        Err(_) => get_piano_key((get_midi_note_number(PianoKey { note: Note { letter, accidental: None }, octave }) as i32 + alter as i32) as u8),
+    }
+}
+
+// Length in unit notes: "" is 1, "3" is 3, "/" is 1/2, "//" is 1/4, "3/2" is 3/2
+fn get_length(length: Rational32) -> String {
+    let units = length * UNIT_LENGTH as i32;
+    match (*units.numer(), *units.denom()) {
+        (1, 1) => String::new(),
+        (n, 1) => n.to_string(),
+        (1, 2) => String::from("/"),
+        (1, d) => format!("/{}", d),
+        (n, d) => format!("{}/{}", n, d),
+    }
+}
+
+fn parse_length(s: &str, line_number: usize) -> Result<(u32, u32), Error> {
+    if s.is_empty() {
+        return Ok((1, 1));
+    }
+    let slashes = s.matches('/').count() as u32;
+    let mut parts = s.split('/');
+    let numerator = match parts.next().unwrap_or_default() {
+        "" => 1,
+        n => n.parse::<u32>().map_err(|_| invalid_abc("invalid note length", line_number))?,
+    };
+    let denominator = match parts.next_back() {
+        Some("") => 1u32.checked_shl(slashes).ok_or_else(|| invalid_abc("invalid note length", line_number))?,
+        Some(d) => d.parse::<u32>().map_err(|_| invalid_abc("invalid note length", line_number))?,
+        None => 1,
+    };
+    if numerator == 0 || denominator == 0 {
+        return Err(invalid_abc("invalid note length", line_number));
+    }
+    Ok((numerator, denominator))
+}
+
+// None when the numerator or the denominator does not fit
+fn get_ratio(numerator: Option<u32>, denominator: Option<u32>) -> Option<Rational32> {
+    Some(Rational32::new(i32::try_from(numerator?).ok()?, i32::try_from(denominator?).ok()?))
+}
+
+fn parse_fraction(s: &str, line_number: usize) -> Result<(u32, u32), Error> {
+    let (numerator, denominator) = s.split_once('/').ok_or_else(|| invalid_abc("invalid fraction", line_number))?;
+    let numerator = numerator.trim().parse::<u32>().map_err(|_| invalid_abc("invalid fraction", line_number))?;
+    let denominator = denominator.trim().parse::<u32>().map_err(|_| invalid_abc("invalid fraction", line_number))?;
+    if numerator == 0 || denominator == 0 {
+        return Err(invalid_abc("invalid fraction", line_number));
+    }
+    Ok((numerator, denominator))
+}
+
+// (p:q:r puts r notes (p by default) in the time of q, q depends on p and on the meter when it is missing
+fn parse_tuplet(s: &str, time_signature: TimeSignature, line_number: usize) -> Result<(Tuplet, u32), Error> {
+    let invalid = || invalid_abc("invalid tuplet", line_number);
+    let mut parts = s.split(':');
+    let actual_notes = parts.next().unwrap_or_default().parse::<u8>().map_err(|_| invalid())?;
+    let compound = time_signature.numerator > 3 && time_signature.numerator.is_multiple_of(3);
+    let normal_notes = match parts.next() {
+        Some("") | None => match actual_notes {
+            2 | 4 | 8 => 3,
+            3 | 6 => 2,
+            _ if compound => 3,
+            _ => 2,
+        },
+        Some(q) => q.parse::<u8>().map_err(|_| invalid())?,
+    };
+    let nb_notes = match parts.next() {
+        Some("") | None => actual_notes as u32,
+        Some(r) => r.parse::<u32>().map_err(|_| invalid())?,
+    };
+    if nb_notes == 0 || parts.next().is_some() {
+        return Err(invalid());
+    }
+    Ok((Tuplet::new(actual_notes, normal_notes).map_err(|_| invalid())?, nb_notes))
+}
+
+fn parse_meter(s: &str, line_number: usize) -> Result<TimeSignature, Error> {
+    match s {
+        "C" => Ok(TimeSignature::from_str("4/4")?),
+        "C|" => Ok(TimeSignature::from_str("2/2")?),
+        _ => TimeSignature::from_str(s).map_err(|_| invalid_abc("invalid meter", line_number)),
+    }
+}
+
+fn parse_tempo(s: &str, line_number: usize) -> Result<Tempo, Error> {
+    // Q:1/4=120, Q:3/8=40 or just Q:120 (in quarter notes)
+    let (beat, bpm) = match s.split_once('=') {
+        Some((beat, bpm)) => (parse_fraction(beat, line_number)?, bpm),
+        None => ((1, 4), s),
+    };
+    let bpm = bpm.trim().parse::<u32>().map_err(|_| invalid_abc("invalid tempo", line_number))?;
+    let quarters_per_minute = bpm.checked_mul(beat.0).and_then(|n| n.checked_mul(4)).ok_or_else(|| invalid_abc("invalid tempo", line_number))? / beat.1;
+    Ok(Tempo::from(quarters_per_minute.min(u16::MAX as u32) as u16))
+}
+
+fn parse_key(s: &str, line_number: usize) -> Result<[i8; 7], Error> {
+    use Mode::*;
+    let s = s.split_whitespace().collect::<Vec<&str>>().join(" ");
+    if s.is_empty() || s == "none" {
+        return Ok([0; 7]);
+    }
+    let letter_length = s.chars().next().map(char::len_utf8).unwrap_or_default();
+    let tonic_length = if matches!(s[letter_length..].chars().next(), Some('#' | 'b')) { letter_length + 1 } else { letter_length };
+    let tonic = Note::from_str(&s[..tonic_length]).map_err(|_| invalid_abc("invalid key", line_number))?;
+    let mode_str = s[tonic_length..].trim().to_lowercase();
+    let mode = match mode_str.get(..3).unwrap_or(mode_str.as_str()) {
+        "" | "maj" | "ion" => Ionian,
+        "m" | "min" | "aeo" => Aeolian,
+        "dor" => Dorian,
+        "phr" => Phrygian,
+        "lyd" => Lydian,
+        "mix" => Mixolydian,
+        "loc" => Locrian,
+        _ => return Err(invalid_abc("invalid key mode", line_number)),
+    };
+
+    let (_, key_signature) = get_key_signature(Key::new(Scale::Diatonic(mode), PianoKey { note: tonic, octave: 4 }, 1));
+    Ok(key_signature)
+}
+
+// The length is the written one, the notes are played in the time of the tuplet
+fn add_notes(measure: &mut Measure, piano_key: PianoKey, length: Rational32, tuplet: Option<Tuplet>, line_number: usize) -> Result<(), Error> {
+    let note_values = get_note_values(length).into_iter()
+        .map(|nv| NoteValue { tuplet, ..nv })
+        .collect::<Vec<NoteValue>>();
+    if note_values.iter().map(|nv| nv.get_relative_duration()).sum::<Rational32>() > measure.get_remaining_value() {
+        return Err(invalid_abc("measure is longer than the meter", line_number));
+    }
+    note_values.iter().for_each(|nv| measure.add_note(piano_key, *nv));
+    Ok(())
+}
+
+fn get_last_note(sheet: &Sheet, pattern: &Pattern) -> Option<SheetNote> {
+    pattern.measures.last()
+        .or_else(|| sheet.patterns.last().and_then(|p| p.measures.last()))
+        .and_then(|m| m.notes.last())
+        .copied()
+}
+
+fn push_measure(pattern: &mut Pattern, measure: &mut Measure, time_signature: TimeSignature) {
+    if !measure.notes.is_empty() {
+        pattern.add_measure(measure.clone());
+    }
+    *measure = Measure::new(time_signature);
+}
+
+fn invalid_abc(message: &str, line_number: usize) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid ABC at line {}: {}", line_number + 1, message))
+}
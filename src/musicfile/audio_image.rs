@@ -0,0 +1,221 @@
+// Waveform and spectrogram images of the offline render, PNG or SVG (chosen from the file extension)
+// Used to check clipping, aliasing of the saw/square waveforms and the spectral balance
+
+use std::{fs::File, io::{self, Error, Write}};
+
+use rustfft::{num_complex::Complex, FftPlanner};
+
+pub const WAVEFORM_WIDTH: usize = 1200;
+pub const WAVEFORM_HEIGHT: usize = 300;
+pub const FFT_SIZE: usize = 1024;
+// Long renders use a bigger hop so the spectrogram stays readable
+pub const MAX_SPECTROGRAM_WIDTH: usize = 2000;
+const MIN_DECIBELS: f32 = -100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ImageFormat {
+    Png,
+    Svg,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &str) -> Result<Self, Error> {
+        match path.rsplit('.').next().map(|e| e.to_lowercase()).as_deref() {
+            Some("png") => Ok(Self::Png),
+            Some("svg") => Ok(Self::Svg),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a .png or .svg file", path),
+            )),
+        }
+    }
+}
+
+// Minimum and maximum of all the channels for each column of the image
+pub fn get_waveform_peaks(samples: &[f32], channels: u16, width: usize) -> Vec<(f32, f32)> {
+    let nb_frames = samples.len() / channels.max(1) as usize;
+    (0..width)
+        .map(|column| {
+            let start = column * nb_frames / width * channels as usize;
+            let end = ((column + 1) * nb_frames / width * channels as usize).max(start + 1).min(samples.len());
+            samples.get(start..end).unwrap_or(&[]).iter()
+                .fold((0.0f32, 0.0f32), |(min, max), sample| (min.min(*sample), max.max(*sample)))
+        })
+        .collect()
+}
+
+// Magnitude in dBFS of each STFT frame (mono downmix, Hann window), the first bin being 0 Hz
+pub fn get_spectrogram(samples: &[f32], channels: u16, fft_size: usize) -> Vec<Vec<f32>> {
+    let channels = channels.max(1) as usize;
+    let mono = samples.chunks(channels)
+        .map(|frame| frame.iter().sum::<f32>() / channels as f32)
+        .collect::<Vec<f32>>();
+    if mono.len() < fft_size {
+        return Vec::new();
+    }
+    let hop = (fft_size / 2).max((mono.len() - fft_size).div_ceil(MAX_SPECTROGRAM_WIDTH));
+    let window = (0..fft_size)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f32::consts::PI * i as f32 / fft_size as f32).cos())
+        .collect::<Vec<f32>>();
+    // A full scale sine gives 0 dB
+    let normalization = 2.0 / window.iter().sum::<f32>();
+    let fft = FftPlanner::<f32>::new().plan_fft_forward(fft_size);
+
+    (0..=(mono.len() - fft_size) / hop)
+        .map(|frame| {
+            let mut buffer = mono[frame * hop..frame * hop + fft_size].iter()
+                .zip(window.iter())
+                .map(|(sample, w)| Complex::new(sample * w, 0.0))
+                .collect::<Vec<Complex<f32>>>();
+            fft.process(&mut buffer);
+            buffer[..fft_size / 2].iter()
+                .map(|c| (20.0 * (c.norm() * normalization).max(1e-10).log10()).max(MIN_DECIBELS))
+                .collect()
+        })
+        .collect()
+}
+
+pub fn waveform_to_png(samples: &[f32], channels: u16) -> Result<Vec<u8>, Error> {
+    let mut pixels = vec![255u8; WAVEFORM_WIDTH * WAVEFORM_HEIGHT * 3];
+    let mut set_pixel = |x: usize, y: usize, color: [u8; 3]| {
+        let i = (y * WAVEFORM_WIDTH + x) * 3;
+        pixels[i..i + 3].copy_from_slice(&color);
+    };
+    (0..WAVEFORM_WIDTH).for_each(|x| set_pixel(x, WAVEFORM_HEIGHT / 2, [200, 200, 200]));
+    get_waveform_peaks(samples, channels, WAVEFORM_WIDTH).iter().enumerate().for_each(|(x, (min, max))| {
+        let color = if is_clipping(*min, *max) { [211, 47, 47] } else { [30, 100, 180] };
+        (get_waveform_y(*max)..=get_waveform_y(*min)).for_each(|y| set_pixel(x, y, color));
+    });
+    encode_png(&pixels, WAVEFORM_WIDTH, WAVEFORM_HEIGHT)
+}
+
+pub fn waveform_to_svg(samples: &[f32], channels: u16) -> String {
+    let mut ret = get_svg_header(WAVEFORM_WIDTH, WAVEFORM_HEIGHT);
+    ret.push_str(&format!(
+        "  <line x1=\"0\" y1=\"{y}\" x2=\"{}\" y2=\"{y}\" stroke=\"#c8c8c8\"/>\n",
+        WAVEFORM_WIDTH, y = WAVEFORM_HEIGHT / 2
+    ));
+    get_waveform_peaks(samples, channels, WAVEFORM_WIDTH).iter().enumerate().for_each(|(x, (min, max))| {
+        let class = if is_clipping(*min, *max) { "clip\" stroke=\"#d32f2f" } else { "peak\" stroke=\"#1e64b4" };
+        ret.push_str(&format!(
+            "  <line class=\"{}\" x1=\"{x}.5\" y1=\"{}\" x2=\"{x}.5\" y2=\"{}\"/>\n",
+            class, get_waveform_y(*max), get_waveform_y(*min) + 1, x = x
+        ));
+    });
+    ret.push_str("</svg>\n");
+    ret
+}
+
+// One column per STFT frame, one row per bin, low frequencies at the bottom
+pub fn spectrogram_to_png(spectrogram: &[Vec<f32>]) -> Result<Vec<u8>, Error> {
+    let width = spectrogram.len().max(1);
+    let height = spectrogram.first().map(|f| f.len()).unwrap_or(1);
+    let mut pixels = vec![0u8; width * height * 3];
+    spectrogram.iter().enumerate().for_each(|(x, frame)| {
+        frame.iter().enumerate().for_each(|(bin, decibels)| {
+            let i = ((height - 1 - bin) * width + x) * 3;
+            pixels[i..i + 3].copy_from_slice(&get_heat_color(1.0 - decibels / MIN_DECIBELS));
+        });
+    });
+    encode_png(&pixels, width, height)
+}
+
+// The SVG embeds the PNG and adds a frequency axis
+pub fn spectrogram_to_svg(spectrogram: &[Vec<f32>], sample_rate: u32) -> Result<String, Error> {
+    let png = spectrogram_to_png(spectrogram)?;
+    let width = spectrogram.len().max(1);
+    let height = spectrogram.first().map(|f| f.len()).unwrap_or(1);
+    let margin = 60;
+    let mut ret = get_svg_header(width + margin, height);
+    ret.push_str(&format!(
+        "  <image x=\"{}\" y=\"0\" width=\"{}\" height=\"{}\" preserveAspectRatio=\"none\" href=\"data:image/png;base64,{}\"/>\n",
+        margin, width, height, encode_base64(&png)
+    ));
+    let nyquist = sample_rate as f32 / 2.0;
+    (0..=4).for_each(|i| {
+        let frequency = nyquist * i as f32 / 4.0;
+        let y = height as f32 * (1.0 - i as f32 / 4.0);
+        ret.push_str(&format!(
+            "  <text x=\"2\" y=\"{}\" font-family=\"sans-serif\" font-size=\"10\">{} Hz</text>\n",
+            y.clamp(10.0, height as f32 - 2.0), frequency.round()
+        ));
+    });
+    ret.push_str("</svg>\n");
+    Ok(ret)
+}
+
+pub fn write_waveform_file(path: &str, samples: &[f32], channels: u16) -> Result<(), Error> {
+    let data = match ImageFormat::from_path(path)? {
+        ImageFormat::Png => waveform_to_png(samples, channels)?,
+        ImageFormat::Svg => waveform_to_svg(samples, channels).into_bytes(),
+    };
+    let mut file = File::create(path)?;
+    file.write_all(&data)
+}
+
+pub fn write_spectrogram_file(path: &str, samples: &[f32], channels: u16, sample_rate: u32) -> Result<(), Error> {
+    let format = ImageFormat::from_path(path)?;
+    let spectrogram = get_spectrogram(samples, channels, FFT_SIZE);
+    if spectrogram.is_empty() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "The render is too short for a spectrogram"));
+    }
+    let data = match format {
+        ImageFormat::Png => spectrogram_to_png(&spectrogram)?,
+        ImageFormat::Svg => spectrogram_to_svg(&spectrogram, sample_rate)?.into_bytes(),
+    };
+    let mut file = File::create(path)?;
+    file.write_all(&data)
+}
+
+fn is_clipping(min: f32, max: f32) -> bool {
+    min <= -1.0 || max >= 1.0
+}
+
+fn get_waveform_y(value: f32) -> usize {
+    ((1.0 - value.clamp(-1.0, 1.0)) / 2.0 * (WAVEFORM_HEIGHT - 1) as f32).round() as usize
+}
+
+// Black, purple, red, yellow, white from 0.0 to 1.0
+fn get_heat_color(value: f32) -> [u8; 3] {
+    const STOPS: [[f32; 3]; 5] = [[0.0, 0.0, 0.0], [90.0, 20.0, 120.0], [210.0, 50.0, 50.0], [250.0, 200.0, 40.0], [255.0, 255.0, 255.0]];
+    let position = value.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let i = (position as usize).min(STOPS.len() - 2);
+    let t = position - i as f32;
+    let mut ret = [0u8; 3];
+    (0..3).for_each(|c| ret[c] = (STOPS[i][c] + (STOPS[i + 1][c] - STOPS[i][c]) * t).round() as u8);
+    ret
+}
+
+fn get_svg_header(width: usize, height: usize) -> String {
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\n  <rect width=\"{w}\" height=\"{h}\" fill=\"white\"/>\n",
+        w = width, h = height
+    )
+}
+
+fn encode_png(rgb: &[u8], width: usize, height: usize) -> Result<Vec<u8>, Error> {
+    let mut ret = Vec::new();
+    let mut encoder = png::Encoder::new(&mut ret, width as u32, height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(rgb)?;
+    writer.finish()?;
+    Ok(ret)
+}
+
+fn encode_base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut ret = String::with_capacity(data.len().div_ceil(3) * 4);
+    data.chunks(3).for_each(|chunk| {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        (0..4).for_each(|i| {
+            if i <= chunk.len() {
+                ret.push(ALPHABET[(n >> (18 - 6 * i) & 0x3F) as usize] as char);
+            } else {
+                ret.push('=');
+            }
+        });
+    });
+    ret
+}
@@ -0,0 +1,279 @@
+// FLAC export, lossless 16/24 bit PCM with Vorbis comment tags
+// Minimal encoder: each subframe is constant, verbatim or a fixed predictor (order 0 to 4),
+// the residual is Rice coded with the partition order and parameters picked by exact bit cost
+
+use std::{fs::File, io::{self, BufWriter, Error, Write}};
+
+use super::wav::{quantize_samples, WavConfig, WavSampleFormat};
+
+pub const FLAC_BLOCK_SIZE: usize = 4096;
+const MAX_RICE_PARAMETER: u32 = 14;
+const MAX_PARTITION_ORDER: u32 = 6;
+
+pub fn samples_to_flac(samples: &[f32], config: WavConfig, tags: &[(String, String)]) -> Result<Vec<u8>, Error> {
+    config.validate()?;
+    let bits = match config.sample_format {
+        WavSampleFormat::Int16 => 16,
+        WavSampleFormat::Int24 => 24,
+        WavSampleFormat::Float32 => return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "FLAC only supports 16 and 24 bit PCM",
+        )),
+    };
+    let channels = config.channels as usize;
+    let pcm = quantize_samples(samples, config);
+    let nb_samples = pcm.len() / channels;
+
+    let mut ret = Vec::from(&b"fLaC"[..]);
+    // STREAMINFO, only the last block can be shorter, frame sizes and MD5 left to 0 (unknown)
+    let mut stream_info = BitWriter::new();
+    stream_info.write(FLAC_BLOCK_SIZE as u64, 16);
+    stream_info.write(FLAC_BLOCK_SIZE as u64, 16);
+    stream_info.write(0, 24);
+    stream_info.write(0, 24);
+    stream_info.write(config.sample_rate as u64, 20);
+    stream_info.write((channels - 1) as u64, 3);
+    stream_info.write((bits - 1) as u64, 5);
+    stream_info.write(nb_samples as u64, 36);
+    stream_info.write(0, 64);
+    stream_info.write(0, 64);
+    push_metadata_block(&mut ret, 0, false, &stream_info.into_bytes());
+    push_metadata_block(&mut ret, 4, true, &get_vorbis_comment(tags));
+
+    pcm.chunks(FLAC_BLOCK_SIZE * channels)
+        .enumerate()
+        .for_each(|(frame_number, block)| ret.extend(encode_frame(block, channels, bits, frame_number as u64)));
+
+    Ok(ret)
+}
+
+pub fn write_flac_file(path: &str, samples: &[f32], config: WavConfig, tags: &[(String, String)]) -> Result<(), Error> {
+    let flac = samples_to_flac(samples, config, tags)?;
+    let file = File::create(path).map_err(|e| io::Error::new(e.kind(), format!("Cannot create {}: {}", path, e)))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&flac)?;
+    writer.flush()
+}
+
+fn push_metadata_block(ret: &mut Vec<u8>, block_type: u8, is_last: bool, data: &[u8]) {
+    ret.push(block_type | if is_last { 0x80 } else { 0 });
+    ret.extend_from_slice(&(data.len() as u32).to_be_bytes()[1..4]);
+    ret.extend_from_slice(data);
+}
+
+// Vorbis comment lengths are little endian, unlike the rest of the stream
+fn get_vorbis_comment(tags: &[(String, String)]) -> Vec<u8> {
+    let vendor = format!("pmusic {}", env!("CARGO_PKG_VERSION"));
+    let mut ret = Vec::new();
+    ret.extend_from_slice(&(vendor.len() as u32).to_le_bytes());
+    ret.extend_from_slice(vendor.as_bytes());
+    ret.extend_from_slice(&(tags.len() as u32).to_le_bytes());
+    tags.iter().for_each(|(name, value)| {
+        let comment = format!("{}={}", name.to_uppercase(), value);
+        ret.extend_from_slice(&(comment.len() as u32).to_le_bytes());
+        ret.extend_from_slice(comment.as_bytes());
+    });
+    ret
+}
+
+fn encode_frame(block: &[i32], channels: usize, bits: u32, frame_number: u64) -> Vec<u8> {
+    let block_size = block.len() / channels;
+    let mut writer = BitWriter::new();
+    writer.write(0b11111111111110, 14);
+    writer.write(0, 1); // reserved
+    writer.write(0, 1); // fixed block size
+    writer.write(0b0111, 4); // block size - 1 on 16 bits at the end of the header
+    writer.write(0b0000, 4); // sample rate from STREAMINFO
+    writer.write((channels - 1) as u64, 4); // independent channels
+    writer.write(if bits == 16 { 0b100 } else { 0b110 }, 3);
+    writer.write(0, 1); // reserved
+    write_utf8_number(&mut writer, frame_number);
+    writer.write((block_size - 1) as u64, 16);
+    let crc8 = get_crc8(writer.bytes());
+    writer.write(crc8 as u64, 8);
+
+    (0..channels).for_each(|channel| {
+        let channel_samples = block.iter().skip(channel).step_by(channels).copied().collect::<Vec<i32>>();
+        encode_subframe(&mut writer, &channel_samples, bits);
+    });
+    writer.align();
+    let crc16 = get_crc16(writer.bytes());
+    writer.write(crc16 as u64, 16);
+    writer.into_bytes()
+}
+
+fn encode_subframe(writer: &mut BitWriter, samples: &[i32], bits: u32) {
+    if samples.iter().all(|s| *s == samples[0]) {
+        writer.write(0b0000000, 7);
+        writer.write(0, 1);
+        writer.write_signed(samples[0] as i64, bits);
+        return;
+    }
+
+    let verbatim_cost = samples.len() as u64 * bits as u64;
+    let best_fixed = (0..=4usize)
+        .filter(|order| *order < samples.len())
+        .map(|order| {
+            let residual = get_fixed_residual(samples, order);
+            let (cost, partition_order, parameters) = get_best_rice_partition(&residual, order, samples.len());
+            (order as u64 * bits as u64 + cost, order, residual, partition_order, parameters)
+        })
+        .min_by_key(|(cost, ..)| *cost);
+
+    match best_fixed {
+        Some((cost, order, residual, partition_order, parameters)) if cost < verbatim_cost => {
+            writer.write(0b0001000 | order as u64, 7);
+            writer.write(0, 1);
+            samples[..order].iter().for_each(|s| writer.write_signed(*s as i64, bits));
+            writer.write(0b00, 2); // Rice coding with 4 bit parameters
+            writer.write(partition_order as u64, 4);
+            let partition_size = samples.len() >> partition_order;
+            let mut start = 0;
+            parameters.iter().enumerate().for_each(|(i, parameter)| {
+                let length = if i == 0 { partition_size - order } else { partition_size };
+                writer.write(*parameter as u64, 4);
+                residual[start..start + length].iter().for_each(|r| writer.write_rice(*r, *parameter));
+                start += length;
+            });
+        },
+        _ => {
+            writer.write(0b0000010, 7);
+            writer.write(0, 1);
+            samples.iter().for_each(|s| writer.write_signed(*s as i64, bits));
+        },
+    }
+}
+
+// Residual of the fixed polynomial predictors, without the warm-up samples
+fn get_fixed_residual(samples: &[i32], order: usize) -> Vec<i32> {
+    (order..samples.len())
+        .map(|i| {
+            let s = |j: usize| samples[i - j] as i64;
+            (match order {
+                0 => s(0),
+                1 => s(0) - s(1),
+                2 => s(0) - 2 * s(1) + s(2),
+                3 => s(0) - 3 * s(1) + 3 * s(2) - s(3),
+                _ => s(0) - 4 * s(1) + 6 * s(2) - 4 * s(3) + s(4),
+            }) as i32
+        })
+        .collect()
+}
+
+// Returns the cost in bits of the residual, the partition order and the Rice parameter of each partition
+fn get_best_rice_partition(residual: &[i32], order: usize, block_size: usize) -> (u64, u32, Vec<u32>) {
+    (0..=MAX_PARTITION_ORDER)
+        .filter(|partition_order| block_size.is_multiple_of(1 << partition_order) && (block_size >> partition_order) > order)
+        .map(|partition_order| {
+            let partition_size = block_size >> partition_order;
+            let mut start = 0;
+            let mut cost = 6; // coding method and partition order
+            let parameters = (0..1usize << partition_order)
+                .map(|i| {
+                    let length = if i == 0 { partition_size - order } else { partition_size };
+                    let (parameter_cost, parameter) = get_best_rice_parameter(&residual[start..start + length]);
+                    start += length;
+                    cost += 4 + parameter_cost;
+                    parameter
+                })
+                .collect::<Vec<u32>>();
+            (cost, partition_order, parameters)
+        })
+        .min_by_key(|(cost, ..)| *cost)
+        .unwrap_or((u64::MAX, 0, Vec::new()))
+}
+
+fn get_best_rice_parameter(residual: &[i32]) -> (u64, u32) {
+    let folded = residual.iter().map(|r| zigzag(*r) as u64).collect::<Vec<u64>>();
+    (0..=MAX_RICE_PARAMETER)
+        .map(|parameter| {
+            let cost = folded.iter().fold(0, |sum, u| sum + (u >> parameter) + 1 + parameter as u64);
+            (cost, parameter)
+        })
+        .min()
+        .unwrap_or((0, 0))
+}
+
+fn zigzag(value: i32) -> u32 {
+    ((value << 1) ^ (value >> 31)) as u32
+}
+
+// Frame numbers use the same variable length coding as UTF-8, extended to 36 bits
+fn write_utf8_number(writer: &mut BitWriter, value: u64) {
+    if value < 0x80 {
+        writer.write(value, 8);
+        return;
+    }
+    let nb_bytes = (2..=7u32).find(|n| value < 1 << (5 * n + 1)).unwrap_or(7);
+    // nb_bytes leading ones, then the remaining high bits of the value
+    let prefix = (0xFF00u64 >> nb_bytes) & 0xFF;
+    writer.write(prefix | (value >> (6 * (nb_bytes - 1))), 8);
+    (0..nb_bytes - 1).rev().for_each(|i| writer.write(0x80 | ((value >> (6 * i)) & 0x3F), 8));
+}
+
+fn get_crc8(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |crc, byte| {
+        (0..8).fold(crc ^ byte, |crc, _| if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 })
+    })
+}
+
+fn get_crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0u16, |crc, byte| {
+        (0..8).fold(crc ^ ((*byte as u16) << 8), |crc, _| if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 })
+    })
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    accumulator: u64,
+    nb_bits: u32,
+}
+
+impl BitWriter {
+    fn new() -> Self {
+        Self { bytes: Vec::new(), accumulator: 0, nb_bits: 0 }
+    }
+
+    // Only complete bytes, the caller aligns before reading them for a CRC
+    fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    fn write(&mut self, value: u64, nb_bits: u32) {
+        (0..nb_bits).rev().for_each(|i| {
+            self.accumulator = (self.accumulator << 1) | ((value >> i) & 1);
+            self.nb_bits += 1;
+            if self.nb_bits == 8 {
+                self.bytes.push(self.accumulator as u8);
+                self.accumulator = 0;
+                self.nb_bits = 0;
+            }
+        });
+    }
+
+    fn write_signed(&mut self, value: i64, nb_bits: u32) {
+        self.write(value as u64 & ((1 << nb_bits) - 1), nb_bits);
+    }
+
+    fn write_rice(&mut self, value: i32, parameter: u32) {
+        let folded = zigzag(value);
+        let mut quotient = folded >> parameter;
+        while quotient >= 32 {
+            self.write(0, 32);
+            quotient -= 32;
+        }
+        self.write(1, quotient + 1);
+        self.write((folded & ((1 << parameter) - 1)) as u64, parameter);
+    }
+
+    fn align(&mut self) {
+        if self.nb_bits > 0 {
+            self.write(0, 8 - self.nb_bits);
+        }
+    }
+
+    fn into_bytes(mut self) -> Vec<u8> {
+        self.align();
+        self.bytes
+    }
+}
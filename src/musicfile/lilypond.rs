@@ -0,0 +1,160 @@
+// LilyPond export: a melody staff with a \chordmode line for the chord progression above it
+// Pitches are written in absolute mode (c' is C4) with the default (dutch) note names
+
+use std::{fs::File, io::{Error, Write}};
+
+use crate::musictheory::{
+    chord::{ChordInversion, ChordType}, chord_progression::ChordProgression, key::Key, mode::Mode, note::{Accidental, Note},
+    note_value::{NoteValue, Tuplet}, piano_key::PianoKey, scale::Scale, sheet::Sheet, spelling::respell_in_key, tempo::Tempo, time_signature::TimeSignature
+};
+
+use super::{get_chord_timeline, get_sheet_length, get_tuplet_runs};
+
+pub const LILYPOND_VERSION: &str = "2.24.0";
+
+pub fn sheet_to_lilypond(sheet: &Sheet, chords: Option<(&ChordProgression, &[NoteValue])>, key: Key, tempo: Tempo) -> String {
+    let mut ret = format!("\\version \"{}\"\n\n", LILYPOND_VERSION);
+    ret.push_str("<<\n");
+
+    if let Some((chord_progression, rhythm_pattern)) = chords {
+        let length = get_sheet_length(sheet);
+        let chord_names = get_chord_timeline(chord_progression, rhythm_pattern, length).iter()
+            .enumerate()
+            .filter(|(_, (start, _, _))| *start < length)
+            .map(|(i, (_, _, chord))| {
+                let note_value = rhythm_pattern[i % rhythm_pattern.len()];
+                let mut chord_name = format!("{}{}", get_note_name(chord.base_note.note), get_duration(note_value));
+                let modifier = get_chord_modifier(chord.chord_type);
+                if !modifier.is_empty() {
+                    chord_name.push_str(&format!(":{}", modifier));
+                }
+                if chord.inversion != ChordInversion::Root {
+                    chord_name.push_str(&format!("/{}", get_note_name((*chord).clone().get_keys()[0].note)));
+                }
+                match note_value.tuplet {
+                    Some(tuplet) => format!("{} {{ {} }}", get_tuplet(tuplet), chord_name),
+                    None => chord_name,
+                }
+            })
+            .collect::<Vec<String>>();
+        ret.push_str(&format!("  \\new ChordNames \\chordmode {{\n    {}\n  }}\n", chord_names.join(" ")));
+    }
+
+    ret.push_str("  \\new Staff {\n");
+    if let Some(mode) = get_key_mode(key.scale) {
+        ret.push_str(&format!("    \\key {} \\{}\n", get_note_name(key.base_note.note), mode));
+    }
+    ret.push_str(&format!("    \\tempo 4 = {}\n", u16::from(tempo)));
+
+    let mut previous_time_signature = None;
+    sheet.patterns.iter().for_each(|pattern| {
+        if !pattern.name.is_empty() {
+            ret.push_str(&format!("    % {}\n", pattern.name));
+        }
+        pattern.measures.iter().for_each(|measure| {
+            if previous_time_signature != Some(measure.time_signature) {
+                ret.push_str(&format!("    \\time {}\n", get_time_signature(measure.time_signature)));
+                previous_time_signature = Some(measure.time_signature);
+            }
+            let tuplet_runs = get_tuplet_runs(&measure.notes.iter().map(|sn| sn.value).collect::<Vec<NoteValue>>());
+            let notes = measure.notes.iter()
+                .enumerate()
+                .map(|(i, sn)| {
+                    let mut note = format!("{}{}", get_pitch(respell_in_key(sn.note, key)), get_duration(sn.value));
+                    if let Some((_, _, tuplet)) = tuplet_runs.iter().find(|(start, _, _)| *start == i) {
+                        note = format!("{} {{ {}", get_tuplet(*tuplet), note);
+                    }
+                    if tuplet_runs.iter().any(|(start, nb_notes, _)| start + nb_notes - 1 == i) {
+                        note.push_str(" }");
+                    }
+                    note
+                })
+                .collect::<Vec<String>>();
+            ret.push_str(&format!("    {} |\n", notes.join(" ")));
+        });
+    });
+    ret.push_str("    \\bar \"|.\"\n  }\n>>\n");
+
+    ret
+}
+
+pub fn write_lilypond_file(path: &str, sheet: &Sheet, chords: Option<(&ChordProgression, &[NoteValue])>, key: Key, tempo: Tempo) -> Result<(), Error> {
+    let mut file = File::create(path)?;
+    file.write_all(sheet_to_lilypond(sheet, chords, key, tempo).as_bytes())
+}
+
+pub fn get_note_name(note: Note) -> String {
+    let accidental = match note.accidental {
+        Some(Accidental::DoubleSharp) => "isis",
+        Some(Accidental::Sharp) => "is",
+        Some(Accidental::Flat) => "es",
+        Some(Accidental::DoubleFlat) => "eses",
+        None => "",
+    };
+    format!("{:?}{}", note.letter, accidental).to_lowercase()
+}
+
+pub fn get_pitch(piano_key: PianoKey) -> String {
+    // c is C3 in absolute mode, every ' is an octave up, every , is an octave down
+    let octave_marks = if piano_key.octave >= 3 {
+        "'".repeat((piano_key.octave - 3) as usize)
+    } else {
+        ",".repeat((3 - piano_key.octave) as usize)
+    };
+    format!("{}{}", get_note_name(piano_key.note), octave_marks)
+}
+
+// Written duration, the tuplets are wrapped around the notes
+pub fn get_duration(note_value: NoteValue) -> String {
+    let nb_dots = note_value.dotted.map(|d| d.get_nb_dots()).unwrap_or_default();
+    format!("{}{}", i8::from(note_value.base), ".".repeat(nb_dots as usize))
+}
+
+// "\tuplet 3/2" for a triplet
+fn get_tuplet(tuplet: Tuplet) -> String {
+    format!("\\tuplet {}/{}", tuplet.actual_notes, tuplet.normal_notes)
+}
+
+// "\time 2,2,3 7/8" sets the beat grouping
+fn get_time_signature(time_signature: TimeSignature) -> String {
+    let (numerator, denominator) = time_signature.get_fraction();
+    if time_signature.has_default_grouping() {
+        return format!("{}/{}", numerator, denominator);
+    }
+    let grouping = time_signature.get_grouping().iter().map(|g| g.to_string()).collect::<Vec<String>>();
+    format!("{} {}/{}", grouping.join(","), numerator, denominator)
+}
+
+fn get_key_mode(scale: Scale) -> Option<&'static str> {
+    use Mode::*;
+    scale.get_signature_mode().map(|mode| match mode {
+        Ionian => "major",
+        Dorian => "dorian",
+        Phrygian => "phrygian",
+        Lydian => "lydian",
+        Mixolydian => "mixolydian",
+        Aeolian => "minor",
+        Locrian => "locrian",
+    })
+}
+
+fn get_chord_modifier(chord_type: ChordType) -> &'static str {
+    use ChordType::*;
+    match chord_type {
+        MajorTriad => "",
+        MajorSixth => "6",
+        DominantSeventh => "7",
+        AugmentedTriad => "aug",
+        AugmentedSeventh => "aug7",
+        MinorTriad => "m",
+        MinorSixth => "m6",
+        MinorSeventh => "m7",
+        MinorMajorSeventh => "m7+",
+        DiminishedTriad => "dim",
+        DiminishedSeventh => "dim7",
+        HalfDiminishedSeventh => "m7.5-",
+        PowerDiad => "5",
+        PowerTriad => "1.5.8",
+        CustomChord => "1",
+    }
+}
@@ -0,0 +1,393 @@
+// Standard MIDI File (SMF) import and export
+// Export is format 1: the first track only holds the tempo and time signature meta events,
+// then one track per part (melody, chords)
+// Import reads the melody of the first track with notes, quantized to sixteenth notes
+// or to the unit of the time signature when it is shorter
+
+use std::{fs::File, io::{Error, ErrorKind, Read, Write}, str::FromStr};
+
+use num::{rational::Rational32, Zero};
+
+use crate::musictheory::{
+    chord_progression::ChordProgression, measure::Measure, note_value::NoteValue, pattern::Pattern, 
+    piano_key::PianoKey, sheet::Sheet, tempo::Tempo, time_signature::{BeatAccent, TimeSignature}
+};
+
+use super::{get_chord_timeline, get_note_values};
+
+pub const TICKS_PER_QUARTER: u16 = 480;
+const MELODY_CHANNEL: u8 = 0;
+const CHORD_CHANNEL: u8 = 1;
+// Melody velocity of the strong, medium and weak beats, and of the notes between the beats
+const MELODY_VELOCITIES: [u8; 4] = [100, 90, 80, 70];
+const CHORD_VELOCITY: u8 = 80;
+
+struct MidiEvent {
+    tick: u32,
+    data: Vec<u8>,
+}
+
+pub fn get_midi_note_number(piano_key: PianoKey) -> u8 {
+    piano_key.get_midi_note_number()
+}
+
+// Notes outside of the keyboard are moved to the nearest octave
+pub fn get_piano_key(midi_note_number: u8) -> PianoKey {
+    let octave = (midi_note_number / 12).saturating_sub(1).min(PianoKey::max_octave());
+    PianoKey::from_midi_note_number((octave + 1) * 12 + midi_note_number % 12).unwrap()
+}
+
+pub fn get_ticks(note_value: NoteValue) -> u32 {
+    get_relative_ticks(note_value.get_relative_duration())
+}
+
+// Rounded to the nearest tick for tuplets which do not divide the quarter
+fn get_relative_ticks(relative_duration: Rational32) -> u32 {
+    (relative_duration * 4 * TICKS_PER_QUARTER as i32).round().to_integer() as u32
+}
+
+pub fn sheet_to_midi(sheet: &Sheet, chords: Option<(&ChordProgression, &[NoteValue])>, tempo: Tempo, time_signature: TimeSignature) -> Vec<u8> {
+    let mut tracks = vec![conductor_track(tempo, time_signature)];
+    let (melody_events, melody_length) = melody_events(sheet);
+    tracks.push(track_from_events("Melody", melody_events));
+
+    if let Some((chord_progression, rhythm_pattern)) = chords {
+        tracks.push(track_from_events("Chords", chord_events(chord_progression, rhythm_pattern, melody_length)));
+    }
+
+    let mut ret = Vec::new();
+    ret.extend_from_slice(b"MThd");
+    ret.extend_from_slice(&6u32.to_be_bytes());
+    ret.extend_from_slice(&1u16.to_be_bytes());
+    ret.extend_from_slice(&(tracks.len() as u16).to_be_bytes());
+    ret.extend_from_slice(&TICKS_PER_QUARTER.to_be_bytes());
+    tracks.iter().for_each(|track| {
+        ret.extend_from_slice(b"MTrk");
+        ret.extend_from_slice(&(track.len() as u32).to_be_bytes());
+        ret.extend_from_slice(track);
+    });
+
+    ret
+}
+
+pub fn write_midi_file(path: &str, sheet: &Sheet, chords: Option<(&ChordProgression, &[NoteValue])>, tempo: Tempo, time_signature: TimeSignature) -> Result<(), Error> {
+    let mut file = File::create(path)?;
+    file.write_all(&sheet_to_midi(sheet, chords, tempo, time_signature))
+}
+
+fn conductor_track(tempo: Tempo, time_signature: TimeSignature) -> Vec<u8> {
+    let microseconds_per_quarter = 60_000_000 / u16::from(tempo).max(1) as u32;
+    let (numerator, denominator) = time_signature.get_fraction();
+
+    let mut tempo_event = vec![0xFF, 0x51, 0x03];
+    tempo_event.extend_from_slice(&microseconds_per_quarter.to_be_bytes()[1..]);
+    let time_signature_event = vec![
+        0xFF, 0x58, 0x04,
+        numerator as u8,
+        denominator.trailing_zeros() as u8, // denominator is stored as a power of 2
+        24, // MIDI clocks per metronome click
+        8, // 32nd notes per quarter
+    ];
+
+    track_from_events("pmusic", vec![
+        MidiEvent { tick: 0, data: tempo_event },
+        MidiEvent { tick: 0, data: time_signature_event },
+    ])
+}
+
+fn melody_events(sheet: &Sheet) -> (Vec<MidiEvent>, u32) {
+    let mut events = Vec::new();
+    // Ticks are computed from the exact start of each note so that rounded tuplets do not drift
+    let mut start = Rational32::zero();
+
+    sheet.patterns.iter()
+        .flat_map(|p| p.measures.iter())
+        .for_each(|measure| {
+            let mut position = Rational32::zero();
+            measure.notes.iter().for_each(|sheet_note| {
+                let key = get_midi_note_number(sheet_note.note);
+                let tick = get_relative_ticks(start);
+                let end = get_relative_ticks(start + sheet_note.value.get_relative_duration());
+                let velocity = match measure.time_signature.get_accent(position) {
+                    Some(BeatAccent::Strong) => MELODY_VELOCITIES[0],
+                    Some(BeatAccent::Medium) => MELODY_VELOCITIES[1],
+                    Some(BeatAccent::Weak) => MELODY_VELOCITIES[2],
+                    None => MELODY_VELOCITIES[3],
+                };
+                events.push(MidiEvent { tick, data: vec![0x90 | MELODY_CHANNEL, key, velocity] });
+                events.push(MidiEvent { tick: end, data: vec![0x80 | MELODY_CHANNEL, key, 0] });
+                start += sheet_note.value.get_relative_duration();
+                position += sheet_note.value.get_relative_duration();
+            });
+        });
+
+    (events, get_relative_ticks(start))
+}
+
+fn chord_events(chord_progression: &ChordProgression, rhythm_pattern: &[NoteValue], length: u32) -> Vec<MidiEvent> {
+    let mut events = Vec::new();
+    let relative_length = Rational32::new(length as i32, 4 * TICKS_PER_QUARTER as i32);
+    get_chord_timeline(chord_progression, rhythm_pattern, relative_length).iter().for_each(|(start, duration, chord)| {
+        let tick = get_relative_ticks(*start);
+        let end = get_relative_ticks(start + duration);
+        let keys = (*chord).clone().get_keys();
+        keys.iter().for_each(|k| {
+            events.push(MidiEvent { tick, data: vec![0x90 | CHORD_CHANNEL, get_midi_note_number(*k), CHORD_VELOCITY] });
+        });
+        keys.iter().for_each(|k| {
+            events.push(MidiEvent { tick: end, data: vec![0x80 | CHORD_CHANNEL, get_midi_note_number(*k), 0] });
+        });
+    });
+
+    events
+}
+
+fn track_from_events(name: &str, mut events: Vec<MidiEvent>) -> Vec<u8> {
+    // Note off must come before note on on the same tick, otherwise repeated notes are cut
+    events.sort_by_key(|e| (e.tick, e.data[0] & 0xF0 != 0x80));
+
+    let mut track = Vec::new();
+    write_variable_length(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x03]);
+    write_variable_length(&mut track, name.len() as u32);
+    track.extend_from_slice(name.as_bytes());
+
+    let mut last_tick = 0;
+    events.iter().for_each(|e| {
+        write_variable_length(&mut track, e.tick - last_tick);
+        track.extend_from_slice(&e.data);
+        last_tick = e.tick;
+    });
+
+    // End of track
+    write_variable_length(&mut track, 0);
+    track.extend_from_slice(&[0xFF, 0x2F, 0x00]);
+    track
+}
+
+fn write_variable_length(buffer: &mut Vec<u8>, value: u32) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut value = value >> 7;
+    while value > 0 {
+        bytes.push(((value & 0x7F) as u8) | 0x80);
+        value >>= 7;
+    }
+    bytes.reverse();
+    buffer.extend_from_slice(&bytes);
+}
+
+pub fn sheet_from_midi_file(path: &str) -> Result<(Sheet, Tempo), Error> {
+    let mut file = File::open(path)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+
+    sheet_from_midi(&buffer)
+}
+
+pub fn sheet_from_midi(data: &[u8]) -> Result<(Sheet, Tempo), Error> {
+    let mut reader = MidiReader { data, position: 0 };
+    if reader.read_bytes(4)? != b"MThd" {
+        return Err(invalid_midi("missing MThd header"));
+    }
+    let header_length = reader.read_u32()? as usize;
+    let header = reader.read_bytes(header_length)?;
+    if header_length < 6 {
+        return Err(invalid_midi("header is too short"));
+    }
+    let nb_tracks = u16::from_be_bytes([header[2], header[3]]);
+    let division = u16::from_be_bytes([header[4], header[5]]);
+    if division & 0x8000 != 0 || division == 0 {
+        return Err(invalid_midi("SMPTE time division is not supported"));
+    }
+
+    let mut tempo = Tempo::from(120);
+    let mut time_signature = TimeSignature::default();
+    let mut melody = Vec::<(u32, u8)>::new();
+    let mut melody_end = 0;
+
+    for _ in 0..nb_tracks {
+        let chunk_type = reader.read_bytes(4)?;
+        let chunk_length = reader.read_u32()? as usize;
+        let chunk = reader.read_bytes(chunk_length)?;
+        if chunk_type != b"MTrk" {
+            continue;
+        }
+
+        let track = read_track(chunk)?;
+        if let Some(t) = track.tempo {
+            tempo = t;
+        }
+        if let Some(ts) = track.time_signature {
+            time_signature = ts;
+        }
+        if melody.is_empty() {
+            melody = track.notes;
+            melody_end = track.last_note_off;
+        }
+    }
+
+    if melody.is_empty() {
+        return Err(invalid_midi("no notes found"));
+    }
+
+    Ok((sheet_from_notes(&melody, melody_end, division, time_signature), tempo))
+}
+
+struct MidiReader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> MidiReader<'a> {
+    fn is_finished(&self) -> bool {
+        self.position >= self.data.len()
+    }
+
+    fn read_bytes(&mut self, length: usize) -> Result<&'a [u8], Error> {
+        if self.position + length > self.data.len() {
+            return Err(invalid_midi("unexpected end of file"));
+        }
+        let ret = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(ret)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, Error> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, Error> {
+        let bytes = self.read_bytes(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn read_variable_length(&mut self) -> Result<u32, Error> {
+        let mut value = 0u32;
+        for _ in 0..4 {
+            let byte = self.read_u8()?;
+            value = (value << 7) | (byte & 0x7F) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(invalid_midi("variable length quantity is too long"))
+    }
+}
+
+struct MidiTrack {
+    tempo: Option<Tempo>,
+    time_signature: Option<TimeSignature>,
+    notes: Vec<(u32, u8)>, // (tick, midi note number) of each note on
+    last_note_off: u32,
+}
+
+fn read_track(chunk: &[u8]) -> Result<MidiTrack, Error> {
+    let mut reader = MidiReader { data: chunk, position: 0 };
+    let mut track = MidiTrack { tempo: None, time_signature: None, notes: Vec::new(), last_note_off: 0 };
+    let mut tick = 0u32;
+    let mut running_status = 0u8;
+
+    while !reader.is_finished() {
+        tick = tick.checked_add(reader.read_variable_length()?).ok_or_else(|| invalid_midi("track is too long"))?;
+        let mut status = reader.read_u8()?;
+        if status < 0x80 {
+            // Running status, the byte we just read is the first data byte
+            if running_status == 0 {
+                return Err(invalid_midi("data byte without status"));
+            }
+            reader.position -= 1;
+            status = running_status;
+        }
+
+        match status {
+            0xFF => {
+                let meta_type = reader.read_u8()?;
+                let length = reader.read_variable_length()? as usize;
+                let meta = reader.read_bytes(length)?;
+                match meta_type {
+                    0x2F => break,
+                    0x51 if length == 3 => {
+                        let microseconds_per_quarter = u32::from_be_bytes([0, meta[0], meta[1], meta[2]]).max(1);
+                        track.tempo = Some(Tempo::from((60_000_000 / microseconds_per_quarter).min(u16::MAX as u32) as u16));
+                    }
+                    0x58 if length >= 2 => {
+                        track.time_signature = TimeSignature::from_str(&format!("{}/{}", meta[0], 1u32 << meta[1].min(31))).ok();
+                    }
+                    _ => {}
+                }
+            }
+            0xF0 | 0xF7 => {
+                let length = reader.read_variable_length()? as usize;
+                reader.read_bytes(length)?;
+            }
+            _ => {
+                running_status = status;
+                let data_length = if matches!(status & 0xF0, 0xC0 | 0xD0) { 1 } else { 2 };
+                let data = reader.read_bytes(data_length)?;
+                // Note on with a velocity of 0 is a note off, channel 10 is for drums
+                if status & 0x0F != 9 {
+                    if status & 0xF0 == 0x90 && data[1] > 0 {
+                        track.notes.push((tick, data[0]));
+                    } else if matches!(status & 0xF0, 0x80 | 0x90) {
+                        track.last_note_off = tick;
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(track)
+}
+
+fn sheet_from_notes(notes: &[(u32, u8)], end: u32, division: u16, time_signature: TimeSignature) -> Sheet {
+    let mut sheet = Sheet::new();
+    let mut pattern = Pattern::new(String::from("MIDI"));
+
+    // Quantize note on to the grid, for chords we keep the highest note
+    let grid = (time_signature.denominator as u32).max(16);
+    let quantize = |tick: u32| ((tick as f64 * grid as f64) / (division as f64 * 4.0)).round() as u32;
+    let mut quantized = Vec::<(u32, u8)>::new();
+    notes.iter().for_each(|(tick, key)| {
+        let position = quantize(*tick);
+        match quantized.iter_mut().find(|(p, _)| *p == position) {
+            Some(q) => q.1 = q.1.max(*key),
+            None => quantized.push((position, *key)),
+        }
+    });
+    quantized.sort_by_key(|(p, _)| *p);
+
+    // The sheet has no rests, so each note lasts until the next one, and the melody starts on the first note
+    let measure_length = time_signature.numerator as u32 * grid / time_signature.denominator as u32;
+    let mut measure = Measure::new(time_signature);
+    let mut measure_position = 0;
+    for (i, (position, key)) in quantized.iter().enumerate() {
+        let mut length = match quantized.get(i + 1) {
+            Some((next_position, _)) => next_position - position,
+            None => quantize(end).saturating_sub(*position).max(1),
+        };
+
+        while length > 0 {
+            let split_length = length.min(measure_length - measure_position);
+            get_note_values(Rational32::new(split_length as i32, grid as i32)).iter().for_each(|value| {
+                measure.add_note(get_piano_key(*key), *value);
+            });
+            length -= split_length;
+            measure_position += split_length;
+            if measure_position == measure_length {
+                pattern.add_measure(measure.clone());
+                measure = Measure::new(time_signature);
+                measure_position = 0;
+            }
+        }
+    }
+    if !measure.notes.is_empty() {
+        pattern.add_measure(measure);
+    }
+
+    sheet.add_pattern(pattern);
+    sheet
+}
+
+fn invalid_midi(message: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid MIDI file: {}", message))
+}
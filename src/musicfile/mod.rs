@@ -0,0 +1,128 @@
+pub mod midi;
+pub mod musicxml;
+pub mod lilypond;
+pub mod abc;
+pub mod scala;
+pub mod text_sheet;
+pub mod wav;
+pub mod flac;
+pub mod render;
+pub mod pcm;
+pub mod piano_roll;
+pub mod audio_image;
+
+use num::{rational::Rational32, Zero};
+
+use crate::musictheory::{
+    chord::Chord, chord_progression::ChordProgression, mode::Mode, note::{Note, NoteLetter}, note_value::{NoteValue, NoteValueBase, NoteValueDotted, Tuplet}, sheet::Sheet
+};
+
+pub fn get_sheet_length(sheet: &Sheet) -> Rational32 {
+    sheet.patterns.iter()
+        .flat_map(|p| p.measures.iter())
+        .flat_map(|m| m.notes.iter())
+        .fold(Rational32::zero(), |sum, sn| sum + sn.value.get_relative_duration())
+}
+
+// Same behavior as the ChordMusicMaker: chords and rhythm pattern cycle independently,
+// we loop until the length is covered (or at least once through the progression)
+// Returns the start and the relative duration of each chord
+pub fn get_chord_timeline<'a>(chord_progression: &'a ChordProgression, rhythm_pattern: &[NoteValue], length: Rational32) -> Vec<(Rational32, Rational32, &'a Chord)> {
+    let mut ret = Vec::new();
+    if chord_progression.chords.is_empty() || rhythm_pattern.is_empty() {
+        return ret;
+    }
+
+    let mut start = Rational32::zero();
+    let mut i = 0;
+    while start < length || i < chord_progression.chords.len() {
+        let duration = rhythm_pattern[i % rhythm_pattern.len()].get_relative_duration();
+        ret.push((start, duration, &chord_progression.chords[i % chord_progression.chords.len()]));
+        start += duration;
+        i += 1;
+    }
+    ret
+}
+
+// Split a length (relative to a whole note) into note values without tuplets, longest first
+pub fn get_note_values(mut length: Rational32) -> Vec<NoteValue> {
+    use NoteValueBase::*;
+    use NoteValueDotted::*;
+    let mut note_values = Vec::new();
+    [Whole, Half, Quarter, Eighth, Sixteenth, ThirtySecond, SixtyFourth].iter().for_each(|base| {
+        [None, Some(Dotted), Some(DoubleDotted), Some(TripleDotted)].iter().for_each(|dotted| {
+            let note_value = NoteValue{base: *base, dotted: *dotted, tuplet: None};
+            note_values.push((note_value.get_relative_duration(), note_value));
+        })
+    });
+    note_values.sort_by_key(|(l, _)| std::cmp::Reverse(*l));
+
+    let mut ret = Vec::new();
+    while length > Rational32::zero() {
+        match note_values.iter().find(|(l, _)| *l <= length) {
+            Some((l, note_value)) => {
+                ret.push(*note_value);
+                length -= l;
+            }
+            None => break, // shorter than a sixty-fourth, dropped
+        }
+    }
+    ret
+}
+
+// Runs of consecutive notes in the same tuplet, as the index of the first note, the number of notes and the tuplet
+pub fn get_tuplet_runs(note_values: &[NoteValue]) -> Vec<(usize, usize, Tuplet)> {
+    let mut ret: Vec<(usize, usize, Tuplet)> = Vec::new();
+    note_values.iter().enumerate().for_each(|(i, note_value)| {
+        let Some(tuplet) = note_value.tuplet else {
+            return;
+        };
+        match ret.last_mut() {
+            Some((start, nb_notes, t)) if *t == tuplet && *start + *nb_notes == i => *nb_notes += 1,
+            _ => ret.push((i, 1, tuplet)),
+        }
+    });
+    ret
+}
+
+// Position on the circle of fifths from C: F is -1, F# is 6
+pub fn get_note_fifths(note: Note) -> i32 {
+    use NoteLetter::*;
+    let letter_fifths = match note.letter {
+        F => -1,
+        C => 0,
+        G => 1,
+        D => 2,
+        A => 3,
+        E => 4,
+        B => 5,
+    };
+    letter_fifths + 7 * note.get_alter() as i32
+}
+
+// Key signature of the mode relative to the major key of the same tonic, A minor has 3 flats less than A major
+pub fn get_mode_fifths(mode: Mode) -> i32 {
+    use Mode::*;
+    match mode {
+        Ionian => 0,
+        Dorian => -2,
+        Phrygian => -4,
+        Lydian => 1,
+        Mixolydian => -1,
+        Aeolian => -3,
+        Locrian => -5,
+    }
+}
+
+// Alteration of each letter (C to B) in the key signature with this number of sharps (or flats when negative)
+pub fn get_alters_from_fifths(fifths: i32) -> [i8; 7] {
+    use NoteLetter::*;
+    let sharps = [F, C, G, D, A, E, B];
+    let mut ret = [0i8; 7];
+    if fifths > 0 {
+        sharps.iter().take(fifths as usize).for_each(|l| ret[*l as usize] = 1);
+    } else {
+        sharps.iter().rev().take((-fifths) as usize).for_each(|l| ret[*l as usize] = -1);
+    }
+    ret
+}
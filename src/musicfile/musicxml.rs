@@ -0,0 +1,244 @@
+// MusicXML export (score-partwise), to open the generated music in notation software like MuseScore
+// The chord progression is written as harmony symbols above the melody, notes and chords are spelled in the key
+
+use std::{collections::HashMap, fs::File, io::{Error, Write}};
+
+use num::{integer::lcm, rational::Rational32};
+
+use crate::musictheory::{
+    chord::{Chord, ChordInversion, ChordType}, chord_progression::ChordProgression, key::Key, mode::Mode, note_value::{NoteValue, NoteValueBase},
+    piano_key::PianoKey, sheet::Sheet, spelling::respell_in_key, tempo::Tempo, time_signature::TimeSignature
+};
+
+use super::{get_alters_from_fifths, get_chord_timeline, get_mode_fifths, get_note_fifths, get_sheet_length, get_tuplet_runs};
+
+// Divisions per quarter note, small enough to be readable, big enough for a triple dotted sixteenth,
+// multiplied when needed so that the tuplets last a whole number of divisions
+pub const DIVISIONS: u32 = 32;
+
+pub fn sheet_to_musicxml(sheet: &Sheet, chords: Option<(&ChordProgression, &[NoteValue])>, key: Key, tempo: Tempo) -> String {
+    let chord_timeline = match chords {
+        Some((chord_progression, rhythm_pattern)) => get_chord_timeline(chord_progression, rhythm_pattern, get_sheet_length(sheet)),
+        None => Vec::new(),
+    };
+    let divisions = sheet.patterns.iter()
+        .flat_map(|p| p.measures.iter())
+        .flat_map(|m| m.notes.iter())
+        .map(|sn| sn.value.get_relative_duration())
+        .chain(chord_timeline.iter().map(|(start, _, _)| *start))
+        .fold(DIVISIONS, |divisions, duration| lcm(divisions, (duration * 4).denom().unsigned_abs()));
+    let mut chord_divisions = chord_timeline.iter()
+        .map(|(start, _, chord)| (get_divisions(*start, divisions), *chord))
+        .peekable();
+    let key_signature = get_key_signature(key);
+    let key_alters = get_alters_from_fifths(key_signature.map(|(fifths, _)| fifths).unwrap_or_default());
+
+    let mut ret = String::new();
+    ret.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\" standalone=\"no\"?>\n");
+    ret.push_str("<!DOCTYPE score-partwise PUBLIC \"-//Recordare//DTD MusicXML 3.1 Partwise//EN\" \"http://www.musicxml.org/dtds/partwise.dtd\">\n");
+    ret.push_str("<score-partwise version=\"3.1\">\n");
+    ret.push_str("  <part-list>\n    <score-part id=\"P1\">\n      <part-name>Melody</part-name>\n    </score-part>\n  </part-list>\n");
+    ret.push_str("  <part id=\"P1\">\n");
+
+    let mut position = 0;
+    let mut previous_time_signature = None;
+    let measures = sheet.patterns.iter().flat_map(|p| p.measures.iter());
+    for (i, measure) in measures.enumerate() {
+        ret.push_str(&format!("    <measure number=\"{}\">\n", i + 1));
+        if i == 0 || previous_time_signature != Some(measure.time_signature) {
+            let (beats, beat_type) = get_time_signature(measure.time_signature);
+            ret.push_str("      <attributes>\n");
+            if i == 0 {
+                ret.push_str(&format!("        <divisions>{}</divisions>\n", divisions));
+                if let Some((fifths, mode_name)) = key_signature {
+                    ret.push_str(&format!("        <key>\n          <fifths>{}</fifths>\n          <mode>{}</mode>\n        </key>\n", fifths, mode_name));
+                }
+            }
+            ret.push_str(&format!("        <time>\n          <beats>{}</beats>\n          <beat-type>{}</beat-type>\n        </time>\n", beats, beat_type));
+            if i == 0 {
+                ret.push_str("        <clef>\n          <sign>G</sign>\n          <line>2</line>\n        </clef>\n");
+            }
+            ret.push_str("      </attributes>\n");
+            previous_time_signature = Some(measure.time_signature);
+        }
+        if i == 0 {
+            ret.push_str(&format!(
+                "      <direction placement=\"above\">\n        <direction-type>\n          <metronome>\n            <beat-unit>quarter</beat-unit>\n            <per-minute>{}</per-minute>\n          </metronome>\n        </direction-type>\n        <sound tempo=\"{}\"/>\n      </direction>\n",
+                u16::from(tempo),
+                u16::from(tempo)
+            ));
+        }
+
+        // Accidentals last until the end of the measure
+        let mut measure_accidentals = HashMap::<(usize, u8), i8>::new();
+        let tuplet_runs = get_tuplet_runs(&measure.notes.iter().map(|sn| sn.value).collect::<Vec<NoteValue>>());
+        for (j, sheet_note) in measure.notes.iter().enumerate() {
+            let duration = get_divisions(sheet_note.value.get_relative_duration(), divisions);
+            while let Some((chord_position, chord)) = chord_divisions.next_if(|(p, _)| *p < position + duration) {
+                ret.push_str(&harmony_to_musicxml(chord, key, chord_position.saturating_sub(position)));
+            }
+            let tuplet_bounds = (
+                tuplet_runs.iter().any(|(start, _, _)| *start == j),
+                tuplet_runs.iter().any(|(start, nb_notes, _)| start + nb_notes - 1 == j),
+            );
+            let piano_key = respell_in_key(sheet_note.note, key);
+            let letter = piano_key.note.letter as usize;
+            let alter = piano_key.note.get_alter();
+            let current_alter = *measure_accidentals.get(&(letter, piano_key.octave)).unwrap_or(&key_alters[letter]);
+            let accidental = (alter != current_alter).then_some(alter);
+            measure_accidentals.insert((letter, piano_key.octave), alter);
+            ret.push_str(&note_to_musicxml(piano_key, sheet_note.value, duration, accidental, tuplet_bounds));
+            position += duration;
+        }
+        ret.push_str("    </measure>\n");
+    }
+
+    ret.push_str("  </part>\n</score-partwise>\n");
+    ret
+}
+
+pub fn write_musicxml_file(path: &str, sheet: &Sheet, chords: Option<(&ChordProgression, &[NoteValue])>, key: Key, tempo: Tempo) -> Result<(), Error> {
+    let mut file = File::create(path)?;
+    file.write_all(sheet_to_musicxml(sheet, chords, key, tempo).as_bytes())
+}
+
+pub fn get_note_type(base: NoteValueBase) -> &'static str {
+    use NoteValueBase::*;
+    match base {
+        Whole => "whole",
+        Half => "half",
+        Quarter => "quarter",
+        Eighth => "eighth",
+        Sixteenth => "16th",
+        ThirtySecond => "32nd",
+        SixtyFourth => "64th",
+    }
+}
+
+pub fn get_chord_kind(chord_type: ChordType) -> &'static str {
+    use ChordType::*;
+    match chord_type {
+        MajorTriad => "major",
+        MajorSixth => "major-sixth",
+        DominantSeventh => "dominant",
+        AugmentedTriad => "augmented",
+        AugmentedSeventh => "augmented-seventh",
+        MinorTriad => "minor",
+        MinorSixth => "minor-sixth",
+        MinorSeventh => "minor-seventh",
+        MinorMajorSeventh => "major-minor",
+        DiminishedTriad => "diminished",
+        DiminishedSeventh => "diminished-seventh",
+        HalfDiminishedSeventh => "half-diminished",
+        PowerDiad | PowerTriad => "power",
+        CustomChord => "other",
+    }
+}
+
+// Sharps (positive) or flats (negative) of the key signature and the mode name, None when the scale has no key signature
+fn get_key_signature(key: Key) -> Option<(i32, &'static str)> {
+    use Mode::*;
+    let mode = key.scale.get_signature_mode()?;
+    let mode_name = match mode {
+        Ionian => "major",
+        Dorian => "dorian",
+        Phrygian => "phrygian",
+        Lydian => "lydian",
+        Mixolydian => "mixolydian",
+        Aeolian => "minor",
+        Locrian => "locrian",
+    };
+    let mut fifths = get_note_fifths(key.base_note.note) + get_mode_fifths(mode);
+    // Use the enharmonic key when there are more than 7 accidentals (G# major is written Ab major)
+    if fifths.abs() > 7 {
+        fifths -= 12 * fifths.signum();
+    }
+    Some((fifths, mode_name))
+}
+
+fn get_divisions(relative_duration: Rational32, divisions: u32) -> u32 {
+    (relative_duration * 4 * divisions as i32).to_integer().max(0) as u32
+}
+
+fn get_alter(piano_key: PianoKey) -> Option<i8> {
+    piano_key.note.accidental.map(|a| a.get_alter())
+}
+
+// The accidental is the alteration to show when the key signature and the measure don't already give it,
+// the tuplet bounds tell if the note starts and ends a tuplet bracket
+fn note_to_musicxml(piano_key: PianoKey, value: NoteValue, duration: u32, accidental: Option<i8>, tuplet_bounds: (bool, bool)) -> String {
+    let mut ret = String::from("      <note>\n        <pitch>\n");
+    ret.push_str(&format!("          <step>{:?}</step>\n", piano_key.note.letter));
+    if let Some(alter) = get_alter(piano_key) {
+        ret.push_str(&format!("          <alter>{}</alter>\n", alter));
+    }
+    ret.push_str(&format!("          <octave>{}</octave>\n        </pitch>\n", piano_key.octave));
+    ret.push_str(&format!("        <duration>{}</duration>\n", duration));
+    ret.push_str(&format!("        <type>{}</type>\n", get_note_type(value.base)));
+    let nb_dots = value.dotted.map(|d| d.get_nb_dots()).unwrap_or_default();
+    for _ in 0..nb_dots {
+        ret.push_str("        <dot/>\n");
+    }
+    if let Some(alter) = accidental {
+        let accidental_str = match alter {
+            -2 => "flat-flat",
+            -1 => "flat",
+            1 => "sharp",
+            2 => "double-sharp",
+            _ => "natural",
+        };
+        ret.push_str(&format!("        <accidental>{}</accidental>\n", accidental_str));
+    }
+    if let Some(tuplet) = value.tuplet {
+        ret.push_str(&format!(
+            "        <time-modification>\n          <actual-notes>{}</actual-notes>\n          <normal-notes>{}</normal-notes>\n        </time-modification>\n",
+            tuplet.actual_notes, tuplet.normal_notes
+        ));
+        if tuplet_bounds.0 || tuplet_bounds.1 {
+            ret.push_str("        <notations>\n");
+            if tuplet_bounds.0 {
+                ret.push_str("          <tuplet type=\"start\" bracket=\"yes\"/>\n");
+            }
+            if tuplet_bounds.1 {
+                ret.push_str("          <tuplet type=\"stop\"/>\n");
+            }
+            ret.push_str("        </notations>\n");
+        }
+    }
+    ret.push_str("      </note>\n");
+    ret
+}
+
+fn harmony_to_musicxml(chord: &Chord, key: Key, offset: u32) -> String {
+    let root = respell_in_key(chord.base_note, key);
+    let mut ret = String::from("      <harmony>\n        <root>\n");
+    ret.push_str(&format!("          <root-step>{:?}</root-step>\n", root.note.letter));
+    if let Some(alter) = get_alter(root) {
+        ret.push_str(&format!("          <root-alter>{}</root-alter>\n", alter));
+    }
+    ret.push_str("        </root>\n");
+    ret.push_str(&format!("        <kind>{}</kind>\n", get_chord_kind(chord.chord_type)));
+    if chord.inversion != ChordInversion::Root {
+        let bass = respell_in_key(chord.clone().get_keys()[0], key);
+        ret.push_str(&format!("        <bass>\n          <bass-step>{:?}</bass-step>\n", bass.note.letter));
+        if let Some(alter) = get_alter(bass) {
+            ret.push_str(&format!("          <bass-alter>{}</bass-alter>\n", alter));
+        }
+        ret.push_str("        </bass>\n");
+    }
+    if offset > 0 {
+        ret.push_str(&format!("        <offset>{}</offset>\n", offset));
+    }
+    ret.push_str("      </harmony>\n");
+    ret
+}
+
+// The grouping is written in the beats (2+2+3) when it is not the usual one
+fn get_time_signature(time_signature: TimeSignature) -> (String, u8) {
+    let beats = if time_signature.has_default_grouping() {
+        time_signature.numerator.to_string()
+    } else {
+        time_signature.get_grouping().iter().map(|g| g.to_string()).collect::<Vec<String>>().join("+")
+    };
+    (beats, time_signature.denominator)
+}
@@ -0,0 +1,35 @@
+// Raw interleaved PCM streaming (s16le, s24_3le or f32le), to pipe pmusic into aplay, ffmpeg...
+// The bytes are the same as the data chunk of a WAV file exported with the same config
+
+use std::io::{Error, Write};
+
+use rand::{rngs::SmallRng, SeedableRng};
+
+use super::wav::{quantize, WavConfig, WavSampleFormat};
+
+// Samples written per chunk, small enough to keep the latency low when piped into a player
+pub const PCM_CHUNK_SIZE: usize = 4096;
+
+// Runs until the samples end, so forever with the looping music makers
+pub fn write_pcm_stream<W, I>(writer: &mut W, samples: I, config: WavConfig) -> Result<(), Error>
+where
+    W: Write,
+    I: Iterator<Item = f32>,
+{
+    config.validate()?;
+    let bits = config.sample_format.get_bits_per_sample() as u32;
+    let mut rng = SmallRng::seed_from_u64(0);
+    let mut buffer = Vec::with_capacity(PCM_CHUNK_SIZE * 4);
+    let mut samples = samples.peekable();
+    while samples.peek().is_some() {
+        buffer.clear();
+        samples.by_ref().take(PCM_CHUNK_SIZE).for_each(|sample| match config.sample_format {
+            WavSampleFormat::Float32 => buffer.extend_from_slice(&sample.to_le_bytes()),
+            WavSampleFormat::Int16 => buffer.extend_from_slice(&(quantize(sample, bits, config.dither, &mut rng) as i16).to_le_bytes()),
+            WavSampleFormat::Int24 => buffer.extend_from_slice(&quantize(sample, bits, config.dither, &mut rng).to_le_bytes()[0..3]),
+        });
+        writer.write_all(&buffer)?;
+        writer.flush()?;
+    }
+    Ok(())
+}
@@ -0,0 +1,117 @@
+// Piano roll SVG of a sheet, to review the generated music without listening to it
+// Time goes left to right (in whole notes), pitch bottom to top, the chord progression is drawn
+// as shaded bands behind the notes with the chord names above the roll
+
+use std::{fs::File, io::{Error, Write}};
+
+use num::{rational::Rational32, ToPrimitive, Zero};
+
+use crate::musictheory::{chord_progression::ChordProgression, note_value::NoteValue, sheet::Sheet};
+
+use super::{get_chord_timeline, get_sheet_length, midi::{get_midi_note_number, get_piano_key}};
+
+pub const WHOLE_NOTE_WIDTH: f32 = 160.0;
+pub const KEY_HEIGHT: f32 = 12.0;
+const KEYBOARD_WIDTH: f32 = 40.0;
+const HEADER_HEIGHT: f32 = 40.0;
+
+pub fn sheet_to_svg(sheet: &Sheet, chords: Option<(&ChordProgression, &[NoteValue])>) -> String {
+    let length = get_sheet_length(sheet);
+    let notes = sheet.patterns.iter()
+        .flat_map(|p| p.measures.iter())
+        .flat_map(|m| m.notes.iter())
+        .collect::<Vec<_>>();
+    // One octave from C4 when there is nothing to draw
+    let lowest = notes.iter().map(|sn| get_midi_note_number(sn.note)).min().unwrap_or(60);
+    let highest = notes.iter().map(|sn| get_midi_note_number(sn.note)).max().unwrap_or(72);
+    let nb_rows = (highest - lowest + 1) as f32;
+
+    let get_width = |duration: Rational32| duration.to_f32().unwrap_or_default() * WHOLE_NOTE_WIDTH;
+    let get_x = |position: Rational32| KEYBOARD_WIDTH + get_width(position);
+    let get_y = |midi_note_number: u8| HEADER_HEIGHT + (highest - midi_note_number) as f32 * KEY_HEIGHT;
+    let width = get_x(length) + 1.0;
+    let height = HEADER_HEIGHT + nb_rows * KEY_HEIGHT + 1.0;
+
+    let mut ret = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\" font-family=\"sans-serif\" font-size=\"10\">\n",
+        width, height, width, height
+    );
+    ret.push_str(&format!("  <rect width=\"{}\" height=\"{}\" fill=\"white\"/>\n", width, height));
+
+    // Keyboard rows, black keys are shaded and every C is labeled
+    (lowest..=highest).for_each(|midi_note_number| {
+        let piano_key = get_piano_key(midi_note_number);
+        if piano_key.note.accidental.is_some() {
+            ret.push_str(&format!(
+                "  <rect class=\"row\" x=\"0\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#eeeeee\"/>\n",
+                get_y(midi_note_number), width, KEY_HEIGHT
+            ));
+        }
+        if midi_note_number % 12 == 0 {
+            ret.push_str(&format!(
+                "  <text x=\"2\" y=\"{}\">{}</text>\n",
+                get_y(midi_note_number) + KEY_HEIGHT - 2.0, piano_key
+            ));
+        }
+    });
+
+    if let Some((chord_progression, rhythm_pattern)) = chords {
+        get_chord_timeline(chord_progression, rhythm_pattern, length).iter()
+            .filter(|(start, _, _)| *start < length)
+            .enumerate()
+            .for_each(|(i, (start, duration, chord))| {
+                let band_width = get_width(*duration.min(&(length - start)));
+                let fill = if i % 2 == 0 { "#4a90d9" } else { "#d9904a" };
+                ret.push_str(&format!(
+                    "  <rect class=\"chord\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" fill-opacity=\"0.15\"/>\n",
+                    get_x(*start), HEADER_HEIGHT / 2.0, band_width, height - HEADER_HEIGHT / 2.0, fill
+                ));
+                ret.push_str(&format!(
+                    "  <text x=\"{}\" y=\"{}\">{}</text>\n",
+                    get_x(*start) + 2.0, HEADER_HEIGHT - 6.0, escape_xml(&chord.to_string())
+                ));
+            });
+    }
+
+    // Measure lines, thicker with the pattern name at the start of a pattern
+    let mut position = Rational32::zero();
+    sheet.patterns.iter().for_each(|pattern| {
+        pattern.measures.iter().enumerate().for_each(|(i, measure)| {
+            if i == 0 {
+                ret.push_str(&format!(
+                    "  <line class=\"pattern\" x1=\"{x}\" y1=\"0\" x2=\"{x}\" y2=\"{}\" stroke=\"#333333\" stroke-width=\"2\"/>\n",
+                    height, x = get_x(position)
+                ));
+                ret.push_str(&format!("  <text x=\"{}\" y=\"12\">{}</text>\n", get_x(position) + 3.0, escape_xml(&pattern.name)));
+            } else {
+                ret.push_str(&format!(
+                    "  <line class=\"measure\" x1=\"{x}\" y1=\"{}\" x2=\"{x}\" y2=\"{}\" stroke=\"#999999\"/>\n",
+                    HEADER_HEIGHT / 2.0, height, x = get_x(position)
+                ));
+            }
+            position += measure.notes.iter().fold(Rational32::zero(), |sum, sn| sum + sn.value.get_relative_duration());
+        });
+    });
+
+    let mut position = Rational32::zero();
+    notes.iter().for_each(|sheet_note| {
+        let duration = sheet_note.value.get_relative_duration();
+        ret.push_str(&format!(
+            "  <rect class=\"note\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"#2e7d32\" stroke=\"#1b5e20\"><title>{}</title></rect>\n",
+            get_x(position), get_y(get_midi_note_number(sheet_note.note)), get_width(duration), KEY_HEIGHT, sheet_note.to_ascii()
+        ));
+        position += duration;
+    });
+
+    ret.push_str("</svg>\n");
+    ret
+}
+
+pub fn write_piano_roll_file(path: &str, sheet: &Sheet, chords: Option<(&ChordProgression, &[NoteValue])>) -> Result<(), Error> {
+    let mut file = File::create(path)?;
+    file.write_all(sheet_to_svg(sheet, chords).as_bytes())
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
@@ -0,0 +1,34 @@
+// Offline rendering of the tracks (melody, chords...) for the file exports
+// Each track is rendered on its own so it can be written as a stem, the mixdown is their sum
+
+use rodio::{dynamic_mixer, Source};
+
+// Render a source in the channel count and sample rate of the export, through a mixer
+// like in play mode, so the source is converted the same way
+pub fn render_source<S>(source: S, channels: u16, sample_rate: u32) -> Vec<f32>
+where
+    S: Source<Item = f32> + Send + 'static,
+{
+    let (controller, mixer) = dynamic_mixer::mixer::<f32>(channels, sample_rate);
+    controller.add(source);
+    mixer.collect()
+}
+
+// Pad the stems with silence so they all have the same length (the tracks all start at 0,
+// so they stay sample-aligned), then return the mixdown
+pub fn align_and_mix_stems(stems: &mut [Vec<f32>]) -> Vec<f32> {
+    let length = stems.iter().map(|s| s.len()).max().unwrap_or(0);
+    stems.iter_mut().for_each(|stem| stem.resize(length, 0.0));
+    (0..length)
+        .map(|i| stems.iter().fold(0.0, |sum, stem| sum + stem[i]))
+        .collect()
+}
+
+// "./output/output.wav" with the "melody" stem gives "./output/output_melody.wav"
+pub fn get_stem_path(path: &str, stem_name: &str) -> String {
+    let file_start = path.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match path[file_start..].rfind('.') {
+        Some(i) => format!("{}_{}{}", &path[..file_start + i], stem_name, &path[file_start + i..]),
+        None => format!("{}_{}", path, stem_name),
+    }
+}
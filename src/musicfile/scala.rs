@@ -0,0 +1,133 @@
+// Scala scale (.scl) and keyboard mapping (.kbm) import
+// Lines starting with '!' are comments, a pitch with a dot is in cents and a pitch without one is a ratio (3/2 or 2)
+
+use std::{fs::File, io::{Error, ErrorKind, Read}};
+
+use crate::musictheory::{cent::Cent, hertz::Hertz, scala_tuning::{KeyboardMapping, ScalaTuning}};
+
+const MAX_MIDI_NOTE_NUMBER: u8 = 127;
+
+pub fn scala_tuning_from_scl_file(path: &str) -> Result<ScalaTuning, Error> {
+    scala_tuning_from_scl(&read_file(path)?)
+}
+
+pub fn scala_tuning_from_scl(s: &str) -> Result<ScalaTuning, Error> {
+    let mut lines = get_lines(s);
+    // The description may be empty but the line has to be there
+    let (_, description) = lines.next().ok_or_else(|| invalid_scl("The description is missing", 0))?;
+    let (line_number, nb_notes) = lines.next().ok_or_else(|| invalid_scl("The number of notes is missing", s.lines().count()))?;
+    let nb_notes = get_first_token(nb_notes).parse::<usize>()
+        .map_err(|_| invalid_scl(&format!("{} is not a number of notes", nb_notes), line_number))?;
+    if nb_notes == 0 {
+        return Err(invalid_scl("The scale needs at least one note", line_number));
+    }
+
+    let mut degrees = Vec::new();
+    for (line_number, line) in lines.take(nb_notes) {
+        degrees.push(parse_pitch(get_first_token(line), line_number)?);
+    }
+    if degrees.len() < nb_notes {
+        return Err(invalid_scl(&format!("Expected {} notes, found {}", nb_notes, degrees.len()), s.lines().count()));
+    }
+    Ok(ScalaTuning::new(description, degrees))
+}
+
+pub fn keyboard_mapping_from_kbm_file(path: &str) -> Result<KeyboardMapping, Error> {
+    keyboard_mapping_from_kbm(&read_file(path)?)
+}
+
+pub fn keyboard_mapping_from_kbm(s: &str) -> Result<KeyboardMapping, Error> {
+    let mut lines = get_lines(s).map(|(line_number, line)| (line_number, get_first_token(line)));
+    let mut next_line = |name: &str| lines.next().ok_or_else(|| invalid_kbm(&format!("The {} is missing", name), s.lines().count()));
+
+    let (line_number, size) = next_line("map size")?;
+    let size = size.parse::<usize>().map_err(|_| invalid_kbm(&format!("{} is not a map size", size), line_number))?;
+    // The pattern repeats over the MIDI keys, so it is never longer than them
+    if size > MAX_MIDI_NOTE_NUMBER as usize + 1 {
+        return Err(invalid_kbm(&format!("The map size {} is larger than the {} MIDI keys", size, MAX_MIDI_NOTE_NUMBER as usize + 1), line_number));
+    }
+    let first_note = parse_midi_note_number(next_line("first note")?)?;
+    let last_note = parse_midi_note_number(next_line("last note")?)?;
+    let middle_note = parse_midi_note_number(next_line("middle note")?)?;
+    let reference_note = parse_midi_note_number(next_line("reference note")?)?;
+    let (line_number, reference_frequency) = next_line("reference frequency")?;
+    let reference_frequency = reference_frequency.parse::<f64>().ok()
+        .filter(|f| f.is_finite() && *f > 0.0)
+        .ok_or_else(|| invalid_kbm(&format!("{} is not a valid frequency", reference_frequency), line_number))?;
+    let (line_number, octave_degree) = next_line("octave degree")?;
+    let octave_degree = octave_degree.parse::<usize>()
+        .map_err(|_| invalid_kbm(&format!("{} is not a scale degree", octave_degree), line_number))?;
+    if first_note > last_note {
+        return Err(invalid_kbm(&format!("The first note {} is above the last note {}", first_note, last_note), line_number));
+    }
+
+    // Missing entries at the end of the mapping are unmapped keys
+    let mut mapping = vec![None; size];
+    for (entry, (line_number, degree)) in mapping.iter_mut().zip(lines) {
+        *entry = match degree {
+            "x" | "X" => None,
+            _ => Some(degree.parse::<usize>().map_err(|_| invalid_kbm(&format!("{} is not a scale degree", degree), line_number))?),
+        };
+    }
+
+    Ok(KeyboardMapping {
+        first_note,
+        last_note,
+        middle_note,
+        reference_note,
+        reference_frequency: Hertz(reference_frequency),
+        // 0 means the scale size
+        octave_degree: (octave_degree > 0).then_some(octave_degree),
+        mapping,
+    })
+}
+
+fn read_file(path: &str) -> Result<String, Error> {
+    let mut file = File::open(path)?;
+    let mut buffer = String::new();
+    file.read_to_string(&mut buffer)?;
+    Ok(buffer)
+}
+
+// The lines which are not comments, with their index
+fn get_lines(s: &str) -> impl Iterator<Item = (usize, &str)> {
+    s.lines()
+        .map(|line| line.trim())
+        .enumerate()
+        .filter(|(_, line)| !line.starts_with('!'))
+}
+
+// Anything after the value is a comment
+fn get_first_token(line: &str) -> &str {
+    line.split_whitespace().next().unwrap_or_default()
+}
+
+fn parse_pitch(s: &str, line_number: usize) -> Result<Cent, Error> {
+    if s.contains('.') {
+        return s.parse::<f64>().ok()
+            .filter(|c| c.is_finite())
+            .map(Cent)
+            .ok_or_else(|| invalid_scl(&format!("{} is not a value in cents", s), line_number));
+    }
+    let (numerator, denominator) = s.split_once('/').unwrap_or((s, "1"));
+    match (numerator.parse::<u64>(), denominator.parse::<u64>()) {
+        (Ok(numerator), Ok(denominator)) if numerator > 0 && denominator > 0 => {
+            Ok(Cent(1200.0 * (numerator as f64 / denominator as f64).log2()))
+        }
+        _ => Err(invalid_scl(&format!("{} is not a positive ratio", s), line_number)),
+    }
+}
+
+fn parse_midi_note_number((line_number, s): (usize, &str)) -> Result<u8, Error> {
+    s.parse::<u8>().ok()
+        .filter(|n| *n <= MAX_MIDI_NOTE_NUMBER)
+        .ok_or_else(|| invalid_kbm(&format!("{} is not a MIDI note number", s), line_number))
+}
+
+fn invalid_scl(message: &str, line_number: usize) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid Scala scale at line {}: {}", line_number + 1, message))
+}
+
+fn invalid_kbm(message: &str, line_number: usize) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("Invalid keyboard mapping at line {}: {}", line_number + 1, message))
+}
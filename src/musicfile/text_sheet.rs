@@ -0,0 +1,22 @@
+// Plain text sheet files, using the ASCII notation of Sheet (see Sheet::to_ascii)
+
+use std::{fs::File, io::{Error, ErrorKind, Read, Write}, str::FromStr};
+
+use crate::musictheory::sheet::Sheet;
+
+pub fn sheet_from_text_file(path: &str) -> Result<Sheet, Error> {
+    let mut file = File::open(path)?;
+    let mut buffer = String::new();
+    file.read_to_string(&mut buffer)?;
+
+    let sheet = Sheet::from_str(&buffer).map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+    if sheet.patterns.iter().all(|p| p.measures.iter().all(|m| m.notes.is_empty())) {
+        return Err(Error::new(ErrorKind::InvalidData, format!("{}: no notes found", path)));
+    }
+    Ok(sheet)
+}
+
+pub fn write_text_file(path: &str, sheet: &Sheet) -> Result<(), Error> {
+    let mut file = File::create(path)?;
+    file.write_all(sheet.to_ascii().as_bytes())
+}
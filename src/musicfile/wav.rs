@@ -0,0 +1,185 @@
+// WAV export, 16/24 bit PCM (with optional TPDF dithering) or 32 bit float
+// The samples are expected interleaved, in the channel count and sample rate of the config
+
+use core::fmt;
+use std::{fs::File, io::{self, BufWriter, Error, Write}, str::FromStr};
+
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum WavSampleFormat {
+    Int16,
+    Int24,
+    #[default]
+    Float32,
+}
+
+impl WavSampleFormat {
+    pub fn get_bits_per_sample(self) -> u16 {
+        match self {
+            Self::Int16 => 16,
+            Self::Int24 => 24,
+            Self::Float32 => 32,
+        }
+    }
+}
+
+impl FromStr for WavSampleFormat {
+    type Err = io::Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_uppercase().as_str() {
+            "16" | "INT16" | "PCM16" => Ok(Self::Int16),
+            "24" | "INT24" | "PCM24" => Ok(Self::Int24),
+            "32" | "FLOAT" | "FLOAT32" | "F32" => Ok(Self::Float32),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a valid sample format (16, 24 or float)", s),
+            )),
+        }
+    }
+}
+
+impl fmt::Display for WavSampleFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Self::Int16 => "16 bit PCM",
+            Self::Int24 => "24 bit PCM",
+            Self::Float32 => "32 bit float",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WavConfig {
+    pub sample_format: WavSampleFormat,
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub dither: bool,
+}
+
+impl Default for WavConfig {
+    fn default() -> Self {
+        Self {
+            sample_format: WavSampleFormat::default(),
+            channels: 2,
+            sample_rate: 44_100,
+            dither: true,
+        }
+    }
+}
+
+impl WavConfig {
+    pub fn new(sample_format: WavSampleFormat, channels: u16, sample_rate: u32) -> Self {
+        Self::default()
+            .set_sample_format(sample_format)
+            .set_channels(channels)
+            .set_sample_rate(sample_rate)
+    }
+
+    pub fn set_sample_format(mut self, sample_format: WavSampleFormat) -> Self {
+        self.sample_format = sample_format;
+        self
+    }
+
+    pub fn set_channels(mut self, channels: u16) -> Self {
+        self.channels = channels;
+        self
+    }
+
+    pub fn set_sample_rate(mut self, sample_rate: u32) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+
+    pub fn set_dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.channels == 0 || self.channels > 2 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "Only mono and stereo are supported"));
+        }
+        if !(8_000..=192_000).contains(&self.sample_rate) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} Hz is not a supported sample rate (8000 to 192000)", self.sample_rate),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for WavConfig {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let channels = if self.channels == 1 { "mono" } else { "stereo" };
+        write!(f, "{} {} {} Hz", self.sample_format, channels, self.sample_rate)
+    }
+}
+
+pub fn samples_to_wav(samples: &[f32], config: WavConfig) -> Result<Vec<u8>, Error> {
+    config.validate()?;
+    let bytes_per_sample = (config.sample_format.get_bits_per_sample() / 8) as u32;
+    let block_align = config.channels as u32 * bytes_per_sample;
+    let data_length = samples.len() as u32 * bytes_per_sample;
+    let format_tag: u16 = match config.sample_format {
+        WavSampleFormat::Float32 => 3, // WAVE_FORMAT_IEEE_FLOAT
+        _ => 1, // WAVE_FORMAT_PCM
+    };
+
+    let mut ret = Vec::with_capacity(44 + data_length as usize);
+    ret.extend_from_slice(b"RIFF");
+    ret.extend_from_slice(&(36 + data_length).to_le_bytes());
+    ret.extend_from_slice(b"WAVE");
+    ret.extend_from_slice(b"fmt ");
+    ret.extend_from_slice(&16u32.to_le_bytes());
+    ret.extend_from_slice(&format_tag.to_le_bytes());
+    ret.extend_from_slice(&config.channels.to_le_bytes());
+    ret.extend_from_slice(&config.sample_rate.to_le_bytes());
+    ret.extend_from_slice(&(config.sample_rate * block_align).to_le_bytes());
+    ret.extend_from_slice(&(block_align as u16).to_le_bytes());
+    ret.extend_from_slice(&config.sample_format.get_bits_per_sample().to_le_bytes());
+    ret.extend_from_slice(b"data");
+    ret.extend_from_slice(&data_length.to_le_bytes());
+
+    match config.sample_format {
+        WavSampleFormat::Float32 => samples.iter().for_each(|sample| ret.extend_from_slice(&sample.to_le_bytes())),
+        WavSampleFormat::Int16 => quantize_samples(samples, config).iter()
+            .for_each(|value| ret.extend_from_slice(&(*value as i16).to_le_bytes())),
+        WavSampleFormat::Int24 => quantize_samples(samples, config).iter()
+            .for_each(|value| ret.extend_from_slice(&value.to_le_bytes()[0..3])),
+    }
+
+    Ok(ret)
+}
+
+pub fn write_wav_file(path: &str, samples: &[f32], config: WavConfig) -> Result<(), Error> {
+    let wav = samples_to_wav(samples, config)?;
+    let file = File::create(path).map_err(|e| io::Error::new(e.kind(), format!("Cannot create {}: {}", path, e)))?;
+    let mut writer = BufWriter::new(file);
+    writer.write_all(&wav)?;
+    writer.flush()
+}
+
+// Integer samples of a PCM render, shared by the WAV and FLAC exports so both are bit-exact
+// The dither uses a fixed seed, so the same render always gives the same file
+pub fn quantize_samples(samples: &[f32], config: WavConfig) -> Vec<i32> {
+    let bits = config.sample_format.get_bits_per_sample() as u32;
+    let mut rng = SmallRng::seed_from_u64(0);
+    samples.iter()
+        .map(|sample| quantize(*sample, bits, config.dither, &mut rng))
+        .collect()
+}
+
+// Scale a sample from [-1.0, 1.0] to a signed integer of the given bit depth,
+// TPDF dithering adds the difference of two uniform noises of 1 LSB before rounding
+pub fn quantize(sample: f32, bits: u32, dither: bool, rng: &mut SmallRng) -> i32 {
+    let max = ((1i64 << (bits - 1)) - 1) as f64;
+    let noise = if dither {
+        rng.gen::<f64>() - rng.gen::<f64>()
+    } else {
+        0.0
+    };
+    (sample as f64 * max + noise).round().clamp(-max - 1.0, max) as i32
+}
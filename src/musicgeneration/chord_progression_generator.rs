@@ -1,7 +1,9 @@
-use crate::musictheory::{mode::{Mode, PentatonicMode}, scale::Scale, time_signature::TimeSignature};
+use crate::musictheory::{mode::{BebopMode, HarmonicMinorMode, MelodicMinorMode, Mode, OctatonicMode, PentatonicMode}, scale::{CustomScale, Scale}, time_signature::TimeSignature};
 use rand::{rngs::SmallRng, seq::IteratorRandom};
 
 pub fn chord_progression_generation(scale: Scale, _time_signature: TimeSignature, full_random: bool, mut seed: &mut SmallRng) -> String {
+    // The random progressions only use the degrees of the scale
+    let nb_degrees = scale.get_intervals().len();
     let chords = vec![
         "I","I","i","i","i°","I↑","Ip3",
         "II","II","ii","ii","ii°","II↑","IIp3",
@@ -11,6 +13,7 @@ pub fn chord_progression_generation(scale: Scale, _time_signature: TimeSignature
         "VI","VI","vi","vi","vi°","VI↑","VIp3",
         "VII","VII","vii","vii","vii°","VII↑","VIIp3",
     ];
+    let custom_chord_progressions: Vec<String>;
     let chord_progressions = match scale {
         Scale::Chromatic => vec!["I"],
         Scale::Tetratonic => vec!["I"],
@@ -114,6 +117,75 @@ pub fn chord_progression_generation(scale: Scale, _time_signature: TimeSignature
                 ],
             }
         },
+        Scale::HarmonicMinor(mode) => {
+            use HarmonicMinorMode::*;
+            match mode {
+                HarmonicMinor => vec![
+                    "i-iv-V-i",
+                    "i-VI-V-i",
+                    "i-ii°-V-i",
+                    "i-iv-vii°-i",
+                ],
+                LocrianNatural6 => vec!["i°-IV-i°"],
+                IonianAugmented => vec!["I↑-ii-I↑"],
+                UkrainianDorian => vec![
+                    "i-II-i",
+                    "i-II-v-i",
+                ],
+                PhrygianDominant => vec![
+                    "I-II-I",
+                    "I-iv-II-I",
+                    "I-vii-II-I",
+                ],
+                LydianSharp2 => vec!["I-VII-I"],
+                AlteredDiminished => vec!["i°-ii-i°"],
+            }
+        },
+        Scale::MelodicMinor(mode) => {
+            use MelodicMinorMode::*;
+            match mode {
+                MelodicMinor => vec![
+                    "i-IV-V-i",
+                    "i-ii-V-i",
+                ],
+                DorianFlat2 => vec!["i-IV-i"],
+                LydianAugmented => vec!["I↑-II-I↑"],
+                LydianDominant => vec![
+                    "I-II-I",
+                    "I-v-II-I",
+                ],
+                MixolydianFlat6 => vec![
+                    "I-iv-I",
+                    "I-VII-iv-I",
+                ],
+                LocrianNatural2 => vec!["i°-iv-i°"],
+                Altered => vec!["i°-ii-i°"],
+            }
+        },
+        Scale::WholeTone => vec!["I↑", "I↑-II↑"],
+        Scale::Octatonic(mode) => match mode {
+            OctatonicMode::HalfWhole => vec!["I-III-I", "I-i°"],
+            OctatonicMode::WholeHalf => vec!["i°-iii°", "i°"],
+        },
+        // Twelve-bar blues, the fourth and fifth of the key are the third and fifth degrees of the blues scale
+        Scale::Blues => vec![
+            "I-III-I-I-III-III-I-I-V-III-I-V",
+            "i-III-i-V",
+        ],
+        // The first five degrees are the ones of the underlying mode
+        Scale::Bebop(mode) => match mode {
+            BebopMode::Dominant => vec!["I-IV-I-V", "ii-V-I"],
+            BebopMode::Major => vec!["ii-V-I", "I-vii-ii-V"],
+            BebopMode::Dorian => vec!["i-V", "i-V-i-vi"],
+        },
+        Scale::Hirajoshi => vec!["i-V-i", "i-V"],
+        Scale::In => vec!["Ip3-II-Ip3", "iii-II-Ip3"],
+        Scale::HungarianMinor => vec!["i-V-i", "i-VI-V-i"],
+        Scale::DoubleHarmonic => vec!["I-II-I", "I-iv-II-I"],
+        Scale::Custom(custom_scale) => {
+            custom_chord_progressions = get_custom_chord_progressions(custom_scale);
+            custom_chord_progressions.iter().map(String::as_str).collect()
+        },
     };
     
     if !full_random {
@@ -122,9 +194,45 @@ pub fn chord_progression_generation(scale: Scale, _time_signature: TimeSignature
         let mut chord_progression: Vec<&str> = vec![];
         let nb_chord = (2..8).into_iter().choose(&mut seed).unwrap();
         for _i in 0..nb_chord {
-            chord_progression.push(chords.iter().choose(&mut seed).unwrap());
+            chord_progression.push(chords.iter().take(7 * nb_degrees.min(7)).choose(&mut seed).unwrap());
         }
 
         chord_progression.join("-")
     }
-}
\ No newline at end of file
+}
+
+// Progressions going from the tonic to each degree with a triad in the scale (stacking every other note) and back
+fn get_custom_chord_progressions(custom_scale: CustomScale) -> Vec<String> {
+    let steps = custom_scale.get_steps();
+    let mut semitones = vec![0];
+    steps.iter().for_each(|step| semitones.push(semitones[semitones.len() - 1] + *step as usize));
+    let get_semitones = |degree: usize| semitones[degree % steps.len()] + 12 * (degree / steps.len());
+    let chords = (0..steps.len().min(7))
+        .filter_map(|degree| {
+            let numeral = roman::to(degree as i32 + 1)?;
+            let third = get_semitones(degree + 2) - get_semitones(degree);
+            let fifth = get_semitones(degree + 4) - get_semitones(degree);
+            let chord = match (third, fifth) {
+                (4, 7) => numeral,
+                (3, 7) => numeral.to_lowercase(),
+                (3, 6) => format!("{}°", numeral.to_lowercase()),
+                (4, 8) => format!("{}↑", numeral),
+                _ => return None,
+            };
+            Some((degree, chord))
+        })
+        .collect::<Vec<(usize, String)>>();
+    // A power chord on the tonic when its triad is not in the scale
+    let tonic = chords.iter()
+        .find(|(degree, _)| *degree == 0)
+        .map(|(_, chord)| chord.clone())
+        .unwrap_or(String::from("Ip3"));
+    let mut ret = chords.iter()
+        .filter(|(degree, _)| *degree != 0)
+        .map(|(_, chord)| format!("{}-{}-{}", tonic, chord, tonic))
+        .collect::<Vec<String>>();
+    if ret.is_empty() {
+        ret.push(tonic);
+    }
+    ret
+}
@@ -18,7 +18,11 @@ use crate::musictheory::{
 use super::rhythm_pattern_generator::rhythm_pattern_generation;
 use super::rhythm_pattern_generator::rhythm_pattern_rand_generation;
 
-pub fn pattern_generation(name: String, base_note: PianoKey, scale: Scale, octaves: u8, nb_measures: i32, use_common_pattern: bool, mut seed: &mut SmallRng) -> Pattern {
+// Random picks before falling back to the nearest key
+const MAX_TRIES: usize = 1000;
+
+#[allow(clippy::too_many_arguments)]
+pub fn pattern_generation(name: String, base_note: PianoKey, scale: Scale, octaves: u8, time_signature: TimeSignature, nb_measures: i32, use_common_pattern: bool, mut seed: &mut SmallRng) -> Pattern {
     let mut pattern = Pattern::new(name);
     let max_distance = 5;
     let max_distance_between_measures = 14;
@@ -29,18 +33,29 @@ pub fn pattern_generation(name: String, base_note: PianoKey, scale: Scale, octav
     for _ in 0..nb_measures {
         let rhythm_pattern: Vec<NoteValue>;
         if use_common_pattern {
-            rhythm_pattern = rhythm_pattern_generation(TimeSignature::default(), &mut seed);
+            rhythm_pattern = rhythm_pattern_generation(time_signature, &mut seed);
         } else {
-            rhythm_pattern = rhythm_pattern_rand_generation(TimeSignature::default(), &mut seed);
+            rhythm_pattern = rhythm_pattern_rand_generation(time_signature, &mut seed);
         }
-        let mut measure = Measure::new(TimeSignature::default());
+        let mut measure = Measure::new(time_signature);
         let mut prev_note: Option<PianoKey> = None;
         rhythm_pattern.iter().for_each(|value| {
-            let mut note = *keys.iter().choose(&mut seed).unwrap();
-            while 
+            let is_too_far = |note: PianoKey|
                 measure_last_note.is_some() && (note.get_distance(measure_last_note.unwrap()) > max_distance_between_measures) ||
-                prev_note.is_some() && (note == prev_note.unwrap() || prev_note.unwrap().get_distance(note) > max_distance) {
+                prev_note.is_some() && (note == prev_note.unwrap() || prev_note.unwrap().get_distance(note) > max_distance);
+            let mut note = *keys.iter().choose(&mut seed).unwrap();
+            let mut nb_tries = 0;
+            while is_too_far(note) && nb_tries < MAX_TRIES {
                 note = *keys.iter().choose(&mut seed).unwrap();
+                nb_tries += 1;
+            }
+            // Custom scales can have steps wider than max_distance, take the nearest other key
+            if let Some(previous) = prev_note.or(measure_last_note).filter(|_| is_too_far(note)) {
+                note = keys.iter()
+                    .filter(|k| **k != previous)
+                    .min_by_key(|k| k.get_distance(previous))
+                    .copied()
+                    .unwrap_or(previous);
             }
             measure_last_note = None;
             prev_note = Some(note.clone());
@@ -20,6 +20,31 @@ pub fn get_random_scale(mut seed: &mut SmallRng) -> Scale {
         Scale::from_str("PENTAMINOR").unwrap(),
         Scale::from_str("CHROMATIC").unwrap(),
         Scale::from_str("TETRATONIC").unwrap(),
+        Scale::from_str("HARMONICMINOR").unwrap(),
+        Scale::from_str("LOCRIANNATURAL6").unwrap(),
+        Scale::from_str("IONIANAUGMENTED").unwrap(),
+        Scale::from_str("UKRAINIANDORIAN").unwrap(),
+        Scale::from_str("PHRYGIANDOMINANT").unwrap(),
+        Scale::from_str("LYDIANSHARP2").unwrap(),
+        Scale::from_str("ALTEREDDIMINISHED").unwrap(),
+        Scale::from_str("MELODICMINOR").unwrap(),
+        Scale::from_str("DORIANFLAT2").unwrap(),
+        Scale::from_str("LYDIANAUGMENTED").unwrap(),
+        Scale::from_str("LYDIANDOMINANT").unwrap(),
+        Scale::from_str("MIXOLYDIANFLAT6").unwrap(),
+        Scale::from_str("LOCRIANNATURAL2").unwrap(),
+        Scale::from_str("ALTERED").unwrap(),
+        Scale::from_str("WHOLETONE").unwrap(),
+        Scale::from_str("HALFWHOLE").unwrap(),
+        Scale::from_str("WHOLEHALF").unwrap(),
+        Scale::from_str("BLUES").unwrap(),
+        Scale::from_str("BEBOPDOMINANT").unwrap(),
+        Scale::from_str("BEBOPMAJOR").unwrap(),
+        Scale::from_str("BEBOPDORIAN").unwrap(),
+        Scale::from_str("HIRAJOSHI").unwrap(),
+        Scale::from_str("IN").unwrap(),
+        Scale::from_str("HUNGARIANMINOR").unwrap(),
+        Scale::from_str("DOUBLEHARMONIC").unwrap(),
     ];
 
     scales.iter().choose(&mut seed).unwrap().clone()
@@ -1,18 +1,20 @@
+use num::{rational::Rational32, Zero};
 use rand::{rngs::SmallRng, seq::IteratorRandom};
-use crate::musictheory::{
-    note_value::{NoteValue, NoteValueBase, NoteValueDotted}, 
-    time_signature::TimeSignature
+use crate::{
+    musicfile::get_note_values,
+    musictheory::{
+        note_value::{NoteValue, NoteValueBase, NoteValueDotted}, 
+        time_signature::TimeSignature
+    }
 };
 
-//TODO: Split the signature, and use the same note value in each (or just randomize it completely)
-
 pub fn rhythm_pattern_generation_for_chord(time_signature: TimeSignature, mut seed: &mut SmallRng) -> Vec<NoteValue> {
     use NoteValueBase::{Whole, Half, Quarter};
     use NoteValueDotted::Dotted;
-    let _whole_note = NoteValue{base: Whole, dotted: None};
-    let _half_note = NoteValue{base: Half, dotted: None};
-    let _half_note_dotted = NoteValue{base: Half, dotted: Some(Dotted)};
-    let _quarter_note = NoteValue{base: Quarter, dotted: None};
+    let _whole_note = NoteValue{base: Whole, dotted: None, tuplet: None};
+    let _half_note = NoteValue{base: Half, dotted: None, tuplet: None};
+    let _half_note_dotted = NoteValue{base: Half, dotted: Some(Dotted), tuplet: None};
+    let _quarter_note = NoteValue{base: Quarter, dotted: None, tuplet: None};
 
     let patterns_4_4 = vec![
         vec![_whole_note],
@@ -24,54 +26,79 @@ pub fn rhythm_pattern_generation_for_chord(time_signature: TimeSignature, mut se
         vec![_half_note, _quarter_note],
     ];
 
-    if f32::from(time_signature) == 1.0 {
-        return patterns_4_4.iter().choose(&mut seed).unwrap().clone();
-    } else if f32::from(time_signature) == 0.75 {
-        return patterns_3_4.iter().choose(&mut seed).unwrap().clone();
+    match time_signature.get_fraction() {
+        (4, 4) | (2, 2) => patterns_4_4.iter().choose(&mut seed).unwrap().clone(),
+        (3, 4) => patterns_3_4.iter().choose(&mut seed).unwrap().clone(),
+        // One chord per measure
+        _ => get_note_values(Rational32::from(time_signature)),
     }
-    
-    vec![]
 }
 
 pub fn rhythm_pattern_rand_generation(time_signature: TimeSignature, mut seed: &mut SmallRng) -> Vec<NoteValue> {
     // Put the same value multiple time to weight the RNG
     // Find a way to randomize the weights
-    let note_values: Vec<NoteValue> = vec![
-        NoteValue{base: NoteValueBase::Whole, dotted: None},
-        NoteValue{base: NoteValueBase::Half, dotted: None},
-        NoteValue{base: NoteValueBase::Half, dotted: None},
-        NoteValue{base: NoteValueBase::Quarter, dotted: None},
-        NoteValue{base: NoteValueBase::Quarter, dotted: None},
-        NoteValue{base: NoteValueBase::Quarter, dotted: None},
-        NoteValue{base: NoteValueBase::Eighth, dotted: None},
-        NoteValue{base: NoteValueBase::Eighth, dotted: None},
-        NoteValue{base: NoteValueBase::Eighth, dotted: None},
-        NoteValue{base: NoteValueBase::Eighth, dotted: None},
-        NoteValue{base: NoteValueBase::Sixteenth, dotted: None},
-        NoteValue{base: NoteValueBase::Sixteenth, dotted: None},
-        NoteValue{base: NoteValueBase::Sixteenth, dotted: None},
-        NoteValue{base: NoteValueBase::Sixteenth, dotted: None},
-        NoteValue{base: NoteValueBase::Sixteenth, dotted: None},
-        NoteValue{base: NoteValueBase::Sixteenth, dotted: None},
+    let mut note_values: Vec<NoteValue> = vec![
+        NoteValue{base: NoteValueBase::Whole, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Half, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Half, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Quarter, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Quarter, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Quarter, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Eighth, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Eighth, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Eighth, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Eighth, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Sixteenth, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Sixteenth, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Sixteenth, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Sixteenth, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Sixteenth, dotted: None, tuplet: None},
+        NoteValue{base: NoteValueBase::Sixteenth, dotted: None, tuplet: None},
         // TODO: Add some dotted note (for uneven time signature)
-        // NoteValue{base: NoteValueBase::Whole, dotted: Some(NoteValueDotted::Dotted)},
-        // NoteValue{base: NoteValueBase::Half, dotted: Some(NoteValueDotted::Dotted)},
-        // NoteValue{base: NoteValueBase::Quarter, dotted: Some(NoteValueDotted::Dotted)},
-        // NoteValue{base: NoteValueBase::Eighth, dotted: Some(NoteValueDotted::Dotted)},
-        // NoteValue{base: NoteValueBase::Sixteenth, dotted: Some(NoteValueDotted::Dotted)},
+        // NoteValue{base: NoteValueBase::Whole, dotted: Some(NoteValueDotted::Dotted), tuplet: None},
+        // NoteValue{base: NoteValueBase::Half, dotted: Some(NoteValueDotted::Dotted), tuplet: None},
+        // NoteValue{base: NoteValueBase::Quarter, dotted: Some(NoteValueDotted::Dotted), tuplet: None},
+        // NoteValue{base: NoteValueBase::Eighth, dotted: Some(NoteValueDotted::Dotted), tuplet: None},
+        // NoteValue{base: NoteValueBase::Sixteenth, dotted: Some(NoteValueDotted::Dotted), tuplet: None},
     ];
+    // The shortest note is the unit of the measure or divides it, so that each group can be filled
+    if time_signature.denominator >= 32 {
+        note_values.extend([NoteValue{base: NoteValueBase::ThirtySecond, dotted: None, tuplet: None}; 4]);
+    }
+    if time_signature.denominator >= 64 {
+        note_values.extend([NoteValue{base: NoteValueBase::SixtyFourth, dotted: None, tuplet: None}; 4]);
+    }
 
     
+    // A note stays in its beat group, or covers whole groups when it starts one
+    let unit_length = Rational32::new(1, time_signature.denominator as i32);
+    let group_ends = time_signature.get_grouping().iter()
+        .scan(Rational32::zero(), |end, group| {
+            *end += unit_length * *group as i32;
+            Some(*end)
+        })
+        .collect::<Vec<Rational32>>();
+    let measure_length = Rational32::from(time_signature);
+
     let mut rhythm_pattern:Vec<NoteValue> = vec![];
-    let mut rhythm_pattern_sum = 0.0;
-    while rhythm_pattern_sum < f32::from(time_signature) {
+    let mut position = Rational32::zero();
+    while position < measure_length {
+        let group_end = *group_ends.iter().find(|end| **end > position).unwrap();
+        let is_group_start = position.is_zero() || group_ends.contains(&position);
+        let fits = |note_value: &NoteValue| {
+            let end = position + note_value.get_relative_duration();
+            end <= group_end || (is_group_start && group_ends.contains(&end))
+        };
+        if !note_values.iter().any(fits) {
+            break;
+        }
         let mut picked_note_value = note_values.iter().choose(&mut seed).unwrap();
-        while rhythm_pattern_sum + picked_note_value.get_relative_duration() > f32::from(time_signature) {
+        while !fits(picked_note_value) {
             picked_note_value = note_values.iter().choose(&mut seed).unwrap();
         }
 
         rhythm_pattern.push(picked_note_value.clone());
-        rhythm_pattern_sum = rhythm_pattern.iter().fold(0.0, |sum, nv| sum + nv.get_relative_duration());
+        position += picked_note_value.get_relative_duration();
     }
 
     rhythm_pattern
@@ -80,9 +107,9 @@ pub fn rhythm_pattern_rand_generation(time_signature: TimeSignature, mut seed: &
 pub fn rhythm_pattern_generation(time_signature: TimeSignature, mut seed: &mut SmallRng) -> Vec<NoteValue> {
     use NoteValueBase::{Quarter, Eighth};
     use NoteValueDotted::Dotted;
-    let quarter_note = NoteValue{base: Quarter, dotted: None};
-    let dotted_quarter_note = NoteValue{base: Quarter, dotted: Some(Dotted)};
-    let eighth_note = NoteValue{base: Eighth, dotted: None};
+    let quarter_note = NoteValue{base: Quarter, dotted: None, tuplet: None};
+    let dotted_quarter_note = NoteValue{base: Quarter, dotted: Some(Dotted), tuplet: None};
+    let eighth_note = NoteValue{base: Eighth, dotted: None, tuplet: None};
 
     let common_pattern_4_4 = vec![
         vec![quarter_note, quarter_note, quarter_note, eighth_note, eighth_note],
@@ -97,11 +124,9 @@ pub fn rhythm_pattern_generation(time_signature: TimeSignature, mut seed: &mut S
         vec![eighth_note, eighth_note, quarter_note, eighth_note, eighth_note],
     ];
 
-    if f32::from(time_signature) == 1.0 {
-        return common_pattern_4_4.iter().choose(&mut seed).unwrap().clone();
-    } else if f32::from(time_signature) == 0.75 {
-        return common_pattern_3_4.iter().choose(&mut seed).unwrap().clone();
+    match time_signature.get_fraction() {
+        (4, 4) | (2, 2) => common_pattern_4_4.iter().choose(&mut seed).unwrap().clone(),
+        (3, 4) => common_pattern_3_4.iter().choose(&mut seed).unwrap().clone(),
+        _ => rhythm_pattern_rand_generation(time_signature, seed),
     }
-    
-    vec![]
 }
\ No newline at end of file
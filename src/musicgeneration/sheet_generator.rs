@@ -4,12 +4,13 @@ use crate::musictheory::{
     pattern::Pattern, 
     piano_key::PianoKey, 
     scale::Scale, 
-    sheet::Sheet 
+    sheet::Sheet,
+    time_signature::TimeSignature
 };
 
 use super::pattern_generator::pattern_generation;
 
-pub fn sheet_generation(base_note: PianoKey, scale: Scale, octaves: u8, nb_measures: i32, use_common_pattern: bool, mut seed: &mut SmallRng) -> Sheet {
+pub fn sheet_generation(base_note: PianoKey, scale: Scale, octaves: u8, time_signature: TimeSignature, nb_measures: i32, use_common_pattern: bool, mut seed: &mut SmallRng) -> Sheet {
     let mut sheet = Sheet::new();  
     let mut patterns = Vec::<Pattern>::new();
     let nb_pattern = (1..4).into_iter().choose(&mut seed).unwrap();
@@ -20,6 +21,7 @@ pub fn sheet_generation(base_note: PianoKey, scale: Scale, octaves: u8, nb_measu
             base_note, 
             scale, 
             octaves, 
+            time_signature,
             nb_measures, 
             use_common_pattern,
             &mut seed
@@ -4,7 +4,7 @@ use std::{f32::consts::PI, time::Duration};
 
 use rodio::Source;
 
-use crate::{f64_to_f32, musictheory::{chord_progression::ChordProgression, hertz::Hertz, note_value::NoteValue, pitch::Pitch, tempo::Tempo}, signal::adsr_envelop::AdsrEnvelop};
+use crate::{f64_to_f32, musictheory::{chord_progression::ChordProgression, hertz::Hertz, note_value::NoteValue, tempo::Tempo, tuning::Tuning}, signal::adsr_envelop::AdsrEnvelop};
 
 pub const SAMPLE_RATE: Hertz = Hertz(44_100.0);
 pub type Sample = f32;
@@ -19,6 +19,7 @@ pub struct ChordMusicMaker {
     current_note_value: usize,
     sample_rate: Hertz,
     tempo: Tempo,
+    tuning: Tuning,
     instrument_debug: bool,
     volume: f32,
 }
@@ -34,6 +35,7 @@ impl Default for ChordMusicMaker {
             current_note_value: usize::default(),
             sample_rate: SAMPLE_RATE,
             tempo: Tempo::from(60),
+            tuning: Tuning::default(),
             instrument_debug: false,
             volume: 2.0,
         }
@@ -52,6 +54,10 @@ impl ChordMusicMaker {
         self.adsr_envelop = adsr_envelop;
         self
     }
+    pub fn set_tuning(mut self, tuning: Tuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
     fn next_chord(&mut self) {
         self.current_chord += 1;
         if self.current_chord >= self.chord_progression.chords.len() {
@@ -95,7 +101,7 @@ impl Iterator for ChordMusicMaker {
         let mut sin = 0.0_f32;
         
         self.chord_progression.chords[self.current_chord].clone().get_keys().iter().for_each(|n| {
-            let frequency = f64_to_f32(Pitch::from(*n).into());
+            let frequency = f64_to_f32(self.tuning.get_pitch(*n).into());
 
             value += self.volume
                 * PI
@@ -7,9 +7,9 @@ use crate::{
     f64_to_f32, 
     musictheory::{
         hertz::Hertz, 
-        pitch::Pitch, 
         sheet::Sheet, 
-        tempo::Tempo
+        tempo::Tempo,
+        tuning::Tuning
     }, 
     signal::adsr_envelop::AdsrEnvelop};
 
@@ -26,6 +26,7 @@ pub struct SheetMusicMaker {
     adsr_envelop: AdsrEnvelop,
     sample_rate: Hertz,
     tempo: Tempo,
+    tuning: Tuning,
     instrument_debug: bool,
     volume: f32,
 }
@@ -41,6 +42,7 @@ impl Default for SheetMusicMaker {
             adsr_envelop: AdsrEnvelop::default(),
             sample_rate: SAMPLE_RATE,
             tempo: Tempo::from(60),
+            tuning: Tuning::default(),
             instrument_debug: false,
             volume: 2.0,
         }
@@ -58,9 +60,13 @@ impl SheetMusicMaker {
         self.adsr_envelop = adsr_envelop;
         self
     }
+    pub fn set_tuning(mut self, tuning: Tuning) -> Self {
+        self.tuning = tuning;
+        self
+    }
     fn get_frequency(&mut self) -> Sample {
         let current_sheet_note = self.sheet.patterns[self.current_pattern].measures[self.current_measure].notes[self.current_note];
-        let pitch = Pitch::from(current_sheet_note.note);
+        let pitch = self.tuning.get_pitch(current_sheet_note.note);
         f64_to_f32(pitch.into())
     }
     fn next_note(&mut self) {
@@ -1,9 +1,11 @@
 use core::fmt;
 use std::{io, str::FromStr};
 
-use super::{interval::Interval, piano_key::PianoKey, pitch::Pitch};
+use super::{interval::Interval, piano_key::PianoKey, pitch::Pitch, spelling::{get_interval_steps, spell_interval}};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ChordType {
     MajorTriad,
     MajorSixth,
@@ -43,6 +45,19 @@ impl ChordType {
             Self::CustomChord => vec![],
         }
     }
+
+    // Scale degree of each interval, used for the spelling (the diminished seventh is a seventh, not a sixth)
+    pub fn get_degrees(self) -> Vec<u8> {
+        match self {
+            Self::MajorSixth | Self::MinorSixth => vec![3, 5, 6],
+            Self::DominantSeventh | Self::AugmentedSeventh | Self::MinorSeventh | Self::MinorMajorSeventh
+                | Self::DiminishedSeventh | Self::HalfDiminishedSeventh => vec![3, 5, 7],
+            Self::MajorTriad | Self::AugmentedTriad | Self::MinorTriad | Self::DiminishedTriad => vec![3, 5],
+            Self::PowerDiad => vec![5],
+            Self::PowerTriad => vec![5, 8],
+            Self::CustomChord => vec![],
+        }
+    }
 }
 
 impl Default for ChordType {
@@ -101,6 +116,8 @@ impl fmt::Display for ChordType {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum ChordInversion {
     Root = 0,
     First,
@@ -115,10 +132,13 @@ impl Default for ChordInversion {
 }
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Chord {
     pub base_note: PianoKey,
     pub chord_type: ChordType,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
     pub intervals: Option<Vec<Interval>>,
+    #[cfg_attr(feature = "serde", serde(default))]
     pub inversion: ChordInversion,
 }
 
@@ -163,11 +183,11 @@ impl Chord {
         ret.push(self.base_note);
         if self.chord_type == ChordType::CustomChord && self.intervals.is_some() {
             self.intervals.unwrap().iter().for_each(|i| {
-                ret.push(self.base_note + *i);
+                ret.push(spell_interval(self.base_note, *i, get_interval_steps(*i)));
             });
         } else {
-            self.chord_type.get_intervals().iter().for_each(|i| {
-                ret.push(self.base_note + *i);
+            self.chord_type.get_intervals().iter().zip(self.chord_type.get_degrees()).for_each(|(i, degree)| {
+                ret.push(spell_interval(self.base_note, *i, degree - 1));
             });
         }
 
@@ -6,6 +6,7 @@ use crate::musictheory::chord::ChordInversion;
 use super::{chord::{Chord, ChordType}, key::Key, piano_key::PianoKey, scale::Scale};
 
 #[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ChordProgression {
     pub progression: String,
     pub chords: Vec<Chord>
@@ -0,0 +1,279 @@
+// Interval with a quality, a number (a 9th is 9, not a 2nd) and a direction
+// Unlike Interval it is not reduced to an octave and keeps the spelling: an augmented 4th is not a diminished 5th
+
+use core::fmt;
+use std::{io, str::FromStr};
+
+use super::{cent::Cent, interval::Interval, piano_key::PianoKey, semitone::Semitone, spelling::spell_piano_key};
+
+// Semitones of the major or perfect simple intervals, from the unison to the 7th
+const SIMPLE_SEMITONES: [i8; 7] = [0, 2, 4, 5, 7, 9, 11];
+// Ten octaves, more than the keyboard
+const MAX_NUMBER: u8 = 71;
+// Name of each number of semitones in an octave, the tritone is an augmented 4th
+const DEFAULT_INTERVALS: [(IntervalQuality, u8); 12] = [
+    (IntervalQuality::Perfect, 1), (IntervalQuality::Minor, 2), (IntervalQuality::Major, 2), (IntervalQuality::Minor, 3),
+    (IntervalQuality::Major, 3), (IntervalQuality::Perfect, 4), (IntervalQuality::Augmented, 4), (IntervalQuality::Perfect, 5),
+    (IntervalQuality::Minor, 6), (IntervalQuality::Major, 6), (IntervalQuality::Minor, 7), (IntervalQuality::Major, 7),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum IntervalQuality {
+    Diminished,
+    Minor,
+    Perfect,
+    Major,
+    Augmented,
+}
+
+impl fmt::Display for IntervalQuality {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use IntervalQuality::*;
+        let s = match self {
+            Diminished => "d",
+            Minor => "m",
+            Perfect => "P",
+            Major => "M",
+            Augmented => "A",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl FromStr for IntervalQuality {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use IntervalQuality::*;
+        match s {
+            "d" | "dim" => Ok(Diminished),
+            "m" | "min" => Ok(Minor),
+            "P" | "perf" => Ok(Perfect),
+            "M" | "maj" => Ok(Major),
+            "A" | "aug" => Ok(Augmented),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a valid interval quality", s),
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum IntervalDirection {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DiatonicInterval {
+    pub quality: IntervalQuality,
+    // 1 is the unison, 8 the octave, 9 the ninth...
+    pub number: u8,
+    pub direction: IntervalDirection,
+}
+
+impl DiatonicInterval {
+    pub fn new(quality: IntervalQuality, number: u8, direction: IntervalDirection) -> Result<Self, io::Error> {
+        use IntervalQuality::*;
+        let is_valid = match quality {
+            _ if number == 0 || number > MAX_NUMBER => false,
+            Diminished => number > 1,
+            Perfect => is_perfect_number(number),
+            Minor | Major => !is_perfect_number(number),
+            Augmented => true,
+        };
+        if !is_valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}{} is not a valid interval", quality, number),
+            ));
+        }
+        Ok(Self { quality, number, direction })
+    }
+
+    // Interval from one key to another, the letters give the number and the semitones the quality
+    pub fn between(from: PianoKey, to: PianoKey) -> Result<Self, io::Error> {
+        let get_steps = |piano_key: PianoKey| piano_key.note.letter as i32 + 7 * piano_key.octave as i32;
+        let steps = get_steps(to) - get_steps(from);
+        let semitones = to.get_midi_note_number() as i32 - from.get_midi_note_number() as i32;
+        let direction = if steps < 0 || (steps == 0 && semitones < 0) {
+            IntervalDirection::Descending
+        } else {
+            IntervalDirection::Ascending
+        };
+        let (steps, semitones) = if direction == IntervalDirection::Descending { (-steps, -semitones) } else { (steps, semitones) };
+        let number = steps as u8 + 1;
+        let deviation = semitones - get_base_semitones(number) as i32;
+        let quality = match (is_perfect_number(number), deviation) {
+            (true, 0) => IntervalQuality::Perfect,
+            (false, 0) => IntervalQuality::Major,
+            (false, -1) => IntervalQuality::Minor,
+            (true, -1) | (false, -2) => IntervalQuality::Diminished,
+            (_, 1) => IntervalQuality::Augmented,
+            _ => return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("The interval from {} to {} has no quality", from, to),
+            )),
+        };
+        Self::new(quality, number, direction)
+    }
+
+    pub fn get_semitones(self) -> i8 {
+        use IntervalQuality::*;
+        let offset = match self.quality {
+            Diminished if is_perfect_number(self.number) => -1,
+            Diminished => -2,
+            Minor => -1,
+            Perfect | Major => 0,
+            Augmented => 1,
+        };
+        let semitones = get_base_semitones(self.number) + offset;
+        match self.direction {
+            IntervalDirection::Ascending => semitones,
+            IntervalDirection::Descending => -semitones,
+        }
+    }
+
+    pub fn is_compound(self) -> bool {
+        self.number > 8
+    }
+
+    // The interval reduced to an octave (a major 9th is a major 2nd), an octave stays an octave
+    pub fn get_simple(self) -> Self {
+        if !self.is_compound() {
+            return self;
+        }
+        Self { number: (self.number - 2) % 7 + 2, ..self }
+    }
+
+    // The simple interval that completes it to an octave (a major 3rd gives a minor 6th)
+    pub fn invert(self) -> Self {
+        use IntervalQuality::*;
+        let simple = self.get_simple();
+        let quality = match simple.quality {
+            Diminished => Augmented,
+            Minor => Major,
+            Perfect => Perfect,
+            Major => Minor,
+            Augmented => Diminished,
+        };
+        Self { quality, number: 9 - simple.number, ..simple }
+    }
+
+    pub fn get_reversed(self) -> Self {
+        let direction = match self.direction {
+            IntervalDirection::Ascending => IntervalDirection::Descending,
+            IntervalDirection::Descending => IntervalDirection::Ascending,
+        };
+        Self { direction, ..self }
+    }
+
+    // The key at this interval, spelled from the letters (a minor 3rd above A is C, an augmented 2nd is B#)
+    pub fn get_piano_key_from(self, piano_key: PianoKey) -> Result<PianoKey, io::Error> {
+        let steps = (self.number - 1) % 7;
+        let letter = match self.direction {
+            IntervalDirection::Ascending => piano_key.note.letter.add_steps(steps),
+            IntervalDirection::Descending => piano_key.note.letter.add_steps(7 - steps),
+        };
+        let midi_note_number = piano_key.get_midi_note_number() as i32 + self.get_semitones() as i32;
+        u8::try_from(midi_note_number).ok()
+            .and_then(|n| spell_piano_key(n, letter))
+            .ok_or_else(|| io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} from {} is not on the keyboard", self, piano_key),
+            ))
+    }
+}
+
+// Fails past ten octaves
+impl TryFrom<Semitone> for DiatonicInterval {
+    type Error = io::Error;
+
+    fn try_from(s: Semitone) -> Result<Self, Self::Error> {
+        let semitones = i8::from(s);
+        let (quality, number) = DEFAULT_INTERVALS[(semitones.unsigned_abs() % 12) as usize];
+        let direction = if semitones < 0 { IntervalDirection::Descending } else { IntervalDirection::Ascending };
+        // A perfect unison plus octaves is an octave
+        let octaves = semitones.unsigned_abs() / 12;
+        let number = if number == 1 && octaves > 0 { 8 + 7 * (octaves - 1) } else { number + 7 * octaves };
+        Self::new(quality, number, direction)
+    }
+}
+
+// Nearest number of semitones, so a just major 3rd (386 cents) is a major 3rd
+impl TryFrom<Cent> for DiatonicInterval {
+    type Error = io::Error;
+
+    fn try_from(c: Cent) -> Result<Self, Self::Error> {
+        let semitones = (f64::from(c) / f64::from(Cent::from(Semitone(1)))).round();
+        if !(i8::MIN as f64..=i8::MAX as f64).contains(&semitones) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} cents is not a valid interval", f64::from(c)),
+            ));
+        }
+        Self::try_from(Semitone(semitones as i8))
+    }
+}
+
+impl From<Interval> for DiatonicInterval {
+    fn from(i: Interval) -> Self {
+        // An Interval is at most an octave
+        Self::try_from(Semitone::from(i)).unwrap()
+    }
+}
+
+impl From<DiatonicInterval> for Semitone {
+    fn from(i: DiatonicInterval) -> Self {
+        Semitone(i.get_semitones())
+    }
+}
+
+impl From<DiatonicInterval> for Cent {
+    fn from(i: DiatonicInterval) -> Self {
+        Semitone::from(i).into()
+    }
+}
+
+impl fmt::Display for DiatonicInterval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.direction == IntervalDirection::Descending { "-" } else { "" };
+        write!(f, "{}{}{}", sign, self.quality, self.number)
+    }
+}
+
+// "M3", "P5", "m9", "A4", "d5", "-m3" for a descending minor 3rd
+impl FromStr for DiatonicInterval {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (direction, s) = match s.strip_prefix('-') {
+            Some(s) => (IntervalDirection::Descending, s),
+            None => (IntervalDirection::Ascending, s),
+        };
+        let number_start = s.find(|c: char| c.is_ascii_digit()).unwrap_or(s.len());
+        let quality = IntervalQuality::from_str(&s[..number_start])?;
+        let number = s[number_start..].parse::<u8>().map_err(|_| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a valid interval", s),
+        ))?;
+        Self::new(quality, number, direction)
+    }
+}
+
+// Unisons, 4ths, 5ths and their compounds are perfect
+fn is_perfect_number(number: u8) -> bool {
+    matches!((number.max(1) - 1) % 7, 0 | 3 | 4)
+}
+
+fn get_base_semitones(number: u8) -> i8 {
+    let steps = number.max(1) - 1;
+    SIMPLE_SEMITONES[(steps % 7) as usize] + 12 * (steps / 7) as i8
+}
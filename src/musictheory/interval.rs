@@ -6,6 +6,8 @@ use std::ops::{Add, AddAssign, Sub};
 use super::semitone::Semitone;
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Interval {
     Unison = 0,
     Min2,
@@ -45,7 +47,7 @@ impl From<Semitone> for Interval {
             9 => Maj6,
             10 => Min7,
             11 => Maj7,
-            12 | _ => Interval::from(Semitone(int_semitones % Octave as i8)),
+            _ => Interval::from(Semitone(int_semitones.rem_euclid(Octave as i8))),
         }
     }
 }
@@ -60,7 +62,7 @@ impl Add for Interval {
     type Output = Self;
     fn add(self, rhs: Self) -> Self {
         Interval::from(Semitone(
-            i8::from(self) + i8::from(rhs) % Interval::Octave as i8,
+            (i8::from(self) + i8::from(rhs)) % Interval::Octave as i8,
         ))
     }
 }
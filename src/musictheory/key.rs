@@ -2,11 +2,12 @@
 // License: MIT
 
 use core::fmt;
-use std::str::FromStr;
+use std::{io, str::FromStr};
 
-use super::{interval::Interval, note::Note, piano_key::PianoKey, scale::Scale};
+use super::{cent::Cent, note::Note, piano_key::PianoKey, pitch::Pitch, scale::Scale, spelling::{spell_piano_key, spell_scale}};
 
 #[derive(Debug, Default, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Key {
     pub base_note: PianoKey,
     pub octaves: u8,
@@ -43,14 +44,29 @@ impl Key {
     }
 
     pub fn get_notes(self) -> Vec<Note> {
-        let mut ret = vec![self.base_note.note];
-        let mut offset = Interval::Unison;
+        spell_scale(self.base_note.note, &self.scale.get_intervals())
+    }
 
-        self.scale.get_intervals().iter().for_each(|i| {
-            offset += *i;
-            ret.push(self.base_note.note + offset)
-        });
-        ret
+    // Snaps the pitch to the nearest degree of the key in any octave, spelled like the key,
+    // ties go to the lower key, the cents are the deviation of the pitch from that key
+    pub fn quantize(self, pitch: Pitch) -> Result<(PianoKey, Cent), io::Error> {
+        let midi_note_number = pitch.get_midi_note_number()?;
+        let notes = self.get_notes();
+        let lowest = midi_note_number.floor() as i32 - 12;
+        (lowest..lowest + 25)
+            .filter_map(|n| {
+                let note = notes.iter().find(|note| note.get_pitch_class() as i32 == n.rem_euclid(12))?;
+                u8::try_from(n).ok().and_then(|n| spell_piano_key(n, note.letter))
+            })
+            .min_by(|a, b| {
+                let distance = |piano_key: &PianoKey| (piano_key.get_midi_note_number() as f64 - midi_note_number).abs();
+                distance(a).total_cmp(&distance(b))
+            })
+            .map(|piano_key| (piano_key, Cent(100.0 * (midi_note_number - piano_key.get_midi_note_number() as f64))))
+            .ok_or_else(|| io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} Hz is out of the keyboard range", f64::from(pitch)),
+            ))
     }
 }
 
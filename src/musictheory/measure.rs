@@ -1,11 +1,15 @@
 use core::fmt;
+use std::{io, str::FromStr};
+
+use num::{rational::Rational32, Zero};
 
 use super::{note_value::NoteValue, piano_key::PianoKey, sheet_note::SheetNote, time_signature::TimeSignature};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Measure {
     pub notes: Vec<SheetNote>,
-    pub time_signature: TimeSignature //this will be the time signature at the end, it should work nice, because 3/4 is literally 0.75, we'll see
+    pub time_signature: TimeSignature
 }
 
 impl Default for Measure {
@@ -25,15 +29,15 @@ impl Measure {
         }
     }
 
-    pub fn get_remaining_value(&self) -> f32 {
-        let mut note_values_sum = 0.0;
+    pub fn get_remaining_value(&self) -> Rational32 {
+        let mut note_values_sum = Rational32::zero();
         self.notes.iter().for_each(|n| note_values_sum += n.value.get_relative_duration());
 
-        f32::from(self.time_signature) - note_values_sum
+        Rational32::from(self.time_signature) - note_values_sum
     }
 
     pub fn is_measure_complete(&self) -> bool {
-        self.get_remaining_value() == 0.0
+        self.get_remaining_value().is_zero()
     }
 
     pub fn add_note(&mut self, note: PianoKey, value: NoteValue) {
@@ -42,6 +46,47 @@ impl Measure {
         }
         self.notes.push(SheetNote {note, value});
     }
+
+    // ASCII notation: notes separated by spaces, optionally preceded by a time signature (3/4 C4h D4q),
+    // the time signature argument is used when there is none
+    pub fn parse(s: &str, time_signature: TimeSignature) -> Result<Self, io::Error> {
+        let mut tokens = s.split_whitespace().peekable();
+        let time_signature = match tokens.next_if(|t| t.contains('/')) {
+            Some(t) => TimeSignature::from_str(t)?,
+            None => time_signature,
+        };
+
+        let mut measure = Measure::new(time_signature);
+        for token in tokens {
+            let sheet_note = SheetNote::from_str(token)?;
+            if sheet_note.value.get_relative_duration() > measure.get_remaining_value() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} overflows the {} measure", token, time_signature),
+                ));
+            }
+            measure.add_note(sheet_note.note, sheet_note.value);
+        }
+        if measure.notes.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the {} measure has no notes", time_signature),
+            ));
+        }
+        Ok(measure)
+    }
+
+    pub fn to_ascii(&self) -> String {
+        self.notes.iter().map(|sn| sn.to_ascii()).collect::<Vec<String>>().join(" ")
+    }
+}
+
+impl FromStr for Measure {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, TimeSignature::default())
+    }
 }
 
 impl fmt::Display for Measure {
@@ -1,5 +1,6 @@
 pub mod cent;
 pub mod interval;
+pub mod diatonic_interval;
 pub mod key;
 pub mod mode;
 pub mod note;
@@ -8,6 +9,8 @@ pub mod scale;
 pub mod semitone;
 pub mod hertz;
 pub mod pitch;
+pub mod tuning;
+pub mod scala_tuning;
 pub mod chord;
 pub mod tempo;
 pub mod chord_progression;
@@ -17,6 +20,7 @@ pub mod measure;
 pub mod pattern;
 pub mod sheet;
 pub mod time_signature;
+pub mod spelling;
 
 pub fn char_strs<'a>(s: &'a str) -> Vec<&'a str> {
     s.split("")
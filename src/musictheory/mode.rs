@@ -4,6 +4,8 @@
 use super::{interval::Interval, scale::ScaleLength};
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Mode {
     Ionian = 0,
     Dorian,
@@ -15,6 +17,8 @@ pub enum Mode {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum PentatonicMode {
     Major = 0,
     Suspended,
@@ -32,4 +36,65 @@ impl Mode {
             Heptatonic | _ => vec![Maj2, Maj2, Min2, Maj2, Maj2, Maj2, Min2]
         }
     }
-}
\ No newline at end of file
+}
+
+// Modes of the harmonic minor scale, from its first degree
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum HarmonicMinorMode {
+    HarmonicMinor = 0,
+    LocrianNatural6,
+    IonianAugmented,
+    UkrainianDorian,
+    PhrygianDominant,
+    LydianSharp2,
+    AlteredDiminished,
+}
+
+impl HarmonicMinorMode {
+    pub fn base_interval() -> Vec<Interval> {
+        use Interval::*;
+        vec![Maj2, Min2, Maj2, Maj2, Min2, Min3, Min2]
+    }
+}
+
+// Modes of the ascending melodic minor scale, from its first degree
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum MelodicMinorMode {
+    MelodicMinor = 0,
+    DorianFlat2,
+    LydianAugmented,
+    LydianDominant,
+    MixolydianFlat6,
+    LocrianNatural2,
+    Altered,
+}
+
+impl MelodicMinorMode {
+    pub fn base_interval() -> Vec<Interval> {
+        use Interval::*;
+        vec![Maj2, Min2, Maj2, Maj2, Maj2, Maj2, Min2]
+    }
+}
+
+// The diminished scale starting with a half step or a whole step
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum OctatonicMode {
+    HalfWhole = 0,
+    WholeHalf,
+}
+
+// Heptatonic scales with a chromatic passing note, so that the chord tones fall on the beats
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BebopMode {
+    Dominant,
+    Major,
+    Dorian,
+}
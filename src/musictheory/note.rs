@@ -12,7 +12,7 @@ use super::char_strs;
 use super::interval::Interval;
 use super::semitone::Semitone;
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum NoteLetter {
     C = 0,
     D,
@@ -71,20 +71,41 @@ impl NoteLetter {
             .take(self as usize)
             .fold(Unison, |acc, i| acc + *i)
     }
+
+    pub fn get_semitones_from_c(self) -> i8 {
+        use NoteLetter::*;
+        match self {
+            C => 0,
+            D => 2,
+            E => 4,
+            F => 5,
+            G => 7,
+            A => 9,
+            B => 11,
+        }
+    }
+
+    pub fn add_steps(self, steps: u8) -> Self {
+        (0..steps % 7).fold(self, |letter, _| letter.inc())
+    }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Accidental {
+    DoubleFlat,
     Flat,
     Sharp,
+    DoubleSharp,
 }
 
 impl fmt::Display for Accidental {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use Accidental::*;
         let acc_str = match self {
+            DoubleFlat => "bb",
             Flat => "b",
-            Sharp => "#"
+            Sharp => "#",
+            DoubleSharp => "x",
         };
         write!(f, "{}", acc_str)
     }
@@ -95,8 +116,10 @@ impl FromStr for Accidental {
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         match s {
+            "bb" => Ok(Accidental::DoubleFlat),
             "b" => Ok(Accidental::Flat),
             "#" => Ok(Accidental::Sharp),
+            "x" | "##" => Ok(Accidental::DoubleSharp),
             _ => Err(io::Error::new(
                 io::ErrorKind::InvalidInput,
                 format!("{} is not a valid accidental", s),
@@ -105,7 +128,35 @@ impl FromStr for Accidental {
     }
 }
 
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+impl Accidental {
+    // Semitones added to the letter
+    pub fn get_alter(self) -> i8 {
+        use Accidental::*;
+        match self {
+            DoubleFlat => -2,
+            Flat => -1,
+            Sharp => 1,
+            DoubleSharp => 2,
+        }
+    }
+
+    pub fn from_alter(alter: i8) -> Result<Option<Self>, io::Error> {
+        use Accidental::*;
+        match alter {
+            -2 => Ok(Some(DoubleFlat)),
+            -1 => Ok(Some(Flat)),
+            0 => Ok(None),
+            1 => Ok(Some(Sharp)),
+            2 => Ok(Some(DoubleSharp)),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} semitones can not be written with an accidental", alter),
+            )),
+        }
+    }
+}
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Note {
     pub accidental: Option<Accidental>,
     pub letter: NoteLetter,
@@ -130,9 +181,10 @@ impl FromStr for Note {
         // note will be first
         if let Some(letter) = char_strs.next() {
             let letter = NoteLetter::from_str(letter)?;
-            if let Some(accidental) = char_strs.next() {
+            let accidental = char_strs.copied().collect::<String>();
+            if !accidental.is_empty() {
                 //check if it's valid
-                let accidental = Accidental::from_str(accidental)?;
+                let accidental = Accidental::from_str(&accidental)?;
                 return Ok(Self {
                     letter,
                     accidental: Some(accidental),
@@ -163,16 +215,16 @@ impl From<Interval> for Note {
     }
 }
 
+// Spelled with sharps, see the spelling module for key-aware spelling
 impl Add<Interval> for Note {
     type Output = Self;
 
     fn add(self, rhs: Interval) -> Self {
-        let semitones = Semitone::from(rhs);
-        let mut ret = self;
-        for _ in 0..i8::from(semitones) {
-            ret.inc();
+        let semitones = i8::from(Semitone::from(rhs));
+        if semitones == 0 {
+            return self;
         }
-        ret
+        Self::from_pitch_class((self.get_pitch_class() as i8 + semitones) as u8)
     }
 }
 
@@ -182,41 +234,40 @@ impl AddAssign<Interval> for Note {
     }
 }
 
+// Spelling used when the key is unknown
+const SHARP_NOTES: [(NoteLetter, Option<Accidental>); 12] = [
+    (NoteLetter::C, None), (NoteLetter::C, Some(Accidental::Sharp)), (NoteLetter::D, None), (NoteLetter::D, Some(Accidental::Sharp)),
+    (NoteLetter::E, None), (NoteLetter::F, None), (NoteLetter::F, Some(Accidental::Sharp)), (NoteLetter::G, None),
+    (NoteLetter::G, Some(Accidental::Sharp)), (NoteLetter::A, None), (NoteLetter::A, Some(Accidental::Sharp)), (NoteLetter::B, None),
+];
+
 impl Note {
+    pub fn from_pitch_class(pitch_class: u8) -> Self {
+        let (letter, accidental) = SHARP_NOTES[(pitch_class % 12) as usize];
+        Self { letter, accidental }
+    }
+
+    pub fn get_alter(self) -> i8 {
+        self.accidental.map(|a| a.get_alter()).unwrap_or(0)
+    }
+
+    // Can be negative (Cb) or above 11 (B#), the octave of a piano key follows the letter
+    pub fn get_semitones_from_c(self) -> i8 {
+        self.letter.get_semitones_from_c() + self.get_alter()
+    }
+
+    // 0 is C, 11 is B
+    pub fn get_pitch_class(self) -> u8 {
+        self.get_semitones_from_c().rem_euclid(12) as u8
+    }
+
     pub fn interval_from_c(self) -> Interval {
-        use Accidental::*;
-        let ret = self.letter.interval_from_c();
-        if let Some(acc) = self.accidental {
-            match acc {
-                Flat => return Interval::from(Semitone::from(i8::from(Semitone::from(ret)) - 1)),
-                Sharp => return ret + Interval::Min2
-            }
-        };
-        ret
+        Interval::from(Semitone::from(self.get_pitch_class() as i8))
     }
+
     pub fn get_offset(self, other: Self) -> Interval {
         let self_interval_from_c = self.interval_from_c();
         let other_interval_from_c = other.interval_from_c();
         self_interval_from_c - other_interval_from_c
     }
-    fn inc(&mut self) {
-        use Accidental::*;
-        use NoteLetter::*;
-
-        if let Some(acc) = self.accidental {
-            self.accidental = None;
-            match acc {
-                Sharp => {
-                    self.letter = self.letter.inc();
-                }
-                Flat => {}
-            }
-        } else {
-            if self.letter == B || self.letter == E {
-                self.letter = self.letter.inc();
-            } else {
-                self.accidental = Some(Sharp);
-            }
-        }
-    }
-}
\ No newline at end of file
+}
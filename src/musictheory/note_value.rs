@@ -1,13 +1,22 @@
 use core::fmt;
+use std::{io, str::FromStr};
+
+use num::{rational::Rational32, ToPrimitive};
+
 use super::tempo::Tempo;
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum NoteValueBase {
     Whole=1,
     Half=2,
+    #[default]
     Quarter=4,
     Eighth=8,
     Sixteenth=16,
+    ThirtySecond=32,
+    SixtyFourth=64,
 }
 
 impl From<NoteValueBase> for i8 {
@@ -16,12 +25,6 @@ impl From<NoteValueBase> for i8 {
     }
 }
 
-impl Default for NoteValueBase {
-    fn default() -> Self {
-        Self::Quarter
-    }
-}
-
 impl fmt::Display for NoteValueBase {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use NoteValueBase::*;
@@ -31,6 +34,8 @@ impl fmt::Display for NoteValueBase {
             Quarter => "𝅘𝅥",
             Eighth => "𝅘𝅥𝅮",
             Sixteenth => "𝅘𝅥𝅯",
+            ThirtySecond => "𝅘𝅥𝅰",
+            SixtyFourth => "𝅘𝅥𝅱",
         };
 
         write!(
@@ -42,12 +47,20 @@ impl fmt::Display for NoteValueBase {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum NoteValueDotted {
     Dotted=2,
     DoubleDotted=4,
     TripleDotted=8,
 }
 
+impl NoteValueDotted {
+    pub fn get_nb_dots(self) -> u8 {
+        (self as u8).trailing_zeros() as u8
+    }
+}
+
 impl fmt::Display for NoteValueDotted {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use NoteValueDotted::*;
@@ -60,21 +73,93 @@ impl fmt::Display for NoteValueDotted {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-pub struct NoteValue {
-    pub base: NoteValueBase,
-    pub dotted: Option<NoteValueDotted>,
+// Larger tuplets would make the exact durations of a measure overflow
+const MAX_TUPLET_NOTES: u8 = 16;
+
+// Actual notes played in the time of the normal notes of the same value: 3:2 for a triplet, 5:4 for a quintuplet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Tuplet {
+    pub actual_notes: u8,
+    pub normal_notes: u8,
 }
 
-impl Default for NoteValue {
-    fn default() -> Self {
-        NoteValue {
-            base: NoteValueBase::default(),
-            dotted: None,
+impl Tuplet {
+    pub fn new(actual_notes: u8, normal_notes: u8) -> Result<Self, io::Error> {
+        if actual_notes < 2 || normal_notes == 0 || actual_notes == normal_notes
+            || actual_notes > MAX_TUPLET_NOTES || normal_notes > MAX_TUPLET_NOTES {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}:{} is not a valid tuplet", actual_notes, normal_notes),
+            ));
         }
+        Ok(Tuplet { actual_notes, normal_notes })
+    }
+
+    // The usual ratio for this number of notes, see get_default_normal_notes
+    pub fn from_actual_notes(actual_notes: u8) -> Result<Self, io::Error> {
+        Self::new(actual_notes, get_default_normal_notes(actual_notes))
+    }
+
+    pub fn triplet() -> Self {
+        Tuplet { actual_notes: 3, normal_notes: 2 }
+    }
+
+    // Factor applied to the duration of each note
+    pub fn get_ratio(&self) -> Rational32 {
+        Rational32::new(self.normal_notes as i32, self.actual_notes as i32)
     }
 }
 
+// "3" for the usual ratio, "7:8" otherwise
+impl fmt::Display for Tuplet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.normal_notes == get_default_normal_notes(self.actual_notes) {
+            return write!(f, "{}", self.actual_notes);
+        }
+        write!(f, "{}:{}", self.actual_notes, self.normal_notes)
+    }
+}
+
+impl FromStr for Tuplet {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not a valid tuplet", s),
+        );
+        match s.split_once(':') {
+            Some((actual_notes, normal_notes)) => Self::new(
+                actual_notes.parse::<u8>().map_err(|_| invalid())?,
+                normal_notes.parse::<u8>().map_err(|_| invalid())?,
+            ),
+            None => Self::from_actual_notes(s.parse::<u8>().map_err(|_| invalid())?),
+        }
+    }
+}
+
+// Tuplets are played in the time of the largest power of 2 below them (3:2, 5:4, 7:4),
+// duplets and quadruplets in the time of 3 (2:3, 4:3, 8:6)
+pub fn get_default_normal_notes(actual_notes: u8) -> u8 {
+    match actual_notes {
+        0 | 1 => 1,
+        2 => 3,
+        n if n.is_power_of_two() => n / 4 * 3,
+        n => 1 << (7 - (n - 1).leading_zeros()),
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, PartialOrd)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct NoteValue {
+    pub base: NoteValueBase,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub dotted: Option<NoteValueDotted>,
+    #[cfg_attr(feature = "serde", serde(default, skip_serializing_if = "Option::is_none"))]
+    pub tuplet: Option<Tuplet>,
+}
+
 impl fmt::Display for NoteValue {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let dotted_str = if let Some(d) = self.dotted {
@@ -82,28 +167,108 @@ impl fmt::Display for NoteValue {
         } else {
             "".to_string()
         };
-        
-        write!(f, "{}{}", self.base, dotted_str)
+        let tuplet_str = if let Some(t) = self.tuplet {
+            format!(":{}", t)
+        } else {
+            "".to_string()
+        };
+
+        write!(f, "{}{}{}", self.base, dotted_str, tuplet_str)
+    }
+}
+
+impl FromStr for NoteValue {
+    type Err = io::Error;
+
+    // ASCII notation: w, h, q, e, s (or 1, 2, 4, 8, 16), 32, 64 followed by the dots,
+    // then the tuplet after a colon (e:3 for a triplet eighth, s:7:8 for a septuplet sixteenth)
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use NoteValueBase::*;
+        use NoteValueDotted::*;
+        let (value_str, tuplet) = match s.split_once(':') {
+            Some((value_str, tuplet_str)) => (value_str, Some(Tuplet::from_str(tuplet_str)?)),
+            None => (s, None),
+        };
+        let base_str = value_str.trim_end_matches('.');
+        let base = match base_str {
+            "w" | "1" => Whole,
+            "h" | "2" => Half,
+            "q" | "4" => Quarter,
+            "e" | "8" => Eighth,
+            "s" | "16" => Sixteenth,
+            "32" => ThirtySecond,
+            "64" => SixtyFourth,
+            _ => return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a valid note value", s),
+            )),
+        };
+        let dotted = match value_str.len() - base_str.len() {
+            0 => None,
+            1 => Some(Dotted),
+            2 => Some(DoubleDotted),
+            3 => Some(TripleDotted),
+            _ => return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} has too many dots", s),
+            )),
+        };
+
+        Ok(NoteValue { base, dotted, tuplet })
     }
 }
 
 impl NoteValue {
-    pub fn get_relative_duration(&self) -> f32 {
-        let base_duration = 1.0 / i8::from(self.base) as f32;
+    pub fn to_ascii(&self) -> String {
+        use NoteValueBase::*;
+        let base_str = match self.base {
+            Whole => "w",
+            Half => "h",
+            Quarter => "q",
+            Eighth => "e",
+            Sixteenth => "s",
+            ThirtySecond => "32",
+            SixtyFourth => "64",
+        };
+        let dotted_str = if let Some(d) = self.dotted {
+            format!("{}", d)
+        } else {
+            "".to_string()
+        };
+        let tuplet_str = if let Some(t) = self.tuplet {
+            format!(":{}", t)
+        } else {
+            "".to_string()
+        };
+
+        format!("{}{}{}", base_str, dotted_str, tuplet_str)
+    }
+
+    // Exact duration relative to a whole note, a triplet eighth is 1/12
+    pub fn get_relative_duration(&self) -> Rational32 {
+        match self.tuplet {
+            Some(t) => self.get_written_duration() * t.get_ratio(),
+            None => self.get_written_duration(),
+        }
+    }
+
+    // Duration of the note as written, without the tuplet
+    pub fn get_written_duration(&self) -> Rational32 {
+        let base_duration = Rational32::new(1, i8::from(self.base) as i32);
         let dotted_duration = if let Some(d) = self.dotted {
             match d {
-                NoteValueDotted::Dotted => base_duration / 2.0,
-                NoteValueDotted::DoubleDotted => base_duration / 2.0 + base_duration / 4.0,
-                NoteValueDotted::TripleDotted => base_duration / 2.0 + base_duration / 4.0 + base_duration / 8.0,
+                NoteValueDotted::Dotted => base_duration / 2,
+                NoteValueDotted::DoubleDotted => base_duration / 2 + base_duration / 4,
+                NoteValueDotted::TripleDotted => base_duration / 2 + base_duration / 4 + base_duration / 8,
             }
         } else {
-            0.0
+            Rational32::from_integer(0)
         };
-        
+
         base_duration + dotted_duration
     }
 
     pub fn get_duration_for_tempo(&self, tempo: Tempo) -> f32 {
-        (self.get_relative_duration() * 4.0) / tempo.get_bps()
+        (self.get_relative_duration() * 4).to_f32().unwrap_or_default() / tempo.get_bps()
     }
 }
\ No newline at end of file
@@ -3,6 +3,7 @@ use core::fmt;
 use super::measure::Measure;
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Pattern {
     pub name: String,
     pub measures: Vec<Measure>,
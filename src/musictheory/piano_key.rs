@@ -1,14 +1,14 @@
 // Author: Ben Lovy <ben@deciduously.com>
 // License: MIT
 
-use core::fmt;
+use core::{cmp::Ordering, fmt};
 use std::{io, ops::Add, str::FromStr};
 
-use crate::musictheory::note::{Accidental, NoteLetter};
-
 use super::{char_strs, interval::Interval, note::Note, semitone::Semitone};
 
-#[derive(Default, Debug, Clone, Copy, PartialEq)]
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "String", try_from = "String"))]
 pub struct PianoKey {
     pub note: Note,
     pub octave: u8,
@@ -20,16 +20,46 @@ impl fmt::Display for PianoKey {
     }
 }
 
+impl From<PianoKey> for String {
+    fn from(piano_key: PianoKey) -> Self {
+        piano_key.to_string()
+    }
+}
+
+impl TryFrom<String> for PianoKey {
+    type Error = io::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::from_str(&s)
+    }
+}
+
+// Saturates at the top of the keyboard (B8)
 impl Add<Interval> for PianoKey {
     type Output = Self;
 
     fn add(self, rhs: Interval) -> Self::Output {
-        let semitones = Semitone::from(rhs);
-        let mut ret = self;
-        for _ in 0..i8::from(semitones) {
-            ret.inc()
+        let semitones = i8::from(Semitone::from(rhs));
+        let midi_note_number = (self.get_midi_note_number() + semitones as u8).min(Self::get_max_midi_note_number());
+        if midi_note_number == self.get_midi_note_number() {
+            return self;
         }
-        ret
+        Self::from_midi_note_number(midi_note_number).unwrap()
+    }
+}
+
+// By pitch first, enharmonic keys (C#4 and Db4) are then sorted by letter to stay consistent with Eq
+impl Ord for PianoKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.get_midi_note_number().cmp(&other.get_midi_note_number())
+            .then((self.note.letter as u8).cmp(&(other.note.letter as u8)))
+            .then(self.note.get_alter().cmp(&other.note.get_alter()))
+    }
+}
+
+impl PartialOrd for PianoKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 
@@ -72,63 +102,40 @@ impl PianoKey {
         8
     }
 
-    pub fn inc(&mut self) {
-        use Accidental::*;
-        use NoteLetter::*;
-
-        if let Some(acc) = self.note.accidental {
-            self.note.accidental = None;
-            match acc {
-                Sharp => {
-                    self.note.letter = self.note.letter.inc();
-                }
-                Flat => {}
-            }
-        } else {
-            if self.note.letter == B || self.note.letter == E {
-                if self.note.letter == B {
-                    self.octave += 1;
-                }
-                self.note.letter = self.note.letter.inc(); 
-            } else {
-                self.note.accidental = Some(Sharp);
-            }
-        }
+    // B of the highest octave
+    pub fn get_max_midi_note_number() -> u8 {
+        (Self::max_octave() + 2) * 12 - 1
     }
 
-    pub fn get_distance(&self, piano_key: PianoKey) -> i32 {
-        let from_note: PianoKey;
-        let to_note: PianoKey;
+    // C4 is 60, the accidental is part of the pitch (Cb4 is 59, B#3 is 60)
+    pub fn get_midi_note_number(&self) -> u8 {
+        ((self.octave as i32 + 1) * 12 + self.note.get_semitones_from_c() as i32).max(0) as u8
+    }
 
-        if self.octave != piano_key.octave {
-            if self.octave > piano_key.octave {
-                from_note = piano_key;
-                to_note = self.clone();
-            } else {
-                from_note = self.clone();
-                to_note = piano_key;
-            }
-        } else {
-            let self_distance_from_c = self.note.interval_from_c() as i32;
-            let other_distance_from_c = piano_key.note.interval_from_c() as i32;
-            let distance_between_note = self_distance_from_c - other_distance_from_c;
-    
-            if distance_between_note > 0 {
-                from_note = piano_key;
-                to_note = self.clone();
-            } else {
-                from_note = self.clone();
-                to_note = piano_key;
-            }
+    // Spelled with sharps
+    pub fn from_midi_note_number(midi_note_number: u8) -> Result<Self, io::Error> {
+        let piano_key = Self::from_midi_note_number_unchecked(midi_note_number);
+        if midi_note_number < 12 || piano_key.octave > Self::max_octave() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is out of the keyboard range", midi_note_number),
+            ));
         }
+        Ok(piano_key)
+    }
 
-        let mut distance = 0;
-        let mut temp_note = from_note.clone();
-        while temp_note != to_note {
-            temp_note.inc();
-            distance += 1;
+    fn from_midi_note_number_unchecked(midi_note_number: u8) -> Self {
+        Self {
+            note: Note::from_pitch_class(midi_note_number % 12),
+            octave: (midi_note_number / 12).saturating_sub(1),
         }
+    }
 
-        return distance;
+    pub fn inc(&mut self) {
+        *self = *self + Interval::Min2;
+    }
+
+    pub fn get_distance(&self, piano_key: PianoKey) -> i32 {
+        (self.get_midi_note_number() as i32 - piano_key.get_midi_note_number() as i32).abs()
     }
 }
\ No newline at end of file
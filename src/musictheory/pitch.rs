@@ -1,11 +1,11 @@
 // Author: Ben Lovy <ben@deciduously.com>
 // License: MIT
 
-use std::ops::AddAssign;
+use std::{io, ops::AddAssign};
 
 use rodio::source::SineWave;
 
-use super::{cent::Cent, hertz::Hertz, interval::Interval, piano_key::PianoKey, semitone::Semitone};
+use super::{cent::Cent, hertz::Hertz, interval::Interval, piano_key::PianoKey, semitone::Semitone, tuning::Tuning};
 
 pub const STANDARD_PITCH: Hertz = Hertz(440.0);
 pub const MIDDLE_C: Hertz = Hertz(261.626);
@@ -18,6 +18,28 @@ impl Pitch {
     pub fn new(frequency: Hertz) -> Self {
         Self(frequency)
     }
+
+    // Fractional MIDI note number in 12-TET with A4 at 440 Hz, 69.5 is a quarter tone above A4
+    pub fn get_midi_note_number(self) -> Result<f64, io::Error> {
+        if !f64::from(self).is_finite() || f64::from(self) <= 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} Hz is not a valid frequency", f64::from(self)),
+            ));
+        }
+        Ok(69.0 + 12.0 * (f64::from(self) / f64::from(STANDARD_PITCH)).log2())
+    }
+
+    // The nearest key and how far the pitch is from it (between -50 and 50 cents)
+    pub fn get_nearest_piano_key(self) -> Result<(PianoKey, Cent), io::Error> {
+        let midi_note_number = self.get_midi_note_number()?;
+        let nearest = midi_note_number.round();
+        let piano_key = PianoKey::from_midi_note_number(nearest.clamp(0.0, 255.0) as u8).map_err(|_| io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} Hz is out of the keyboard range", f64::from(self)),
+        ))?;
+        Ok((piano_key, Cent(100.0 * (midi_note_number - nearest))))
+    }
 }
 
 impl Default for Pitch {
@@ -38,17 +60,10 @@ impl From<Pitch> for SineWave {
     }
 }
 
+// 12-TET with A4 at 440 Hz, see Tuning for the other tunings
 impl From<PianoKey> for Pitch {
     fn from(sp: PianoKey) -> Self {
-        use Interval::*;
-        let mut ret = Pitch::new(C_ZERO);
-
-        for _ in 0..sp.octave {
-            ret += Octave;
-        }
-
-        ret += sp.note.letter.interval_from_c();
-        ret
+        Tuning::default().get_pitch(sp)
     }
 }
 
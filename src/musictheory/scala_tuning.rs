@@ -0,0 +1,134 @@
+// Tuning loaded from a Scala scale (.scl) and keyboard mapping (.kbm), see https://www.huygens-fokker.org/scala/scl_format.html
+// The scale lists the degrees above the tonic in cents, the last one is the period (usually the octave) which repeats the scale
+
+use std::io;
+
+use super::{cent::Cent, hertz::Hertz, pitch::{Pitch, STANDARD_PITCH}};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyboardMapping {
+    // Keys outside of the range are not mapped
+    pub first_note: u8,
+    pub last_note: u8,
+    // Key of the first mapping entry (the tonic of the scale with the linear mapping)
+    pub middle_note: u8,
+    pub reference_note: u8,
+    pub reference_frequency: Hertz,
+    // Scale degree reached after a full mapping, the scale size when it is None
+    pub octave_degree: Option<usize>,
+    // Scale degree of each key from the middle note, None for an unmapped key
+    // An empty mapping maps each key to the next degree
+    pub mapping: Vec<Option<usize>>,
+}
+
+impl Default for KeyboardMapping {
+    // Linear mapping from C4 with A4 at 440 Hz
+    fn default() -> Self {
+        Self {
+            first_note: 0,
+            last_note: 127,
+            middle_note: 60,
+            reference_note: 69,
+            reference_frequency: STANDARD_PITCH,
+            octave_degree: None,
+            mapping: Vec::new(),
+        }
+    }
+}
+
+impl KeyboardMapping {
+    pub fn set_reference(mut self, reference_note: u8, reference_frequency: Hertz) -> Self {
+        self.reference_note = reference_note;
+        self.reference_frequency = reference_frequency;
+        self
+    }
+
+    // Scale degree from the tonic, negative below the middle note
+    pub fn get_degree(&self, midi_note_number: u8, scale_size: usize) -> Option<i32> {
+        if !(self.first_note..=self.last_note).contains(&midi_note_number) {
+            return None;
+        }
+        let keys = midi_note_number as i32 - self.middle_note as i32;
+        if self.mapping.is_empty() {
+            return Some(keys);
+        }
+        let size = self.mapping.len() as i32;
+        let degree = self.mapping[keys.rem_euclid(size) as usize]?;
+        let octave_degree = self.octave_degree.unwrap_or(scale_size) as i32;
+        Some(degree as i32 + keys.div_euclid(size) * octave_degree)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScalaTuning {
+    pub description: String,
+    // Degrees above the tonic, without the tonic itself, the last one is the period
+    pub degrees: Vec<Cent>,
+    pub mapping: KeyboardMapping,
+}
+
+impl ScalaTuning {
+    pub fn new(description: &str, degrees: Vec<Cent>) -> Self {
+        Self {
+            description: String::from(description),
+            degrees,
+            mapping: KeyboardMapping::default(),
+        }
+    }
+
+    pub fn set_mapping(mut self, mapping: KeyboardMapping) -> Self {
+        self.mapping = mapping;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), io::Error> {
+        if self.degrees.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "A Scala scale needs at least one degree"));
+        }
+        if let Some(degree) = self.mapping.mapping.iter().flatten().find(|d| **d > self.degrees.len()) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("The keyboard mapping uses degree {} but the scale only has {}", degree, self.degrees.len()),
+            ));
+        }
+        let reference = f64::from(self.mapping.reference_frequency);
+        if !reference.is_finite() || reference <= 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} Hz is not a valid reference frequency", reference),
+            ));
+        }
+        if self.get_key_cents(self.mapping.reference_note).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("The reference key {} is not mapped", self.mapping.reference_note),
+            ));
+        }
+        Ok(())
+    }
+
+    // Cents of a degree above the tonic, the scale repeats every period
+    pub fn get_degree_cents(&self, degree: i32) -> Cent {
+        let size = self.degrees.len() as i32;
+        let period = f64::from(self.degrees[self.degrees.len() - 1]);
+        let step = degree.rem_euclid(size);
+        let cents = if step == 0 { 0.0 } else { f64::from(self.degrees[step as usize - 1]) };
+        Cent(cents + degree.div_euclid(size) as f64 * period)
+    }
+
+    // None when the key is not mapped
+    pub fn get_pitch(&self, midi_note_number: u8) -> Option<Pitch> {
+        let cents = f64::from(self.get_key_cents(midi_note_number)?);
+        let reference_cents = f64::from(self.get_key_cents(self.mapping.reference_note)?);
+        let mut ret = Pitch::new(self.mapping.reference_frequency);
+        ret += Cent(cents - reference_cents);
+        Some(ret)
+    }
+
+    fn get_key_cents(&self, midi_note_number: u8) -> Option<Cent> {
+        if self.degrees.is_empty() {
+            return None;
+        }
+        self.mapping.get_degree(midi_note_number, self.degrees.len()).map(|d| self.get_degree_cents(d))
+    }
+}
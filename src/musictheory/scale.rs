@@ -5,14 +5,30 @@
 use core::fmt;
 use std::{io, str::FromStr};
 
-use super::{interval::Interval, mode::{Mode, PentatonicMode}};
+use super::{interval::Interval, mode::{BebopMode, HarmonicMinorMode, MelodicMinorMode, Mode, OctatonicMode, PentatonicMode}, note::Note, semitone::Semitone};
+
+const OCTAVE_SEMITONES: u8 = 12;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 pub enum Scale {
     Chromatic,
     Diatonic(Mode),
     Pentatonic(PentatonicMode),
     Tetratonic,
+    HarmonicMinor(HarmonicMinorMode),
+    MelodicMinor(MelodicMinorMode),
+    WholeTone,
+    Octatonic(OctatonicMode),
+    // Minor pentatonic with the flat fifth
+    Blues,
+    Bebop(BebopMode),
+    Hirajoshi,
+    In,
+    HungarianMinor,
+    DoubleHarmonic,
+    Custom(CustomScale),
 }
 
 impl Default for Scale {
@@ -46,18 +62,77 @@ impl Scale {
                 .take(ScaleLength::Heptatonic as usize)
                 .copied()
                 .collect::<Vec<Interval>>(),
-            Tetratonic => vec![Min2, Maj2, Maj3]
+            Tetratonic => vec![Min2, Maj2, Maj3],
+            HarmonicMinor(mode) => get_mode_intervals(&HarmonicMinorMode::base_interval(), mode as usize),
+            MelodicMinor(mode) => get_mode_intervals(&MelodicMinorMode::base_interval(), mode as usize),
+            WholeTone => vec![Maj2; ScaleLength::Hexatonic as usize],
+            Octatonic(mode) => get_mode_intervals(&[Min2, Maj2], mode as usize)
+                .iter()
+                .cycle()
+                .take(ScaleLength::Octatonic as usize)
+                .copied()
+                .collect::<Vec<Interval>>(),
+            Blues => vec![Min3, Maj2, Min2, Min2, Min3, Maj2],
+            Bebop(mode) => match mode {
+                BebopMode::Dominant => vec![Maj2, Maj2, Min2, Maj2, Maj2, Min2, Min2, Min2],
+                BebopMode::Major => vec![Maj2, Maj2, Min2, Maj2, Min2, Min2, Maj2, Min2],
+                BebopMode::Dorian => vec![Maj2, Min2, Min2, Min2, Maj2, Maj2, Min2, Maj2],
+            },
+            Hirajoshi => vec![Maj2, Min2, Maj3, Min2, Maj3],
+            In => vec![Min2, Maj3, Maj2, Min2, Maj3],
+            HungarianMinor => vec![Maj2, Min2, Min3, Min2, Min2, Min3, Min2],
+            DoubleHarmonic => vec![Min2, Min3, Min2, Maj2, Min2, Min3, Min2],
+            Custom(custom_scale) => custom_scale.get_steps()
+                .iter()
+                .map(|step| Interval::from(Semitone(*step as i8)))
+                .collect::<Vec<Interval>>(),
+        }
+    }
+
+    // The diatonic mode whose key signature is used to write the scale, None when it is written with accidentals
+    pub fn get_signature_mode(self) -> Option<Mode> {
+        use Mode::*;
+        match self {
+            Scale::Diatonic(mode) => Some(mode),
+            // Pentatonic modes are subsets of the diatonic modes
+            Scale::Pentatonic(mode) => Some(match mode {
+                PentatonicMode::Major => Ionian,
+                PentatonicMode::Suspended => Dorian,
+                PentatonicMode::BluesMinor => Phrygian,
+                PentatonicMode::BluesMajor => Mixolydian,
+                PentatonicMode::Minor => Aeolian,
+            }),
+            // The raised degrees of the minor scales are written as accidentals
+            Scale::HarmonicMinor(HarmonicMinorMode::HarmonicMinor) | Scale::MelodicMinor(MelodicMinorMode::MelodicMinor)
+                | Scale::Blues | Scale::Hirajoshi | Scale::HungarianMinor => Some(Aeolian),
+            Scale::Bebop(mode) => Some(match mode {
+                BebopMode::Dominant => Mixolydian,
+                BebopMode::Major => Ionian,
+                BebopMode::Dorian => Dorian,
+            }),
+            _ => None,
         }
     }
 }
 
+// Intervals of the mode starting on the given degree of the base scale
+fn get_mode_intervals(base_interval: &[Interval], degree: usize) -> Vec<Interval> {
+    base_interval.iter()
+        .cycle()
+        .skip(degree)
+        .take(base_interval.len())
+        .copied()
+        .collect::<Vec<Interval>>()
+}
+
 impl FromStr for Scale {
     type Err = io::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
         use Mode::*;
         use PentatonicMode::*;
         use Scale::*;
-        match s.to_uppercase().as_str() {
+        // "harmonic-minor", "Harmonic Minor" and "HARMONIC_MINOR" are the same scale
+        match s.to_uppercase().replace(['-', '_', ' '], "").as_str() {
             "IONIAN" | "MAJOR" => Ok(Diatonic(Ionian)),
             "DORIAN" => Ok(Diatonic(Dorian)),
             "PHRYGIAN" => Ok(Diatonic(Phrygian)),
@@ -72,7 +147,35 @@ impl FromStr for Scale {
             "PENTAMINOR" => Ok(Pentatonic(Minor)),
             "CHROMATIC" => Ok(Chromatic),
             "TETRATONIC" => Ok(Tetratonic),
-            _ => Err(io::Error::new(io::ErrorKind::InvalidInput, "Unknown scale")),
+            "HARMONICMINOR" => Ok(HarmonicMinor(HarmonicMinorMode::HarmonicMinor)),
+            "LOCRIANNATURAL6" | "LOCRIAN#6" => Ok(HarmonicMinor(HarmonicMinorMode::LocrianNatural6)),
+            "IONIANAUGMENTED" | "IONIAN#5" => Ok(HarmonicMinor(HarmonicMinorMode::IonianAugmented)),
+            "UKRAINIANDORIAN" | "DORIAN#4" => Ok(HarmonicMinor(HarmonicMinorMode::UkrainianDorian)),
+            "PHRYGIANDOMINANT" => Ok(HarmonicMinor(HarmonicMinorMode::PhrygianDominant)),
+            "LYDIAN#2" | "LYDIANSHARP2" => Ok(HarmonicMinor(HarmonicMinorMode::LydianSharp2)),
+            "ALTEREDDIMINISHED" | "SUPERLOCRIANBB7" => Ok(HarmonicMinor(HarmonicMinorMode::AlteredDiminished)),
+            "MELODICMINOR" | "JAZZMINOR" => Ok(MelodicMinor(MelodicMinorMode::MelodicMinor)),
+            "DORIANB2" | "DORIANFLAT2" => Ok(MelodicMinor(MelodicMinorMode::DorianFlat2)),
+            "LYDIANAUGMENTED" | "LYDIAN#5" => Ok(MelodicMinor(MelodicMinorMode::LydianAugmented)),
+            "LYDIANDOMINANT" | "ACOUSTIC" => Ok(MelodicMinor(MelodicMinorMode::LydianDominant)),
+            "MIXOLYDIANB6" | "MIXOLYDIANFLAT6" => Ok(MelodicMinor(MelodicMinorMode::MixolydianFlat6)),
+            "LOCRIANNATURAL2" | "HALFDIMINISHED" => Ok(MelodicMinor(MelodicMinorMode::LocrianNatural2)),
+            "ALTERED" | "SUPERLOCRIAN" => Ok(MelodicMinor(MelodicMinorMode::Altered)),
+            "WHOLETONE" => Ok(WholeTone),
+            "OCTATONIC" | "HALFWHOLE" => Ok(Octatonic(OctatonicMode::HalfWhole)),
+            "WHOLEHALF" => Ok(Octatonic(OctatonicMode::WholeHalf)),
+            "BLUES" => Ok(Blues),
+            "BEBOP" | "BEBOPDOMINANT" => Ok(Bebop(BebopMode::Dominant)),
+            "BEBOPMAJOR" => Ok(Bebop(BebopMode::Major)),
+            "BEBOPDORIAN" => Ok(Bebop(BebopMode::Dorian)),
+            "HIRAJOSHI" => Ok(Hirajoshi),
+            "IN" => Ok(In),
+            "HUNGARIANMINOR" => Ok(HungarianMinor),
+            "DOUBLEHARMONIC" => Ok(DoubleHarmonic),
+            _ => CustomScale::from_str(s).map(Custom).map_err(|e| io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Unknown scale {} ({})", s, e),
+            )),
         }
     }
 }
@@ -91,15 +194,117 @@ impl fmt::Display for Scale {
                     _ => format!("{:?} mode", mode)
                 }
             }
+            HarmonicMinor(HarmonicMinorMode::HarmonicMinor) => "harmonic minor scale".into(),
+            MelodicMinor(MelodicMinorMode::MelodicMinor) => "melodic minor scale".into(),
+            HarmonicMinor(mode) => format!("{:?} mode", mode),
+            MelodicMinor(mode) => format!("{:?} mode", mode),
+            WholeTone => "whole tone scale".into(),
+            Octatonic(OctatonicMode::HalfWhole) => "half-whole octatonic scale".into(),
+            Octatonic(OctatonicMode::WholeHalf) => "whole-half octatonic scale".into(),
+            Bebop(mode) => format!("bebop {:?} scale", mode).to_lowercase(),
+            HungarianMinor => "hungarian minor scale".into(),
+            DoubleHarmonic => "double harmonic scale".into(),
+            Blues | Hirajoshi | In => format!("{:?} scale", self).to_lowercase(),
+            Custom(custom_scale) => format!("custom scale {}", custom_scale),
         };
         write!(f, "{}", s)
     }
 }
 
+// Scale defined by the user, stored as the semitones above the tonic so that it stays Copy like the other scales
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CustomScale {
+    // Bit n is set when the note n semitones above the tonic is in the scale, the tonic is always in it
+    pitch_classes: u16,
+}
+
+impl CustomScale {
+    // Semitones between consecutive notes, they have to add up to an octave
+    pub fn from_steps(steps: &[u8]) -> Result<Self, io::Error> {
+        let sum = steps.iter().map(|s| *s as u32).sum::<u32>();
+        if steps.len() < 2 || steps.contains(&0) || sum != OCTAVE_SEMITONES as u32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the steps {:?} are not at least two steps adding up to an octave", steps),
+            ));
+        }
+        let mut pitch_classes = 1;
+        steps.iter().take(steps.len() - 1).fold(0, |semitones, step| {
+            pitch_classes |= 1 << (semitones + step);
+            semitones + step
+        });
+        Ok(Self { pitch_classes })
+    }
+
+    // Only the intervals between the notes are kept, the first note is the tonic and the others go up within an octave
+    pub fn from_notes(notes: &[Note]) -> Result<Self, io::Error> {
+        let Some(tonic) = notes.first() else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "the scale has no notes"));
+        };
+        let mut steps = Vec::new();
+        let mut previous = 0;
+        for note in notes.iter().skip(1) {
+            let semitones = (note.get_pitch_class() as i8 - tonic.get_pitch_class() as i8).rem_euclid(OCTAVE_SEMITONES as i8) as u8;
+            if semitones <= previous {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} does not go up from the previous note within an octave of {}", note, tonic),
+                ));
+            }
+            steps.push(semitones - previous);
+            previous = semitones;
+        }
+        steps.push(OCTAVE_SEMITONES - previous);
+        Self::from_steps(&steps)
+    }
+
+    pub fn get_steps(self) -> Vec<u8> {
+        let mut ret = Vec::new();
+        let mut previous = 0;
+        (1..=OCTAVE_SEMITONES).filter(|n| *n == OCTAVE_SEMITONES || self.pitch_classes & (1 << n) != 0).for_each(|n| {
+            ret.push(n - previous);
+            previous = n;
+        });
+        ret
+    }
+}
+
+// Steps in semitones or W (whole) and H (half) joined by dashes, a '+' adds up steps ("2-1-2-2-1-3-1" or "W-H-W-W-H-W+H-H"),
+// or a list of notes ("C D Eb F# G")
+impl FromStr for CustomScale {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.split_whitespace().count() > 1 {
+            let notes = s.split_whitespace().map(Note::from_str).collect::<Result<Vec<Note>, io::Error>>()?;
+            return Self::from_notes(&notes);
+        }
+        let steps = s.split('-')
+            .map(|step| step.split('+').map(|part| match part.trim().to_uppercase().as_str() {
+                "W" => Some(2),
+                "H" => Some(1),
+                part => part.parse::<u32>().ok(),
+            }).sum::<Option<u32>>().and_then(|step| u8::try_from(step).ok()))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a list of steps", s)))?;
+        Self::from_steps(&steps)
+    }
+}
+
+impl fmt::Display for CustomScale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let steps = self.get_steps().iter().map(|s| s.to_string()).collect::<Vec<String>>();
+        write!(f, "{}", steps.join("-"))
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ScaleLength {
     Tetratonic = 4,
     Pentatonic = 5,
+    Hexatonic = 6,
     Heptatonic = 7,
+    Octatonic = 8,
     Dodecatonic = 12,
 }
\ No newline at end of file
@@ -1,8 +1,10 @@
 use core::fmt;
+use std::{io, str::FromStr};
 
-use super::pattern::Pattern;
+use super::{measure::Measure, pattern::Pattern, time_signature::TimeSignature};
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Sheet {
     pub patterns: Vec<Pattern>
 }
@@ -17,6 +19,65 @@ impl Sheet {
     pub fn add_pattern(&mut self, pattern: Pattern) {
         self.patterns.push(pattern);
     }
+
+    // ASCII notation: one pattern per line, with an optional name, measures are separated by |
+    // The time signature is written at the start of a measure when it changes (4/4 by default)
+    // Pattern 0: C4h E4q G4q | 3/4 C4h. |
+    pub fn to_ascii(&self) -> String {
+        let mut ret = String::new();
+        let mut time_signature = TimeSignature::default();
+        self.patterns.iter().for_each(|p| {
+            if !p.name.is_empty() {
+                ret.push_str(&format!("{}: ", p.name));
+            }
+            p.measures.iter().for_each(|m| {
+                if m.time_signature != time_signature {
+                    ret.push_str(&format!("{} ", m.time_signature));
+                    time_signature = m.time_signature;
+                }
+                ret.push_str(&format!("{} | ", m.to_ascii()));
+            });
+            ret.truncate(ret.trim_end().len());
+            ret.push('\n');
+        });
+        ret
+    }
+}
+
+impl FromStr for Sheet {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut sheet = Sheet::new();
+        let mut time_signature = TimeSignature::default();
+
+        // Empty lines and lines starting with # are ignored
+        for (line_number, line) in s.lines().enumerate().filter(|(_, l)| !l.trim().is_empty() && !l.trim_start().starts_with('#')) {
+            // The pattern name ends with a colon followed by a space, the colon of a tuplet (C4e:3) is followed by a number
+            let (name, measures) = line.char_indices()
+                .find(|(i, c)| *c == ':' && line[i + 1..].chars().next().is_none_or(char::is_whitespace))
+                .map(|(i, _)| (&line[..i], &line[i + 1..]))
+                .unwrap_or(("", line));
+            let mut pattern = Pattern::new(name.trim().to_string());
+            for measure_str in measures.split('|').filter(|m| !m.trim().is_empty()) {
+                let measure = Measure::parse(measure_str, time_signature).map_err(|e| io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("line {}: {}", line_number + 1, e),
+                ))?;
+                time_signature = measure.time_signature;
+                pattern.add_measure(measure);
+            }
+            if pattern.measures.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("line {}: the pattern {} has no measures", line_number + 1, pattern.name),
+                ));
+            }
+            sheet.add_pattern(pattern);
+        }
+
+        Ok(sheet)
+    }
 }
 
 impl fmt::Display for Sheet {
@@ -1,13 +1,49 @@
 use core::fmt;
+use std::{io, str::FromStr};
 
 use super::{note_value::NoteValue, piano_key::PianoKey};
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SheetNote {
     pub note: PianoKey,
     pub value: NoteValue,
 }
 
+impl SheetNote {
+    pub fn to_ascii(&self) -> String {
+        format!("{}{}", self.note, self.value.to_ascii())
+    }
+}
+
+impl FromStr for SheetNote {
+    type Err = io::Error;
+
+    // ASCII notation: piano key followed by the note value, like C4q, D#4e. or Fx4q
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.starts_with(['r', 'R']) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{}: rests are not supported by the sheet", s),
+            ));
+        }
+        // The letter, every accidental (#, b, x) and the octave digit
+        let nb_accidentals = s.bytes().skip(1).take_while(|c| matches!(c, b'#' | b'b' | b'x')).count();
+        let key_length = nb_accidentals + 2;
+        if s.len() <= key_length || !s.is_char_boundary(key_length) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a valid sheet note", s),
+            ));
+        }
+
+        Ok(SheetNote {
+            note: PianoKey::from_str(&s[..key_length])?,
+            value: NoteValue::from_str(&s[key_length..])?,
+        })
+    }
+}
+
 impl fmt::Display for SheetNote {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}{}", self.note, self.value)
@@ -0,0 +1,79 @@
+// Key-aware spelling: the letter of a note comes from its scale degree and the accidental makes up
+// the difference (up to a double sharp or double flat), so F major has a Bb and not an A#
+
+use super::{
+    diatonic_interval::DiatonicInterval, interval::Interval, key::Key, note::{Accidental, Note, NoteLetter}, piano_key::PianoKey,
+    scale::Scale, semitone::Semitone
+};
+
+// None when the letter is more than two semitones away from the pitch class
+pub fn spell_note(letter: NoteLetter, pitch_class: u8) -> Option<Note> {
+    let alter = (pitch_class as i8 - letter.get_semitones_from_c() + 6).rem_euclid(12) - 6;
+    Accidental::from_alter(alter).ok().map(|accidental| Note { letter, accidental })
+}
+
+// The octave follows the letter, B#3 and C4 are the same key
+pub fn spell_piano_key(midi_note_number: u8, letter: NoteLetter) -> Option<PianoKey> {
+    let note = spell_note(letter, midi_note_number % 12)?;
+    let octave = (midi_note_number as i32 - note.get_semitones_from_c() as i32) / 12 - 1;
+    (0..=PianoKey::max_octave() as i32).contains(&octave).then_some(PianoKey { note, octave: octave as u8 })
+}
+
+// Letters above the root when the degree is unknown: minor intervals are written as flattened degrees (b3, b7),
+// the tritone as an augmented fourth
+pub fn get_interval_steps(interval: Interval) -> u8 {
+    DiatonicInterval::from(interval).number - 1
+}
+
+// The interval above the key is written with the letter `steps` letters above (2 steps for a third)
+pub fn spell_interval(piano_key: PianoKey, interval: Interval, steps: u8) -> PianoKey {
+    let midi_note_number = piano_key.get_midi_note_number() + i8::from(Semitone::from(interval)) as u8;
+    spell_piano_key(midi_note_number, piano_key.note.letter.add_steps(steps)).unwrap_or(piano_key + interval)
+}
+
+// Same layout as Key::get_notes: the tonic, then one note per interval
+// Heptatonic scales use each letter once, the other scales spell each note from its interval to the tonic
+pub fn spell_scale(tonic: Note, intervals: &[Interval]) -> Vec<Note> {
+    let mut ret = vec![tonic];
+    let mut semitones = 0u8;
+    intervals.iter().enumerate().for_each(|(i, interval)| {
+        semitones += i8::from(Semitone::from(*interval)) as u8;
+        let steps = if intervals.len() == 7 { i as u8 + 1 } else { get_interval_steps(Interval::from(Semitone(semitones as i8))) };
+        let pitch_class = (tonic.get_pitch_class() + semitones) % 12;
+        ret.push(spell_note(tonic.letter.add_steps(steps), pitch_class).unwrap_or(Note::from_pitch_class(pitch_class)));
+    });
+    ret
+}
+
+// Notes of the key keep the key spelling, the others use flats in flat keys and sharps otherwise
+pub fn respell_in_key(piano_key: PianoKey, key: Key) -> PianoKey {
+    let notes = key.get_notes();
+    let pitch_class = piano_key.note.get_pitch_class();
+    let midi_note_number = piano_key.get_midi_note_number();
+    if let Some(note) = notes.iter().find(|n| n.get_pitch_class() == pitch_class) {
+        return spell_piano_key(midi_note_number, note.letter).unwrap_or(piano_key);
+    }
+    let preferred_alter = if notes.iter().any(|n| n.get_alter() < 0) { -1 } else { 1 };
+    [0, preferred_alter].iter()
+        .find_map(|alter| {
+            let letter = get_letters().into_iter().find(|l| spell_note(*l, pitch_class).map(|n| n.get_alter()) == Some(*alter))?;
+            spell_piano_key(midi_note_number, letter)
+        })
+        .unwrap_or(piano_key)
+}
+
+// The enharmonic tonic giving the fewest accidentals in the scale, A# major is written Bb major
+pub fn get_key_tonic(tonic: PianoKey, scale: Scale) -> PianoKey {
+    let intervals = scale.get_intervals();
+    let get_nb_accidentals = |note: Note| spell_scale(note, &intervals).iter().map(|n| u32::from(n.get_alter().unsigned_abs())).sum::<u32>();
+    get_letters().into_iter()
+        .filter_map(|letter| spell_piano_key(tonic.get_midi_note_number(), letter))
+        .fold(tonic, |best, candidate| {
+            if get_nb_accidentals(candidate.note) < get_nb_accidentals(best.note) { candidate } else { best }
+        })
+}
+
+fn get_letters() -> [NoteLetter; 7] {
+    use NoteLetter::*;
+    [C, D, E, F, G, A, B]
+}
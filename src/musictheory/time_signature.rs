@@ -1,72 +1,175 @@
 use core::fmt;
 use std::{io, str::FromStr};
 
-#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
-pub struct TimeSignature(pub f32);
+use num::rational::Rational32;
+
+// Enough for 64/64, the bits of the group starts
+const MAX_NUMERATOR: u8 = 64;
+const MAX_DENOMINATOR: u8 = 64;
+
+// Accent of each unit of the measure: the downbeat, the start of the other beat groups, the rest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum BeatAccent {
+    Strong,
+    Medium,
+    Weak,
+}
+
+// The numerator counts units of 1/denominator, grouped in beats (7/8 as 2+2+3, 6/8 as 3+3)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(into = "String", try_from = "String"))]
+pub struct TimeSignature {
+    pub numerator: u8,
+    pub denominator: u8,
+    // Bit n is set when a group starts on the nth unit, stored as bits so that the time signature stays Copy
+    group_starts: u64,
+}
 
 impl Default for TimeSignature {
     fn default() -> Self {
-        TimeSignature(1.0)
+        Self::new(4, 4).unwrap()
     }
 }
 
-impl From<f32> for TimeSignature {
-    fn from(value: f32) -> Self {
-        Self(value)
+// Length of the measure relative to a whole note, 3/4 and 6/8 are both 3/4
+impl From<TimeSignature> for Rational32 {
+    fn from(value: TimeSignature) -> Self {
+        Rational32::new(value.numerator as i32, value.denominator as i32)
     }
 }
 
-impl From<TimeSignature> for f32 {
-    fn from(value: TimeSignature) -> Self {
-        value.0
+impl From<TimeSignature> for String {
+    fn from(time_signature: TimeSignature) -> Self {
+        time_signature.to_string()
+    }
+}
+
+impl TryFrom<String> for TimeSignature {
+    type Error = io::Error;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Self::from_str(&s)
     }
 }
 
+// "3/4", or the grouping over the denominator for an explicit grouping ("2+2+3/8")
 impl FromStr for TimeSignature {
     type Err = io::Error;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let splitted_signature = s.split('/').collect::<Vec<&str>>();
-        if splitted_signature.len() == 2 {
-            let counting_value = splitted_signature[1].parse::<i32>().unwrap_or_default();
-            let total_values = splitted_signature[0].parse::<i32>().unwrap_or_default();
-
-            if (counting_value & (counting_value - 1)) == 0 { // check if counting_value is a power of 2
-                if counting_value > 0 && total_values > 0 {
-                    let relative_couting_value = 1.0 / counting_value as f32;
-                    return Ok(TimeSignature::from(total_values as f32 * relative_couting_value));
-                }
-            }
-        }
-
-        Err(io::Error::new(
+        let invalid = || io::Error::new(
             io::ErrorKind::InvalidInput,
             format!("{} is not a valid signature", s),
-        ))
+        );
+        let (numerator, denominator) = s.split_once('/').ok_or_else(invalid)?;
+        let denominator = denominator.trim().parse::<u8>().map_err(|_| invalid())?;
+        let grouping = numerator.split('+')
+            .map(|n| n.trim().parse::<u8>())
+            .collect::<Result<Vec<u8>, _>>()
+            .map_err(|_| invalid())?;
+        let time_signature = if grouping.len() == 1 {
+            Self::new(grouping[0], denominator)
+        } else {
+            Self::from_grouping(&grouping, denominator)
+        };
+        time_signature.map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a valid signature: {}", s, e)))
+    }
+}
+
+impl TimeSignature {
+    // The grouping is the usual one for this numerator, see get_default_grouping
+    pub fn new(numerator: u8, denominator: u8) -> Result<Self, io::Error> {
+        Self::from_grouping(&get_default_grouping(numerator), denominator)
+    }
+
+    // The numerator is the sum of the groups
+    pub fn from_grouping(grouping: &[u8], denominator: u8) -> Result<Self, io::Error> {
+        let numerator = grouping.iter().map(|g| *g as u32).sum::<u32>();
+        if !denominator.is_power_of_two() || denominator > MAX_DENOMINATOR {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the denominator {} is not a power of 2 up to {}", denominator, MAX_DENOMINATOR),
+            ));
+        }
+        if grouping.contains(&0) || numerator == 0 || numerator > MAX_NUMERATOR as u32 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("the groups {:?} do not add up to a numerator between 1 and {}", grouping, MAX_NUMERATOR),
+            ));
+        }
+        let mut group_starts = 0;
+        grouping.iter().fold(0, |start, group| {
+            group_starts |= 1 << start;
+            start + group
+        });
+        Ok(Self { numerator: numerator as u8, denominator, group_starts })
+    }
+
+    // Number of units in each beat group
+    pub fn get_grouping(&self) -> Vec<u8> {
+        let mut ret = Vec::new();
+        let mut start = 0;
+        (1..=self.numerator).filter(|n| *n == self.numerator || self.group_starts & (1 << n) != 0).for_each(|n| {
+            ret.push(n - start);
+            start = n;
+        });
+        ret
+    }
+
+    pub fn has_default_grouping(&self) -> bool {
+        self.get_grouping() == get_default_grouping(self.numerator)
+    }
+
+    // Accent of each unit of the measure
+    pub fn get_accents(&self) -> Vec<BeatAccent> {
+        (0..self.numerator)
+            .map(|n| match n {
+                0 => BeatAccent::Strong,
+                _ if self.group_starts & (1 << n) != 0 => BeatAccent::Medium,
+                _ => BeatAccent::Weak,
+            })
+            .collect()
+    }
+
+    // Accent of a note starting at this position of the measure (relative to a whole note), None between the units
+    pub fn get_accent(&self, position: Rational32) -> Option<BeatAccent> {
+        let unit = position * self.denominator as i32;
+        if !unit.is_integer() || unit < Rational32::from_integer(0) {
+            return None;
+        }
+        self.get_accents().get(unit.to_integer() as usize).copied()
+    }
+
+    pub fn get_fraction(&self) -> (i32, i32) {
+        (self.numerator as i32, self.denominator as i32)
     }
 }
 
 impl fmt::Display for TimeSignature {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut numerator = (f32::from(*self) * 1000.0) as i32;
-        let mut denominator = 1000;
-
-        let gcd = num::integer::gcd(numerator, denominator);
-        numerator /= gcd;
-        denominator /= gcd;
-
-        if denominator == 1 {
-            numerator *= 4;
-            denominator *= 4
-        } else if denominator == 2 {
-            numerator *= 2;
-            denominator *= 2;
+        if self.has_default_grouping() {
+            return write!(f, "{}/{}", self.numerator, self.denominator);
         }
+        let grouping = self.get_grouping().iter().map(|g| g.to_string()).collect::<Vec<String>>();
+        write!(f, "{}/{}", grouping.join("+"), self.denominator)
+    }
+}
 
-        write!(
-            f,
-            "{}/{}",
-            numerator,
-            denominator
-        )
+// Compound meters are grouped by 3 (6/8 is 3+3), up to 3 units are a single group (3/4),
+// the others by 2 with a group of 3 at the end when the numerator is odd (4/4 is 2+2, 7/8 is 2+2+3)
+pub fn get_default_grouping(numerator: u8) -> Vec<u8> {
+    if numerator > 3 && numerator.is_multiple_of(3) {
+        return vec![3; (numerator / 3) as usize];
+    }
+    if numerator <= 3 {
+        return vec![numerator];
     }
-}
\ No newline at end of file
+    let mut ret = vec![2; (numerator / 2) as usize];
+    if numerator % 2 == 1 {
+        let last = ret.len() - 1;
+        ret[last] = 3;
+    }
+    ret
+}
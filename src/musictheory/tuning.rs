@@ -0,0 +1,209 @@
+// Tuning systems used to turn the keys into frequencies, the reference is the frequency of A4
+// Pythagorean and meantone are generated by their fifth along the chain of fifths, so the spelling matters
+// (G# and Ab differ in meantone), just intonation uses 5-limit ratios from the tonic
+// N-EDO moves each key to the nearest step of the division, so the divisions which are multiples of 12 sound like 12-TET
+// Scala tunings map the MIDI note numbers to the degrees of a scale loaded from a file
+
+use core::fmt;
+use std::{io, str::FromStr};
+
+use super::{cent::Cent, hertz::Hertz, note::{Note, NoteLetter}, piano_key::PianoKey, pitch::{Pitch, STANDARD_PITCH}, scala_tuning::ScalaTuning};
+
+const A4_MIDI_NOTE_NUMBER: i32 = 69;
+const OCTAVE_CENTS: f64 = 1200.0;
+// 5-limit ratio of each number of semitones above the tonic
+const JUST_RATIOS: [f64; 12] = [
+    1.0, 16.0 / 15.0, 9.0 / 8.0, 6.0 / 5.0, 5.0 / 4.0, 4.0 / 3.0, 45.0 / 32.0, 3.0 / 2.0, 8.0 / 5.0, 5.0 / 3.0, 9.0 / 5.0, 15.0 / 8.0,
+];
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub enum TuningSystem {
+    #[default]
+    EqualTemperament,
+    JustIntonation,
+    Pythagorean,
+    QuarterCommaMeantone,
+    // Equal division of the octave in N steps
+    Edo(u16),
+    Scala(ScalaTuning),
+}
+
+impl TuningSystem {
+    // Size of the fifth generating the tuning, None when it is not a regular temperament
+    pub fn get_fifth(&self) -> Option<Cent> {
+        match self {
+            Self::EqualTemperament => Some(Cent(700.0)),
+            Self::JustIntonation | Self::Scala(_) => None,
+            Self::Pythagorean => Some(Cent(OCTAVE_CENTS * 1.5f64.log2())),
+            Self::QuarterCommaMeantone => Some(Cent(OCTAVE_CENTS * 5f64.powf(0.25).log2())),
+            Self::Edo(divisions) => Some(get_edo_cents(7, *divisions)),
+        }
+    }
+}
+
+impl FromStr for TuningSystem {
+    type Err = io::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.to_lowercase();
+        match s.as_str() {
+            "12tet" | "12edo" | "equal" => Ok(Self::EqualTemperament),
+            "just" => Ok(Self::JustIntonation),
+            "pythagorean" => Ok(Self::Pythagorean),
+            "meantone" => Ok(Self::QuarterCommaMeantone),
+            _ => s.strip_suffix("edo")
+                .and_then(|n| n.parse::<u16>().ok())
+                .filter(|n| *n > 0)
+                .map(Self::Edo)
+                .ok_or_else(|| io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Unknown tuning {} (12tet, just, pythagorean, meantone or <N>edo)", s),
+                )),
+        }
+    }
+}
+
+impl fmt::Display for TuningSystem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::EqualTemperament => write!(f, "12tet"),
+            Self::JustIntonation => write!(f, "just"),
+            Self::Pythagorean => write!(f, "pythagorean"),
+            Self::QuarterCommaMeantone => write!(f, "meantone"),
+            Self::Edo(divisions) => write!(f, "{}edo", divisions),
+            Self::Scala(scala_tuning) => write!(f, "scala \"{}\"", scala_tuning.description),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tuning {
+    pub system: TuningSystem,
+    pub reference: Hertz,
+    // Just intonation, Pythagorean and meantone are pure from the tonic, which keeps its equal temperament frequency
+    pub tonic: Note,
+}
+
+impl Default for Tuning {
+    fn default() -> Self {
+        Self {
+            system: TuningSystem::default(),
+            reference: STANDARD_PITCH,
+            tonic: Note::default(),
+        }
+    }
+}
+
+impl Tuning {
+    pub fn new(system: TuningSystem, reference: Hertz, tonic: Note) -> Self {
+        Self { system, reference, tonic }
+    }
+
+    pub fn set_system(mut self, system: TuningSystem) -> Self {
+        self.system = system;
+        self
+    }
+
+    pub fn set_reference(mut self, reference: Hertz) -> Self {
+        self.reference = reference;
+        self
+    }
+
+    pub fn set_tonic(mut self, tonic: Note) -> Self {
+        self.tonic = tonic;
+        self
+    }
+
+    pub fn validate(&self) -> Result<(), io::Error> {
+        let reference = f64::from(self.reference);
+        if !reference.is_finite() || reference <= 0.0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} Hz is not a valid reference pitch", reference),
+            ));
+        }
+        if let TuningSystem::Scala(scala_tuning) = &self.system {
+            scala_tuning.validate()?;
+        }
+        Ok(())
+    }
+
+    pub fn get_pitch(&self, piano_key: PianoKey) -> Pitch {
+        let midi_note_number = piano_key.get_midi_note_number() as i32;
+        let mut ret = Pitch::new(self.reference);
+        ret += match &self.system {
+            TuningSystem::EqualTemperament => get_equal_cents(midi_note_number - A4_MIDI_NOTE_NUMBER),
+            TuningSystem::Edo(divisions) => get_edo_cents(midi_note_number - A4_MIDI_NOTE_NUMBER, *divisions),
+            TuningSystem::Pythagorean | TuningSystem::QuarterCommaMeantone => {
+                let tonic = self.get_tonic_key();
+                Cent(f64::from(get_equal_cents(tonic.get_midi_note_number() as i32 - A4_MIDI_NOTE_NUMBER))
+                    + f64::from(get_fifth_chain_cents(piano_key, tonic, self.system.get_fifth().unwrap_or(Cent(700.0)))))
+            }
+            TuningSystem::JustIntonation => {
+                let tonic = self.get_tonic_key().get_midi_note_number() as i32;
+                let semitones = midi_note_number - tonic;
+                let ratio = JUST_RATIOS[semitones.rem_euclid(12) as usize] * 2f64.powi(semitones.div_euclid(12));
+                Cent(f64::from(get_equal_cents(tonic - A4_MIDI_NOTE_NUMBER)) + OCTAVE_CENTS * ratio.log2())
+            }
+            // Unmapped keys keep their equal temperament pitch
+            TuningSystem::Scala(scala_tuning) => match scala_tuning.get_pitch(piano_key.get_midi_note_number()) {
+                Some(pitch) => return pitch,
+                None => get_equal_cents(midi_note_number - A4_MIDI_NOTE_NUMBER),
+            },
+        };
+        ret
+    }
+
+    fn get_tonic_key(&self) -> PianoKey {
+        PianoKey { note: self.tonic, octave: 4 }
+    }
+}
+
+impl fmt::Display for Tuning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if let TuningSystem::Scala(scala_tuning) = &self.system {
+            let mapping = &scala_tuning.mapping;
+            return write!(f, "{} ({} degrees, key {} = {} Hz)", self.system, scala_tuning.degrees.len(),
+                mapping.reference_note, f64::from(mapping.reference_frequency));
+        }
+        write!(f, "{} (A4 = {} Hz", self.system, f64::from(self.reference))?;
+        if matches!(self.system, TuningSystem::JustIntonation | TuningSystem::Pythagorean | TuningSystem::QuarterCommaMeantone) {
+            write!(f, ", tonic {}", self.tonic)?;
+        }
+        write!(f, ")")
+    }
+}
+
+fn get_equal_cents(semitones: i32) -> Cent {
+    Cent(100.0 * semitones as f64)
+}
+
+// Nearest step of the division to the equal temperament semitones
+fn get_edo_cents(semitones: i32, divisions: u16) -> Cent {
+    let divisions = divisions.max(1) as f64;
+    Cent((semitones as f64 * divisions / 12.0).round() * OCTAVE_CENTS / divisions)
+}
+
+// Position of the note on the chain of fifths (C is 0, G is 1, F is -1, F# is 6)
+fn get_fifths(note: Note) -> i32 {
+    use NoteLetter::*;
+    let letter_fifths = match note.letter {
+        F => -1,
+        C => 0,
+        G => 1,
+        D => 2,
+        A => 3,
+        E => 4,
+        B => 5,
+    };
+    letter_fifths + 7 * note.get_alter() as i32
+}
+
+// Cents from the origin, going up the fifths and back down by octaves to the key
+fn get_fifth_chain_cents(piano_key: PianoKey, origin: PianoKey, fifth: Cent) -> Cent {
+    let fifths = get_fifths(piano_key.note) - get_fifths(origin.note);
+    let semitones = piano_key.get_midi_note_number() as i32 - origin.get_midi_note_number() as i32;
+    // A fifth is 7 semitones, the remaining octaves bring it back to the key
+    let octaves = (7 * fifths - semitones).div_euclid(12);
+    Cent(fifths as f64 * f64::from(fifth) - octaves as f64 * OCTAVE_CENTS)
+}
@@ -4,18 +4,37 @@
 
 use std::str::FromStr;
 
+use num::{rational::Rational32, Zero};
+
 use crate::{
-    musicgeneration::rhythm_pattern_generator, 
+    musicfile::{
+        abc::{sheet_from_abc, sheet_to_abc},
+        scala::{keyboard_mapping_from_kbm, scala_tuning_from_scl},
+        get_chord_timeline, get_note_values,
+        midi::{get_midi_note_number, get_piano_key, get_ticks, sheet_from_midi, sheet_to_midi, TICKS_PER_QUARTER},
+        lilypond::{get_duration, get_pitch, sheet_to_lilypond},
+        musicxml::sheet_to_musicxml,
+        wav::{quantize, quantize_samples, samples_to_wav, WavConfig, WavSampleFormat},
+        flac::{samples_to_flac, FLAC_BLOCK_SIZE},
+        render::{align_and_mix_stems, get_stem_path, render_source},
+        pcm::{write_pcm_stream, PCM_CHUNK_SIZE},
+        piano_roll::{sheet_to_svg, KEY_HEIGHT, WHOLE_NOTE_WIDTH},
+        audio_image::{get_spectrogram, get_waveform_peaks, spectrogram_to_png, spectrogram_to_svg, waveform_to_png, waveform_to_svg, ImageFormat, FFT_SIZE, WAVEFORM_HEIGHT, WAVEFORM_WIDTH}
+    },
+    musicgeneration::{chord_progression_generator::chord_progression_generation, random_scale::get_random_scale, rhythm_pattern_generator, sheet_generator::sheet_generation}, 
     musictheory::{
         cent::Cent, 
         char_strs, 
         chord::{Chord, ChordInversion, ChordType}, 
-        chord_progression::ChordProgression, hertz::Hertz, interval::Interval, key::Key, measure::Measure, 
-        mode::{Mode, PentatonicMode}, note::{self, Note, NoteLetter}, note_value::{NoteValue, NoteValueBase, NoteValueDotted}, 
-        piano_key::PianoKey, pitch::{Pitch, C_ZERO, MIDDLE_C}, scale::Scale, semitone::Semitone, tempo::Tempo, 
-        time_signature::TimeSignature
+        chord_progression::ChordProgression, diatonic_interval::{DiatonicInterval, IntervalDirection, IntervalQuality}, hertz::Hertz, interval::Interval, key::Key, measure::Measure, 
+        mode::{Mode, PentatonicMode}, note::{self, Note, NoteLetter}, note_value::{NoteValue, NoteValueBase, NoteValueDotted, Tuplet}, 
+        pattern::Pattern, piano_key::PianoKey, pitch::{Pitch, C_ZERO, MIDDLE_C}, scale::Scale, semitone::Semitone, sheet::Sheet, 
+        spelling::{get_key_tonic, respell_in_key, spell_note}, tempo::Tempo, time_signature::{BeatAccent, TimeSignature},
+        scala_tuning::KeyboardMapping, tuning::{Tuning, TuningSystem}
     }
 };
+#[cfg(feature = "tui")]
+use crate::tui::{get_chord_index, get_next_pattern_start, get_piano_roll_lines, get_sheet_position, SheetPosition};
 
 #[test]
 fn test_substract_hertz() {
@@ -92,6 +111,21 @@ fn test_piano_key_to_str() {
     assert_eq!(PianoKey::new("Bb5").unwrap().to_string(), "Bb5".to_string());
 }
 
+#[test]
+fn test_piano_key_add_interval() {
+    use Interval::*;
+    assert_eq!(PianoKey::new("A4").unwrap() + Min3, PianoKey::new("C5").unwrap());
+    assert_eq!(PianoKey::new("Bb5").unwrap() + Unison, PianoKey::new("Bb5").unwrap());
+    // The top of the keyboard is B8
+    assert_eq!(PianoKey::new("A8").unwrap() + Octave, PianoKey::new("B8").unwrap());
+    assert_eq!(PianoKey::new("B8").unwrap() + Min2, PianoKey::new("B8").unwrap());
+    let mut piano_key = PianoKey::new("A#8").unwrap();
+    piano_key.inc();
+    assert_eq!(piano_key, PianoKey::new("B8").unwrap());
+    piano_key.inc();
+    assert_eq!(piano_key, PianoKey::new("B8").unwrap());
+}
+
 #[test]
 fn test_char_strs() {
         assert_eq!(char_strs("Hello"), ["H", "e", "l", "l", "o"])
@@ -108,6 +142,62 @@ fn test_add_interval() {
     assert_eq!(Maj7 + Min3, Maj2);
 }
 
+#[test]
+fn test_interval_from_semitones() {
+    use Interval::*;
+    assert_eq!(Interval::from(Semitone(14)), Maj2);
+    assert_eq!(Interval::from(Semitone(-1)), Maj7);
+    assert_eq!(Min7 + Maj3, Maj2);
+}
+
+#[test]
+fn test_diatonic_interval() {
+    use IntervalQuality::*;
+    let interval = |s: &str| DiatonicInterval::from_str(s).unwrap();
+    assert_eq!(i8::from(Semitone::from(interval("M9"))), 14);
+    assert_eq!(i8::from(Semitone::from(interval("P11"))), 17);
+    assert_eq!(i8::from(Semitone::from(interval("m13"))), 20);
+    assert_eq!(i8::from(Semitone::from(interval("-m3"))), -3);
+    assert_eq!(Semitone::from(interval("A4")), Semitone::from(interval("d5")));
+    assert_ne!(interval("A4"), interval("d5"));
+    assert_eq!(Cent::from(interval("P8")), Cent(1200.0));
+    assert_eq!(interval("M9").get_simple(), interval("M2"));
+    assert_eq!(interval("P15").get_simple(), interval("P8"));
+    assert_eq!(interval("M3").invert(), interval("m6"));
+    assert_eq!(interval("A4").invert(), interval("d5"));
+    assert_eq!(interval("-m10").to_string(), "-m10");
+    assert_eq!(DiatonicInterval::try_from(Semitone(-19)).unwrap(), interval("-P12"));
+    assert_eq!(DiatonicInterval::try_from(Semitone(12)).unwrap(), interval("P8"));
+    assert!(DiatonicInterval::try_from(Semitone(i8::MIN)).is_err());
+    assert!(DiatonicInterval::try_from(Semitone(i8::MAX)).is_err());
+    assert_eq!(DiatonicInterval::try_from(Cent(386.3)).unwrap(), interval("M3"));
+    assert_eq!(DiatonicInterval::try_from(Cent(-702.0)).unwrap(), interval("-P5"));
+    assert!(DiatonicInterval::try_from(Cent(f64::NAN)).is_err());
+    assert!(DiatonicInterval::try_from(Cent(1e9)).is_err());
+    assert_eq!(DiatonicInterval::from(Interval::Tritone), DiatonicInterval::new(Augmented, 4, IntervalDirection::Ascending).unwrap());
+    assert!(DiatonicInterval::from_str("P3").is_err());
+    assert!(DiatonicInterval::from_str("M5").is_err());
+    assert!(DiatonicInterval::from_str("d1").is_err());
+    assert!(DiatonicInterval::from_str("X3").is_err());
+}
+
+#[test]
+fn test_diatonic_interval_between_keys() {
+    let key = |s: &str| PianoKey::from_str(s).unwrap();
+    let interval = |s: &str| DiatonicInterval::from_str(s).unwrap();
+    assert_eq!(DiatonicInterval::between(key("C4"), key("D5")).unwrap(), interval("M9"));
+    assert_eq!(DiatonicInterval::between(key("C4"), key("F#4")).unwrap(), interval("A4"));
+    assert_eq!(DiatonicInterval::between(key("C4"), key("Gb4")).unwrap(), interval("d5"));
+    assert_eq!(DiatonicInterval::between(key("E4"), key("C4")).unwrap(), interval("-M3"));
+    assert_eq!(DiatonicInterval::between(key("C4"), key("C4")).unwrap(), interval("P1"));
+    assert!(DiatonicInterval::between(key("C4"), key("Ex4")).is_err());
+    assert_eq!(interval("m3").get_piano_key_from(key("A4")).unwrap(), key("C5"));
+    assert_eq!(interval("A2").get_piano_key_from(key("A4")).unwrap(), key("B#4"));
+    assert_eq!(interval("-P5").get_piano_key_from(key("C4")).unwrap(), key("F3"));
+    assert_eq!(interval("M13").get_piano_key_from(key("Eb3")).unwrap(), key("C5"));
+    assert!(interval("P8").get_piano_key_from(key("C8")).is_err());
+}
+
 #[test]
 fn test_sub_interval() {
     use Interval::*;
@@ -253,10 +343,131 @@ fn test_pentatonic_c_suspended() {
 
     assert_eq!(
         &Key::new(Pentatonic(Suspended), PianoKey::from_str("C4").unwrap(), 1).to_string(),
-        "[ C D F G A# C ]"
+        "[ C D F G Bb C ]"
     )
 }
 
+#[test]
+fn test_key_spelling() {
+    use Mode::*;
+    use Scale::*;
+    let key = |scale: Scale, tonic: &str| Key::new(scale, PianoKey::from_str(tonic).unwrap(), 1).to_string();
+    assert_eq!(&key(Diatonic(Ionian), "F4"), "[ F G A Bb C D E F ]");
+    assert_eq!(&key(Diatonic(Ionian), "Eb4"), "[ Eb F G Ab Bb C D Eb ]");
+    assert_eq!(&key(Diatonic(Aeolian), "D#4"), "[ D# E# F# G# A# B C# D# ]");
+    assert_eq!(&key(Diatonic(Ionian), "G#4"), "[ G# A# B# C# D# E# Fx G# ]");
+    assert_eq!(&key(Diatonic(Locrian), "B4"), "[ B C D E F G A B ]");
+    assert_eq!(&key(Chromatic, "C4"), "[ C Db D Eb E F F# G Ab A Bb B C ]");
+}
+
+#[test]
+fn test_extended_scales() {
+    let key = |scale: &str, tonic: &str| Key::new(Scale::from_str(scale).unwrap(), PianoKey::from_str(tonic).unwrap(), 1).to_string();
+    assert_eq!(&key("harmonic-minor", "A4"), "[ A B C D E F G# A ]");
+    assert_eq!(&key("melodic minor", "C4"), "[ C D Eb F G A B C ]");
+    assert_eq!(&key("PHRYGIAN_DOMINANT", "E4"), "[ E F G# A B C D E ]");
+    assert_eq!(&key("altered", "B4"), "[ B C D Eb F G A B ]");
+    assert_eq!(&key("lydian-dominant", "F4"), "[ F G A B C D Eb F ]");
+    assert_eq!(&key("hungarian-minor", "C4"), "[ C D Eb F# G Ab B C ]");
+    assert_eq!(&key("double-harmonic", "C4"), "[ C Db E F G Ab B C ]");
+    assert_eq!(&key("whole-tone", "C4"), "[ C D E F# Ab Bb C ]");
+    assert_eq!(&key("blues", "C4"), "[ C Eb F F# G Bb C ]");
+    assert_eq!(&key("half-whole", "C4"), "[ C Db Eb E F# G A Bb C ]");
+    assert_eq!(&key("bebop", "C4"), "[ C D E F G A Bb B C ]");
+    assert_eq!(&key("hirajoshi", "C4"), "[ C D Eb G Ab C ]");
+    assert_eq!(&key("in", "C4"), "[ C Db F G Ab C ]");
+    assert_eq!(Scale::from_str("harmonic-minor").unwrap().to_string(), "harmonic minor scale");
+    assert_eq!(Scale::from_str("bebop-dorian").unwrap().to_string(), "bebop dorian scale");
+    assert!(Scale::from_str("harmonic").is_err());
+    ["ukrainian-dorian", "lydian#2", "superlocrianbb7", "dorian-b2", "lydian-augmented", "mixolydian-b6", "locrian-natural2",
+        "whole-half", "bebop-major", "ionian-augmented", "locrian-natural6"].iter().for_each(|name| {
+        let octave: i32 = Scale::from_str(name).unwrap().get_intervals().iter().map(|i| i8::from(*i) as i32).sum();
+        assert_eq!(octave, 12, "{}", name);
+    });
+}
+
+#[test]
+fn test_extended_scales_generation() {
+    use rand::{rngs::SmallRng, SeedableRng};
+    let mut rng = SmallRng::seed_from_u64(0);
+    (0..200).for_each(|_| {
+        let scale = get_random_scale(&mut rng);
+        let progression = chord_progression_generation(scale, TimeSignature::default(), false, &mut rng);
+        let chord_progression = ChordProgression::from_scale_and_str(scale, PianoKey::from_str("C3").unwrap(), &progression);
+        assert!(!chord_progression.chords.is_empty());
+    });
+}
+
+#[test]
+fn test_custom_scale() {
+    let key = |scale: &str, tonic: &str| Key::new(Scale::from_str(scale).unwrap(), PianoKey::from_str(tonic).unwrap(), 1).to_string();
+    assert_eq!(&key("2-1-2-2-1-3-1", "A4"), "[ A B C D E F G# A ]");
+    assert_eq!(&key("W-H-W-W-H-W+H-H", "A4"), "[ A B C D E F G# A ]");
+    assert_eq!(&key("C D Eb F# G", "C4"), "[ C D Eb F# G C ]");
+    // Only the intervals of the notes are kept
+    assert_eq!(&key("D E F G# A", "C4"), "[ C D Eb F# G C ]");
+    assert_eq!(Scale::from_str("W-H-W-W-H-W+H-H").unwrap(), Scale::from_str("2-1-2-2-1-3-1").unwrap());
+    assert_eq!(Scale::from_str("3-2-2-3-2").unwrap().to_string(), "custom scale 3-2-2-3-2");
+    assert_eq!(Scale::from_str("3-2-2-3-2").unwrap().get_intervals(), vec![Interval::Min3, Interval::Maj2, Interval::Maj2, Interval::Min3, Interval::Maj2]);
+
+    assert!(Scale::from_str("2-2-2").unwrap_err().to_string().contains("octave"));
+    assert!(Scale::from_str("2-0-10").is_err());
+    assert!(Scale::from_str("12").is_err());
+    assert!(Scale::from_str("W-X-W").is_err());
+    assert!(Scale::from_str("200+100-1").is_err());
+    assert!(Scale::from_str("C E D").is_err());
+    assert!(Scale::from_str("C B# E").is_err());
+}
+
+#[test]
+fn test_custom_scale_generation() {
+    use rand::{rngs::SmallRng, SeedableRng};
+    let mut rng = SmallRng::seed_from_u64(0);
+    let base_note = PianoKey::from_str("C3").unwrap();
+    ["2-1-2-2-1-3-1", "C D Eb F# G", "1-11", "3-3-3-3", "6-6", "7-5"].iter().for_each(|name| {
+        let scale = Scale::from_str(name).unwrap();
+        (0..20).for_each(|i| {
+            let progression = chord_progression_generation(scale, TimeSignature::default(), i % 2 == 0, &mut rng);
+            let chord_progression = ChordProgression::from_scale_and_str(scale, base_note, &progression);
+            assert!(!chord_progression.chords.is_empty());
+        });
+        let sheet = sheet_generation(base_note, scale, 1, TimeSignature::default(), 2, false, &mut rng);
+        let notes = Key::new(scale, base_note, 1).get_notes();
+        assert!(sheet.patterns.iter().flat_map(|p| p.measures.iter()).flat_map(|m| m.notes.iter())
+            .all(|sn| notes.iter().any(|n| n.get_pitch_class() == sn.note.note.get_pitch_class())));
+    });
+    // Harmonic minor given by its steps has the chords of the scale
+    let progression = chord_progression_generation(Scale::from_str("2-1-2-2-1-3-1").unwrap(), TimeSignature::default(), false, &mut rng);
+    assert!(progression.starts_with("i-"), "{}", progression);
+}
+
+#[test]
+fn test_double_accidentals() {
+    assert_eq!(PianoKey::from_str("Fx4").unwrap().get_midi_note_number(), 67);
+    assert_eq!(PianoKey::from_str("F##4").unwrap(), PianoKey::from_str("Fx4").unwrap());
+    assert_eq!(PianoKey::from_str("Bbb3").unwrap().get_midi_note_number(), 57);
+    assert_eq!(PianoKey::from_str("Bbb3").unwrap().to_string(), "Bbb3");
+    assert_eq!(spell_note(NoteLetter::E, 2), Some(Note::from_str("Ebb").unwrap()));
+    assert_eq!(spell_note(NoteLetter::E, 1), None);
+    assert!(Note::from_str("C#b").is_err());
+}
+
+#[test]
+fn test_key_tonic_and_respelling() {
+    use Mode::*;
+    use Scale::*;
+    assert_eq!(get_key_tonic(PianoKey::from_str("A#4").unwrap(), Diatonic(Ionian)), PianoKey::from_str("Bb4").unwrap());
+    assert_eq!(get_key_tonic(PianoKey::from_str("C#4").unwrap(), Diatonic(Aeolian)), PianoKey::from_str("C#4").unwrap());
+    assert_eq!(get_key_tonic(PianoKey::from_str("G#4").unwrap(), Diatonic(Ionian)), PianoKey::from_str("Ab4").unwrap());
+
+    let f_major = Key::new(Diatonic(Ionian), PianoKey::from_str("F4").unwrap(), 1);
+    assert_eq!(respell_in_key(PianoKey::from_str("A#4").unwrap(), f_major), PianoKey::from_str("Bb4").unwrap());
+    assert_eq!(respell_in_key(PianoKey::from_str("C#5").unwrap(), f_major), PianoKey::from_str("Db5").unwrap());
+    let e_major = Key::new(Diatonic(Ionian), PianoKey::from_str("E4").unwrap(), 1);
+    assert_eq!(respell_in_key(PianoKey::from_str("F4").unwrap(), e_major), PianoKey::from_str("F4").unwrap());
+    assert_eq!(respell_in_key(PianoKey::from_str("Bb4").unwrap(), e_major), PianoKey::from_str("A#4").unwrap());
+}
+
 #[test]
 fn test_pentatonic_e_blues_minor() {
     use PentatonicMode::*;
@@ -310,6 +521,189 @@ fn test_piano_key_to_pitch() {
     assert_eq!(Pitch::from(PianoKey::default()), Pitch::new(C_ZERO));
 }
 
+#[test]
+fn test_piano_key_to_pitch_with_accidental() {
+    assert_eq!(Pitch::from(PianoKey::from_str("A#4").unwrap()), Pitch::new(Hertz(466.16)));
+    assert_eq!(Pitch::from(PianoKey::from_str("Bb4").unwrap()), Pitch::new(Hertz(466.16)));
+    assert_eq!(Pitch::from(PianoKey::from_str("C#4").unwrap()), Pitch::new(Hertz(277.18)));
+}
+
+#[test]
+fn test_pitch_to_nearest_piano_key() {
+    let (piano_key, cents) = Pitch::new(Hertz(440.0)).get_nearest_piano_key().unwrap();
+    assert_eq!(piano_key, PianoKey::from_str("A4").unwrap());
+    assert!(f64::from(cents).abs() < 0.1);
+    let (piano_key, cents) = Pitch::new(Hertz(450.0)).get_nearest_piano_key().unwrap();
+    assert_eq!(piano_key, PianoKey::from_str("A4").unwrap());
+    assert!((f64::from(cents) - 38.9).abs() < 0.1);
+    let (piano_key, cents) = Pitch::new(Hertz(270.0)).get_nearest_piano_key().unwrap();
+    assert_eq!(piano_key, PianoKey::from_str("C#4").unwrap());
+    assert!(f64::from(cents) < 0.0);
+    (12..120).for_each(|n| {
+        let piano_key = PianoKey::from_midi_note_number(n).unwrap();
+        assert_eq!(Pitch::from(piano_key).get_nearest_piano_key().unwrap().0, piano_key);
+    });
+    assert!(Pitch::new(Hertz(0.0)).get_nearest_piano_key().is_err());
+    assert!(Pitch::new(Hertz(-10.0)).get_midi_note_number().is_err());
+    assert!(Pitch::new(Hertz(20000.0)).get_nearest_piano_key().is_err());
+}
+
+#[test]
+fn test_key_quantize() {
+    use Mode::*;
+    use Scale::*;
+    let f_major = Key::new(Diatonic(Ionian), PianoKey::from_str("F4").unwrap(), 1);
+    // A#4 is in the key and spelled Bb4
+    let (piano_key, cents) = f_major.quantize(Pitch::new(Hertz(466.0))).unwrap();
+    assert_eq!(piano_key, PianoKey::from_str("Bb4").unwrap());
+    assert!(f64::from(cents).abs() < 1.0);
+    // B4 is as far from Bb4 as from C5, ties go to the lower key
+    let (piano_key, _) = f_major.quantize(Pitch::from(PianoKey::from_str("B4").unwrap())).unwrap();
+    assert_eq!(piano_key, PianoKey::from_str("Bb4").unwrap());
+    let mut pitch = Pitch::from(PianoKey::from_str("B4").unwrap());
+    pitch += Cent(20.0);
+    let (piano_key, cents) = f_major.quantize(pitch).unwrap();
+    assert_eq!(piano_key, PianoKey::from_str("C5").unwrap());
+    assert!((f64::from(cents) + 80.0).abs() < 0.1);
+
+    let c_pentatonic = Key::new(Pentatonic(PentatonicMode::Major), PianoKey::from_str("C4").unwrap(), 1);
+    assert_eq!(c_pentatonic.quantize(Pitch::new(Hertz(1400.0))).unwrap().0, PianoKey::from_str("E6").unwrap());
+    assert!(c_pentatonic.quantize(Pitch::new(Hertz(0.0))).is_err());
+}
+
+#[test]
+fn test_equal_temperament_tuning() {
+    let key = |s: &str| PianoKey::from_str(s).unwrap();
+    let tuning = Tuning::default();
+    assert_eq!(tuning.get_pitch(key("A4")), Pitch::new(Hertz(440.0)));
+    assert_eq!(tuning.get_pitch(key("C4")), Pitch::new(Hertz(261.626)));
+    assert_eq!(tuning.clone().set_reference(Hertz(432.0)).get_pitch(key("A4")), Pitch::new(Hertz(432.0)));
+    assert_eq!(tuning.clone().set_reference(Hertz(432.0)).get_pitch(key("A5")), Pitch::new(Hertz(864.0)));
+    let edo12 = tuning.clone().set_system(TuningSystem::Edo(12));
+    (12..120).for_each(|n| {
+        let piano_key = PianoKey::from_midi_note_number(n).unwrap();
+        assert_eq!(edo12.get_pitch(piano_key), tuning.get_pitch(piano_key));
+    });
+    assert!(tuning.clone().set_reference(Hertz(0.0)).validate().is_err());
+}
+
+#[test]
+fn test_pure_tunings() {
+    let key = |s: &str| PianoKey::from_str(s).unwrap();
+    let c4 = 261.626;
+    let just = Tuning::default().set_system(TuningSystem::JustIntonation);
+    assert_eq!(just.get_pitch(key("C4")), Pitch::new(Hertz(c4)));
+    assert_eq!(just.get_pitch(key("E4")), Pitch::new(Hertz(c4 * 5.0 / 4.0)));
+    assert_eq!(just.get_pitch(key("A3")), Pitch::new(Hertz(c4 * 5.0 / 6.0)));
+    // Pure from the tonic
+    let just_in_d = just.set_tonic(Note::from_str("D").unwrap());
+    assert_eq!(just_in_d.get_pitch(key("A4")), Pitch::new(Hertz(f64::from(Tuning::default().get_pitch(key("D4"))) * 1.5)));
+
+    let pythagorean = Tuning::default().set_system(TuningSystem::Pythagorean);
+    assert_eq!(pythagorean.get_pitch(key("G4")), Pitch::new(Hertz(c4 * 1.5)));
+    assert_eq!(pythagorean.get_pitch(key("E4")), Pitch::new(Hertz(c4 * 81.0 / 64.0)));
+    assert_eq!(pythagorean.get_pitch(key("F3")), Pitch::new(Hertz(c4 * 2.0 / 3.0)));
+
+    let meantone = Tuning::default().set_system(TuningSystem::QuarterCommaMeantone);
+    assert_eq!(meantone.get_pitch(key("E4")), Pitch::new(Hertz(c4 * 5.0 / 4.0)));
+    assert!(f64::from(meantone.get_pitch(key("G#4"))) < f64::from(meantone.get_pitch(key("Ab4"))));
+}
+
+#[test]
+fn test_edo_tuning() {
+    let key = |s: &str| PianoKey::from_str(s).unwrap();
+    let edo19 = Tuning::default().set_system(TuningSystem::Edo(19));
+    assert_eq!(edo19.get_pitch(key("A4")), Pitch::new(Hertz(440.0)));
+    assert_eq!(edo19.get_pitch(key("A5")), Pitch::new(Hertz(880.0)));
+    // Nearest step: the fifth is 11 steps of 19, the major third 6 steps (4 semitones are 6.33 steps)
+    assert_eq!(edo19.get_pitch(key("E5")), Pitch::new(Hertz(440.0 * 2f64.powf(11.0 / 19.0))));
+    assert_eq!(edo19.get_pitch(key("C#5")), Pitch::new(Hertz(440.0 * 2f64.powf(6.0 / 19.0))));
+    assert_eq!(edo19.get_pitch(key("C#5")), edo19.get_pitch(key("Db5")));
+    assert_eq!(edo19.get_pitch(key("G4")), Pitch::new(Hertz(440.0 * 2f64.powf(-3.0 / 19.0))));
+    assert_eq!(TuningSystem::Edo(19).get_fifth(), Some(Cent(1200.0 * 11.0 / 19.0)));
+    // 5 semitones are 2.08 steps of 5
+    let edo5 = Tuning::default().set_system(TuningSystem::Edo(5));
+    assert_eq!(edo5.get_pitch(key("D5")), Pitch::new(Hertz(440.0 * 2f64.powf(2.0 / 5.0))));
+    // The multiples of 12 contain every key of 12-TET
+    let edo24 = Tuning::default().set_system(TuningSystem::Edo(24));
+    (12..120).for_each(|n| {
+        let piano_key = PianoKey::from_midi_note_number(n).unwrap();
+        assert_eq!(edo24.get_pitch(piano_key), Tuning::default().get_pitch(piano_key));
+    });
+
+    assert_eq!(TuningSystem::from_str("19edo").unwrap(), TuningSystem::Edo(19));
+    assert_eq!(TuningSystem::from_str("Meantone").unwrap(), TuningSystem::QuarterCommaMeantone);
+    assert_eq!(TuningSystem::Edo(31).to_string(), "31edo");
+    assert!(TuningSystem::from_str("0edo").is_err());
+    assert!(TuningSystem::from_str("werckmeister").is_err());
+}
+
+#[test]
+fn test_scala_scale() {
+    let key = |s: &str| PianoKey::from_str(s).unwrap();
+    let scl = "! pentatonic.scl\n!\nJust pentatonic\n 5\n!\n 9/8\n 5/4 major third\n 701.955\n 5/3\n 2\n";
+    let scala_tuning = scala_tuning_from_scl(scl).unwrap();
+    assert_eq!(scala_tuning.description, "Just pentatonic");
+    assert_eq!(scala_tuning.degrees.len(), 5);
+    assert!((f64::from(scala_tuning.degrees[1]) - 386.314).abs() < 0.001);
+    assert!((f64::from(scala_tuning.get_degree_cents(-1)) - (884.359 - 1200.0)).abs() < 0.001);
+
+    // Linear mapping from C4, A4 is a period and a 5/3 above at 440 Hz
+    let tuning = Tuning::default().set_system(TuningSystem::Scala(scala_tuning));
+    tuning.validate().unwrap();
+    assert_eq!(tuning.get_pitch(key("A4")), Pitch::new(Hertz(440.0)));
+    assert_eq!(tuning.get_pitch(key("C4")), Pitch::new(Hertz(132.0)));
+    assert_eq!(tuning.get_pitch(key("C#4")), Pitch::new(Hertz(148.5)));
+    assert_eq!(tuning.get_pitch(key("F4")), Pitch::new(Hertz(264.0)));
+    assert_eq!(tuning.get_pitch(key("B3")), Pitch::new(Hertz(110.0)));
+}
+
+#[test]
+fn test_keyboard_mapping() {
+    let key = |s: &str| PianoKey::from_str(s).unwrap();
+    let scl = "12-TET\n12\n100.0\n200.0\n300.0\n400.0\n500.0\n600.0\n700.0\n800.0\n900.0\n1000.0\n1100.0\n2/1\n";
+    // White keys only from C4, C#4 unmapped, A4 at 432 Hz
+    let kbm = "! white.kbm\n12\n0\n127\n60\n69\n432.0\n12\n0\nx\n2\nx\n4\n5\nx\n7\nx\n9\nx\n11\n";
+    let mapping = keyboard_mapping_from_kbm(kbm).unwrap();
+    assert_eq!(mapping.mapping.len(), 12);
+    assert_eq!(mapping.mapping[1], None);
+    assert_eq!(mapping.get_degree(74, 12), Some(14));
+    assert_eq!(mapping.get_degree(73, 12), None);
+
+    let scala_tuning = scala_tuning_from_scl(scl).unwrap().set_mapping(mapping);
+    assert_eq!(scala_tuning.get_pitch(69), Some(Pitch::new(Hertz(432.0))));
+    assert_eq!(scala_tuning.get_pitch(81), Some(Pitch::new(Hertz(864.0))));
+    assert_eq!(scala_tuning.get_pitch(61), None);
+    // Unmapped keys fall back to equal temperament from the tuning reference
+    let tuning = Tuning::default().set_system(TuningSystem::Scala(scala_tuning));
+    assert_eq!(tuning.get_pitch(key("C#4")), Pitch::new(Hertz(277.18)));
+
+    let unmapped_reference = KeyboardMapping::default().set_reference(61, Hertz(440.0));
+    let mut scala_tuning = scala_tuning_from_scl(scl).unwrap().set_mapping(unmapped_reference);
+    scala_tuning.mapping.mapping = vec![Some(0), None];
+    assert!(scala_tuning.validate().is_err());
+}
+
+#[test]
+fn test_invalid_scala_files() {
+    let error = scala_tuning_from_scl("! bad.scl\nBad\n3\n9/8\n-5/4\n2\n").unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+    assert!(error.to_string().contains("line 5"), "{}", error);
+    assert!(scala_tuning_from_scl("Bad\nthree\n").unwrap_err().to_string().contains("line 2"));
+    assert!(scala_tuning_from_scl("Bad\n3\n9/8\n2\n").unwrap_err().to_string().contains("Expected 3 notes"));
+    assert!(scala_tuning_from_scl("Bad\n1\n1.2.3\n").unwrap_err().to_string().contains("line 3"));
+
+    let error = keyboard_mapping_from_kbm("0\n0\n127\n60\n200\n440.0\n0\n").unwrap_err();
+    assert!(error.to_string().contains("line 5"), "{}", error);
+    assert!(keyboard_mapping_from_kbm("2\n0\n127\n60\n69\n440.0\n0\n0\ny\n").unwrap_err().to_string().contains("line 9"));
+    assert!(keyboard_mapping_from_kbm("0\n0\n127\n").unwrap_err().to_string().contains("middle note"));
+    assert!(keyboard_mapping_from_kbm("0\n0\n127\n60\n69\n440.0\n0\n").is_ok());
+    let error = keyboard_mapping_from_kbm("! huge map\n18446744073709551615\n0\n127\n60\n69\n440.0\n0\n").unwrap_err();
+    assert_eq!(error.to_string(), "Invalid keyboard mapping at line 2: The map size 18446744073709551615 is larger than the 128 MIDI keys");
+    assert!(keyboard_mapping_from_kbm("128\n0\n127\n60\n69\n440.0\n0\n").is_ok());
+    assert!(scala_tuning_from_scl("huge\n18446744073709551615\n3/2\n").is_err());
+}
+
 #[test]
 fn test_tempo_bps() {
     assert_eq!(Tempo::from(60).get_bps(), 1.0);
@@ -343,7 +737,7 @@ fn test_c4_minor_triad() {
     use ChordInversion::Root;
     assert_eq!(
         &Chord::new(MinorTriad, PianoKey::from_str("C4").unwrap(), Root).get_keys_string(),
-        "| C4 D#4 G4 |"
+        "| C4 Eb4 G4 |"
     );
 }
 
@@ -386,10 +780,23 @@ fn test_custom_chord() {
     let chord = Chord::new(CustomChord,PianoKey::from_str("A4").unwrap(), Root).set_intervals(vec![Min2, Maj3]);
     assert_eq!(
         &chord.get_keys_string(),
-        "| A4 A#4 C#5 |"
+        "| A4 Bb4 C#5 |"
     )
 }
 
+#[test]
+fn test_chord_spelling() {
+    use ChordType::*;
+    use ChordInversion::*;
+    let chord = |chord_type: ChordType, base_note: &str, inversion: ChordInversion| {
+        Chord::new(chord_type, PianoKey::from_str(base_note).unwrap(), inversion).get_keys_string()
+    };
+    assert_eq!(&chord(DiminishedSeventh, "B3", Root), "| B3 D4 F4 Ab4 |");
+    assert_eq!(&chord(AugmentedTriad, "E4", Root), "| E4 G#4 B#4 |");
+    assert_eq!(&chord(DominantSeventh, "Eb4", Root), "| Eb4 G4 Bb4 Db5 |");
+    assert_eq!(&chord(MinorTriad, "F4", First), "| Ab4 C5 F5 |");
+}
+
 #[test]
 #[should_panic]
 fn test_custom_chord_without_interval() {
@@ -398,7 +805,7 @@ fn test_custom_chord_without_interval() {
     let chord = Chord::new(CustomChord,PianoKey::from_str("A4").unwrap(), Root);
     assert_eq!(
         &chord.get_keys_string(),
-        "| A4 A#4 C#5 |"
+        "| A4 Bb4 C#5 |"
     )
 }
 
@@ -455,19 +862,19 @@ fn test_note_value_to_str() {
     use NoteValueBase::*;
     use NoteValueDotted::*;
     assert_eq!(NoteValue::default().to_string(), "𝅘𝅥");
-    assert_eq!(NoteValue{base: Whole, dotted: None}.to_string(), "𝅝");
-    assert_eq!(NoteValue{base: Half, dotted: Some(Dotted)}.to_string(), "𝅗𝅥.");
-    assert_eq!(NoteValue{base: Eighth, dotted: Some(DoubleDotted)}.to_string(), "𝅘𝅥𝅮..");
+    assert_eq!(NoteValue{base: Whole, dotted: None, tuplet: None}.to_string(), "𝅝");
+    assert_eq!(NoteValue{base: Half, dotted: Some(Dotted), tuplet: None}.to_string(), "𝅗𝅥.");
+    assert_eq!(NoteValue{base: Eighth, dotted: Some(DoubleDotted), tuplet: None}.to_string(), "𝅘𝅥𝅮..");
 }
 
 #[test]
 fn test_note_value_relative_duration() {
     use NoteValueBase::*;
     use NoteValueDotted::*;
-    assert_eq!(NoteValue::default().get_relative_duration(), 0.25);
-    assert_eq!(NoteValue{base: Whole, dotted: None}.get_relative_duration(), 1.0);
-    assert_eq!(NoteValue{base: Half, dotted: Some(Dotted)}.get_relative_duration(), 0.75);
-    assert_eq!(NoteValue{base: Whole, dotted: Some(DoubleDotted)}.get_relative_duration(), 1.75);
+    assert_eq!(NoteValue::default().get_relative_duration(), Rational32::new(1, 4));
+    assert_eq!(NoteValue{base: Whole, dotted: None, tuplet: None}.get_relative_duration(), Rational32::new(1, 1));
+    assert_eq!(NoteValue{base: Half, dotted: Some(Dotted), tuplet: None}.get_relative_duration(), Rational32::new(3, 4));
+    assert_eq!(NoteValue{base: Whole, dotted: Some(DoubleDotted), tuplet: None}.get_relative_duration(), Rational32::new(7, 4));
 }
 
 #[test]
@@ -476,18 +883,23 @@ fn test_note_value_duration_in_second() {
     use NoteValueDotted::*;
     assert_eq!(NoteValue::default().get_duration_for_tempo(Tempo::from(60)), 1.0);
     assert_eq!(NoteValue::default().get_duration_for_tempo(Tempo::from(120)), 0.5);
-    assert_eq!(NoteValue{base: Half, dotted: None}.get_duration_for_tempo(Tempo::from(120)), 1.0);
-    assert_eq!(NoteValue{base: Whole, dotted: Some(Dotted)}.get_duration_for_tempo(Tempo::from(60)), 6.0);
+    assert_eq!(NoteValue{base: Half, dotted: None, tuplet: None}.get_duration_for_tempo(Tempo::from(120)), 1.0);
+    assert_eq!(NoteValue{base: Whole, dotted: Some(Dotted), tuplet: None}.get_duration_for_tempo(Tempo::from(60)), 6.0);
 }
 
 #[test]
 fn test_new_time_signature() {
-    assert_eq!(TimeSignature::default(), TimeSignature(1.0));
-    assert_eq!(TimeSignature::from_str("4/4").unwrap(), TimeSignature(1.0));
-    assert_eq!(TimeSignature::from_str("3/4").unwrap(), TimeSignature(0.75));
-    assert_eq!(TimeSignature::from_str("5/4").unwrap(), TimeSignature(1.25));
-    assert_eq!(TimeSignature::from_str("2/4").unwrap(), TimeSignature(0.5));
-    assert_eq!(TimeSignature::from_str("3/8").unwrap(), TimeSignature(0.375));
+    let time_signature = |s: &str| TimeSignature::from_str(s).unwrap();
+    assert_eq!(TimeSignature::default(), TimeSignature::new(4, 4).unwrap());
+    assert_eq!(time_signature("4/4"), TimeSignature::new(4, 4).unwrap());
+    assert_eq!(Rational32::from(time_signature("3/4")), Rational32::new(3, 4));
+    assert_eq!(Rational32::from(time_signature("5/4")), Rational32::new(5, 4));
+    assert_eq!(Rational32::from(time_signature("2/4")), Rational32::new(1, 2));
+    assert_eq!(Rational32::from(time_signature("3/8")), Rational32::new(3, 8));
+    // Same length, different meters
+    assert_ne!(time_signature("3/4"), time_signature("6/8"));
+    assert_eq!(time_signature("6/8").get_fraction(), (6, 8));
+    assert_eq!(time_signature("2/2").get_fraction(), (2, 2));
 }
 
 #[test]
@@ -504,10 +916,67 @@ fn test_new_incorrect_time_signature_2() {
 
 #[test]
 fn test_time_signature_to_str() {
-    assert_eq!(TimeSignature(0.75).to_string(), "3/4");
-    assert_eq!(TimeSignature(0.375).to_string(), "3/8");
-    assert_eq!(TimeSignature(1.0).to_string(), "4/4");
-    assert_eq!(TimeSignature(0.5).to_string(), "2/4")
+    assert_eq!(TimeSignature::new(3, 4).unwrap().to_string(), "3/4");
+    assert_eq!(TimeSignature::new(3, 8).unwrap().to_string(), "3/8");
+    assert_eq!(TimeSignature::new(4, 4).unwrap().to_string(), "4/4");
+    assert_eq!(TimeSignature::new(2, 4).unwrap().to_string(), "2/4");
+    assert_eq!(TimeSignature::new(6, 8).unwrap().to_string(), "6/8");
+    assert_eq!(TimeSignature::from_grouping(&[3, 2, 2], 8).unwrap().to_string(), "3+2+2/8");
+    assert_eq!(TimeSignature::from_str("2+2+3/8").unwrap().to_string(), "7/8");
+}
+
+#[test]
+fn test_time_signature_grouping() {
+    use BeatAccent::*;
+    let time_signature = |s: &str| TimeSignature::from_str(s).unwrap();
+    assert_eq!(time_signature("4/4").get_grouping(), vec![2, 2]);
+    assert_eq!(time_signature("3/4").get_grouping(), vec![3]);
+    assert_eq!(time_signature("6/8").get_grouping(), vec![3, 3]);
+    assert_eq!(time_signature("7/8").get_grouping(), vec![2, 2, 3]);
+    assert_eq!(time_signature("3+2+2/8").get_grouping(), vec![3, 2, 2]);
+    assert_eq!(time_signature("3+2+2/8").numerator, 7);
+
+    assert_eq!(time_signature("4/4").get_accents(), vec![Strong, Weak, Medium, Weak]);
+    assert_eq!(time_signature("6/8").get_accents(), vec![Strong, Weak, Weak, Medium, Weak, Weak]);
+    assert_eq!(time_signature("2+2+3/8").get_accents(), vec![Strong, Weak, Medium, Weak, Medium, Weak, Weak]);
+    assert_eq!(time_signature("6/8").get_accent(Rational32::new(3, 8)), Some(Medium));
+    assert_eq!(time_signature("6/8").get_accent(Rational32::new(1, 16)), None);
+    assert_eq!(time_signature("6/8").get_accent(Rational32::new(3, 4)), None);
+
+    assert!(TimeSignature::from_str("2+0+3/8").is_err());
+    assert!(TimeSignature::from_str("7/12").is_err());
+    assert!(TimeSignature::from_str("65/4").is_err());
+    assert!(TimeSignature::from_str("7").is_err());
+}
+
+#[test]
+fn test_generation_in_time_signature() {
+    use rand::{rngs::SmallRng, SeedableRng};
+    use rhythm_pattern_generator::{rhythm_pattern_generation, rhythm_pattern_generation_for_chord, rhythm_pattern_rand_generation};
+    let mut rng = SmallRng::seed_from_u64(0);
+    ["7/8", "6/8", "5/4", "3+3+2/8", "4/4", "1/32", "3/32", "1/64", "5/64", "2+3/64"].iter().for_each(|s| {
+        let time_signature = TimeSignature::from_str(s).unwrap();
+        let length = Rational32::from(time_signature);
+        let get_length = |rhythm_pattern: &[NoteValue]| rhythm_pattern.iter().fold(Rational32::zero(), |sum, nv| sum + nv.get_relative_duration());
+        (0..20).for_each(|_| {
+            let rhythm_pattern = rhythm_pattern_rand_generation(time_signature, &mut rng);
+            assert_eq!(get_length(&rhythm_pattern), length, "{}", s);
+            // No note crosses a beat group unless it starts one
+            let mut position = Rational32::zero();
+            rhythm_pattern.iter().for_each(|nv| {
+                let end = position + nv.get_relative_duration();
+                let crossed = (1..time_signature.numerator)
+                    .map(|n| Rational32::new(n as i32, time_signature.denominator as i32))
+                    .any(|p| p > position && p < end && time_signature.get_accent(p) != Some(BeatAccent::Weak));
+                assert!(!crossed || time_signature.get_accent(position).is_some_and(|a| a != BeatAccent::Weak), "{} {:?}", s, rhythm_pattern);
+                position = end;
+            });
+        });
+        assert_eq!(get_length(&rhythm_pattern_generation(time_signature, &mut rng)), length);
+        assert!(get_length(&rhythm_pattern_generation_for_chord(time_signature, &mut rng)) >= length);
+        let sheet = sheet_generation(PianoKey::from_str("C4").unwrap(), Scale::default(), 1, time_signature, 2, false, &mut rng);
+        assert!(sheet.patterns.iter().flat_map(|p| p.measures.iter()).all(|m| m.time_signature == time_signature && m.is_measure_complete()));
+    });
 }
 
 #[test]
@@ -516,20 +985,20 @@ fn test_measure_remaining_value() {
 
     let mut measure = Measure::new(TimeSignature::default());
     measure.add_note(PianoKey::default(), NoteValue::default());
-    assert_eq!(measure.get_remaining_value(), 0.75);
+    assert_eq!(measure.get_remaining_value(), Rational32::new(3, 4));
 
-    measure.add_note(PianoKey::default(), NoteValue{base: Half, dotted: None});
-    assert_eq!(measure.get_remaining_value(), 0.25);
+    measure.add_note(PianoKey::default(), NoteValue{base: Half, dotted: None, tuplet: None});
+    assert_eq!(measure.get_remaining_value(), Rational32::new(1, 4));
 }
 
 #[test]
 fn test_measure_is_complete() {
     use NoteValueBase::Whole;
     let mut measure = Measure::new(TimeSignature::default());
-    assert_eq!(measure.is_measure_complete(), false);
+    assert!(!measure.is_measure_complete());
 
-    measure.add_note(PianoKey::default(), NoteValue{base: Whole, dotted: None});
-    assert_eq!(measure.is_measure_complete(), true);
+    measure.add_note(PianoKey::default(), NoteValue{base: Whole, dotted: None, tuplet: None});
+    assert!(measure.is_measure_complete());
 }
 
 #[test]
@@ -539,8 +1008,8 @@ fn test_rand_pattern_generation() {
 
     let rhythm_pattern = rhythm_pattern_rand_generation(TimeSignature::default(), &mut seed_gen);
     assert_eq!(
-        rhythm_pattern.iter().fold(0.0, |sum, nv| sum + nv.get_relative_duration()), 
-        f32::from(TimeSignature::default())
+        rhythm_pattern.iter().fold(Rational32::zero(), |sum, nv| sum + nv.get_relative_duration()), 
+        Rational32::from(TimeSignature::default())
     )
 }
 
@@ -551,7 +1020,7 @@ fn test_measure_add_note_over_total_duration() {
     use NoteValueDotted::Dotted;
 
     let mut measure = Measure::new(TimeSignature::default());
-    measure.add_note(PianoKey::default(), NoteValue{base: Whole, dotted: Some(Dotted)})
+    measure.add_note(PianoKey::default(), NoteValue{base: Whole, dotted: Some(Dotted), tuplet: None})
 }
 
 #[test]
@@ -562,4 +1031,821 @@ fn test_piano_key_get_distance() {
     assert_eq!(PianoKey::from_str("D5").unwrap().get_distance(PianoKey::from_str("C#4").unwrap()), 13);
     assert_eq!(PianoKey::from_str("A#3").unwrap().get_distance(PianoKey::from_str("C4").unwrap()), 2);
     assert_eq!(PianoKey::from_str("F#4").unwrap().get_distance(PianoKey::from_str("A5").unwrap()), 15);
-}
\ No newline at end of file
+    assert_eq!(PianoKey::from_str("Cb4").unwrap().get_distance(PianoKey::from_str("B3").unwrap()), 0);
+}
+
+#[test]
+fn test_piano_key_midi_note_number() {
+    assert_eq!(PianoKey::from_str("C4").unwrap().get_midi_note_number(), 60);
+    assert_eq!(PianoKey::from_str("Cb4").unwrap().get_midi_note_number(), 59);
+    assert_eq!(PianoKey::from_str("B#3").unwrap().get_midi_note_number(), 60);
+    assert_eq!(PianoKey::from_midi_note_number(61).unwrap(), PianoKey::from_str("C#4").unwrap());
+    assert!(PianoKey::from_midi_note_number(11).is_err());
+    assert!(PianoKey::from_midi_note_number(120).is_err());
+    (12..120).for_each(|n| assert_eq!(PianoKey::from_midi_note_number(n).unwrap().get_midi_note_number(), n));
+}
+
+#[test]
+fn test_piano_key_ordering() {
+    let key = |s: &str| PianoKey::from_str(s).unwrap();
+    assert!(key("B3") < key("C4"));
+    assert!(key("Cb4") < key("C4"));
+    assert!(key("C#4") < key("D4"));
+    assert!(key("C#4") < key("Db4"));
+    let mut keys = vec![key("G4"), key("C4"), key("E3"), key("A#3")];
+    keys.sort();
+    assert_eq!(keys, vec![key("E3"), key("A#3"), key("C4"), key("G4")]);
+    assert_eq!(keys.iter().max(), Some(&key("G4")));
+}
+
+// Test for MIDI export
+fn get_test_sheet() -> Sheet {
+    use NoteValueBase::Half;
+    let mut measure = Measure::new(TimeSignature::default());
+    measure.add_note(PianoKey::from_str("C4").unwrap(), NoteValue{base: Half, dotted: None, tuplet: None});
+    measure.add_note(PianoKey::from_str("E4").unwrap(), NoteValue::default());
+    measure.add_note(PianoKey::from_str("G4").unwrap(), NoteValue::default());
+
+    let mut pattern = Pattern::new(String::from("Pattern 0"));
+    pattern.add_measure(measure);
+    let mut sheet = Sheet::new();
+    sheet.add_pattern(pattern);
+    sheet
+}
+
+#[test]
+fn test_midi_note_number() {
+    assert_eq!(get_midi_note_number(PianoKey::from_str("C4").unwrap()), 60);
+    assert_eq!(get_midi_note_number(PianoKey::from_str("A4").unwrap()), 69);
+    assert_eq!(get_midi_note_number(PianoKey::from_str("C#4").unwrap()), 61);
+    assert_eq!(get_midi_note_number(PianoKey::from_str("Bb3").unwrap()), 58);
+}
+
+#[test]
+fn test_midi_ticks() {
+    use NoteValueBase::*;
+    use NoteValueDotted::*;
+    assert_eq!(get_ticks(NoteValue::default()), TICKS_PER_QUARTER as u32);
+    assert_eq!(get_ticks(NoteValue{base: Whole, dotted: None, tuplet: None}), 4 * TICKS_PER_QUARTER as u32);
+    assert_eq!(get_ticks(NoteValue{base: Quarter, dotted: Some(Dotted), tuplet: None}), 720);
+}
+
+#[test]
+fn test_sheet_to_midi_header() {
+    let midi = sheet_to_midi(&get_test_sheet(), None, Tempo::from(120), TimeSignature::default());
+    assert_eq!(&midi[0..4], b"MThd");
+    // format 1, 2 tracks (conductor and melody), 480 ticks per quarter
+    assert_eq!(&midi[8..14], &[0, 1, 0, 2, 0x01, 0xE0]);
+    assert_eq!(&midi[14..18], b"MTrk");
+    // 500 000 microseconds per quarter at 120 BPM
+    assert!(midi.windows(6).any(|w| w == [0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20]));
+    // 4/4
+    assert!(midi.windows(5).any(|w| w == [0xFF, 0x58, 0x04, 4, 2]));
+}
+
+#[test]
+fn test_sheet_to_midi_with_chords() {
+    use NoteValueBase::Whole;
+    let rhythm_pattern = vec![NoteValue{base: Whole, dotted: None, tuplet: None}];
+    let midi = sheet_to_midi(
+        &get_test_sheet(),
+        Some((&ChordProgression::default(), &rhythm_pattern)),
+        Tempo::from(60),
+        TimeSignature::from_str("3/4").unwrap()
+    );
+    assert_eq!(&midi[10..12], &[0, 3]);
+    assert!(midi.windows(5).any(|w| w == [0xFF, 0x58, 0x04, 3, 2]));
+    // melody: C4 half note, then E4 on tick 960 (encoded as 0x87 0x40)
+    assert!(midi.windows(6).any(|w| w == [0x90, 60, 100, 0x87, 0x40, 0x80]));
+    // chords on the second channel, C4maj first
+    assert!(midi.windows(3).any(|w| w == [0x91, 60, 80]));
+}
+
+#[test]
+fn test_midi_note_number_to_piano_key() {
+    assert_eq!(get_piano_key(60), PianoKey::from_str("C4").unwrap());
+    assert_eq!(get_piano_key(70), PianoKey::from_str("A#4").unwrap());
+    assert_eq!(get_piano_key(0), PianoKey::from_str("C0").unwrap());
+}
+
+#[test]
+fn test_sheet_from_midi_round_trip() {
+    let sheet = get_test_sheet();
+    let midi = sheet_to_midi(&sheet, None, Tempo::from(90), TimeSignature::default());
+    let (imported_sheet, tempo) = sheet_from_midi(&midi).unwrap();
+    assert_eq!(tempo, Tempo::from(90));
+    assert_eq!(imported_sheet.to_string(), sheet.to_string());
+}
+
+#[test]
+fn test_sheet_from_midi_split_measures() {
+    use NoteValueBase::*;
+    use NoteValueDotted::Dotted;
+    // A dotted half note in 2/4 has to be split over two measures
+    let mut measure = Measure::new(TimeSignature::default());
+    measure.add_note(PianoKey::from_str("C4").unwrap(), NoteValue::default());
+    measure.add_note(PianoKey::from_str("D4").unwrap(), NoteValue{base: Half, dotted: Some(Dotted), tuplet: None});
+    let mut pattern = Pattern::new(String::new());
+    pattern.add_measure(measure);
+    let mut sheet = Sheet::new();
+    sheet.add_pattern(pattern);
+
+    let midi = sheet_to_midi(&sheet, None, Tempo::from(60), TimeSignature::from_str("2/4").unwrap());
+    let (imported_sheet, _) = sheet_from_midi(&midi).unwrap();
+    let measures = &imported_sheet.patterns[0].measures;
+    assert_eq!(measures.len(), 2);
+    assert_eq!(measures[0].notes.len(), 2);
+    assert_eq!(measures[0].notes[1].note, PianoKey::from_str("D4").unwrap());
+    assert_eq!(measures[0].notes[1].value, NoteValue::default());
+    assert_eq!(measures[1].notes.len(), 1);
+    assert_eq!(measures[1].notes[0].note, PianoKey::from_str("D4").unwrap());
+    assert_eq!(measures[1].notes[0].value, NoteValue{base: Half, dotted: None, tuplet: None});
+    assert!(measures.iter().all(|m| m.is_measure_complete()));
+}
+
+#[test]
+fn test_sheet_from_midi_short_unit() {
+    use NoteValueBase::*;
+    // 3/32 is not a whole number of sixteenths
+    let time_signature = TimeSignature::from_str("3/32").unwrap();
+    let mut pattern = Pattern::new(String::new());
+    for (first, second) in [(ThirtySecond, Sixteenth), (Sixteenth, ThirtySecond)] {
+        let mut measure = Measure::new(time_signature);
+        measure.add_note(PianoKey::from_str("C4").unwrap(), NoteValue{base: first, dotted: None, tuplet: None});
+        measure.add_note(PianoKey::from_str("E4").unwrap(), NoteValue{base: second, dotted: None, tuplet: None});
+        pattern.add_measure(measure);
+    }
+    let mut sheet = Sheet::new();
+    sheet.add_pattern(pattern);
+
+    let midi = sheet_to_midi(&sheet, None, Tempo::from(60), time_signature);
+    let (imported_sheet, _) = sheet_from_midi(&midi).unwrap();
+    let measures = &imported_sheet.patterns[0].measures;
+    assert_eq!(measures.len(), 2);
+    assert!(measures.iter().all(|m| m.is_measure_complete()));
+    assert_eq!(measures[0].notes[0].value, NoteValue{base: ThirtySecond, dotted: None, tuplet: None});
+    assert_eq!(measures[1].notes[1].value, NoteValue{base: ThirtySecond, dotted: None, tuplet: None});
+}
+
+#[test]
+fn test_sheet_from_invalid_midi() {
+    assert!(sheet_from_midi(b"RIFF0000").is_err());
+    assert!(sheet_from_midi(&[]).is_err());
+
+    // The delta times of the track add up past the u32 range
+    let mut track = Vec::new();
+    (0..17).for_each(|_| track.extend([0xFF, 0xFF, 0xFF, 0x7F, 0xFF, 0x01, 0x00]));
+    let mut midi = b"MThd\x00\x00\x00\x06\x00\x00\x00\x01\x01\xE0MTrk".to_vec();
+    midi.extend((track.len() as u32).to_be_bytes());
+    midi.extend(track);
+    assert_eq!(sheet_from_midi(&midi).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_chord_timeline() {
+    use NoteValueBase::*;
+    let rhythm_pattern = vec![NoteValue{base: Whole, dotted: None, tuplet: None}, NoteValue{base: Half, dotted: None, tuplet: None}];
+    let chord_progression = ChordProgression::default();
+    let timeline = get_chord_timeline(&chord_progression, &rhythm_pattern, Rational32::from_integer(6));
+    assert_eq!(timeline.len(), 8);
+    assert_eq!(timeline[1].0, Rational32::new(1, 1));
+    assert_eq!(timeline[2].0, Rational32::new(3, 2));
+    assert_eq!(timeline[4].2.to_string(), "C4maj");
+}
+
+// Test for MusicXML export
+#[test]
+fn test_sheet_to_musicxml() {
+    use NoteValueBase::Whole;
+    let rhythm_pattern = vec![NoteValue{base: Whole, dotted: None, tuplet: None}];
+    let musicxml = sheet_to_musicxml(
+        &get_test_sheet(),
+        Some((&ChordProgression::default(), &rhythm_pattern)),
+        Key::new(Scale::default(), PianoKey::from_str("C4").unwrap(), 1),
+        Tempo::from(100)
+    );
+    assert!(musicxml.starts_with("<?xml"));
+    assert!(musicxml.contains("<divisions>32</divisions>"));
+    assert!(musicxml.contains("<beats>4</beats>\n          <beat-type>4</beat-type>"));
+    assert!(musicxml.contains("<sound tempo=\"100\"/>"));
+    assert!(musicxml.contains("<step>C</step>\n          <octave>4</octave>\n        </pitch>\n        <duration>64</duration>\n        <type>half</type>"));
+    assert_eq!(musicxml.matches("<measure number=").count(), 1);
+    // Only the first chord fits in the melody
+    assert_eq!(musicxml.matches("<harmony>").count(), 1);
+    assert!(musicxml.contains("<root-step>C</root-step>\n        </root>\n        <kind>major</kind>"));
+}
+
+#[test]
+fn test_sheet_to_musicxml_dotted_and_altered_notes() {
+    use NoteValueBase::*;
+    use NoteValueDotted::DoubleDotted;
+    let mut measure = Measure::new(TimeSignature::from_str("2/4").unwrap());
+    measure.add_note(PianoKey::from_str("Bb3").unwrap(), NoteValue{base: Quarter, dotted: Some(DoubleDotted), tuplet: None});
+    measure.add_note(PianoKey::from_str("F#4").unwrap(), NoteValue{base: Sixteenth, dotted: None, tuplet: None});
+    let mut pattern = Pattern::new(String::new());
+    pattern.add_measure(measure);
+    let mut sheet = Sheet::new();
+    sheet.add_pattern(pattern);
+
+    let key = Key::new(Scale::from_str("harmonic minor").unwrap(), PianoKey::from_str("G3").unwrap(), 1);
+    let musicxml = sheet_to_musicxml(&sheet, None, key, Tempo::from(60));
+    assert!(musicxml.contains("<step>B</step>\n          <alter>-1</alter>\n          <octave>3</octave>"));
+    // Bb is in the G minor key signature, the raised F# is not
+    assert!(musicxml.contains("<key>\n          <fifths>-2</fifths>\n          <mode>minor</mode>\n        </key>"));
+    assert!(musicxml.contains("<duration>56</duration>\n        <type>quarter</type>\n        <dot/>\n        <dot/>\n      </note>"));
+    assert!(musicxml.contains("<alter>1</alter>"));
+    assert!(musicxml.contains("<accidental>sharp</accidental>"));
+    assert!(!musicxml.contains("<accidental>flat</accidental>"));
+    assert!(musicxml.contains("<beats>2</beats>"));
+    assert!(!musicxml.contains("<harmony>"));
+}
+
+#[test]
+fn test_sheet_to_musicxml_spelled_in_key() {
+    // Imported sheets are spelled with sharps, Bb is in F major
+    let sheet = Sheet::from_str("A#4h B4q A#4q |\n").unwrap();
+    let rhythm_pattern = vec![NoteValue{base: NoteValueBase::Whole, dotted: None, tuplet: None}];
+    let key = Key::new(Scale::default(), PianoKey::from_str("F4").unwrap(), 1);
+    let chord_progression = ChordProgression::from_scale_and_str(Scale::default(), PianoKey::from_str("F3").unwrap(), "IV");
+    let musicxml = sheet_to_musicxml(&sheet, Some((&chord_progression, &rhythm_pattern)), key, Tempo::from(60));
+    assert!(musicxml.contains("<step>B</step>\n          <alter>-1</alter>\n          <octave>4</octave>"));
+    assert!(musicxml.contains("<root-step>B</root-step>\n          <root-alter>-1</root-alter>"));
+    // The first Bb comes from the key signature, the one after the B natural needs its flat again
+    assert!(musicxml.contains("<key>\n          <fifths>-1</fifths>\n          <mode>major</mode>\n        </key>"));
+    assert_eq!(musicxml.matches("<accidental>natural</accidental>").count(), 1);
+    assert_eq!(musicxml.matches("<accidental>flat</accidental>").count(), 1);
+}
+
+// Test for LilyPond export
+#[test]
+fn test_lilypond_pitch_and_duration() {
+    use NoteValueBase::*;
+    use NoteValueDotted::*;
+    assert_eq!(get_pitch(PianoKey::from_str("C4").unwrap()), "c'");
+    assert_eq!(get_pitch(PianoKey::from_str("F#5").unwrap()), "fis''");
+    assert_eq!(get_pitch(PianoKey::from_str("Bb3").unwrap()), "bes");
+    assert_eq!(get_pitch(PianoKey::from_str("E1").unwrap()), "e,,");
+    assert_eq!(get_duration(NoteValue::default()), "4");
+    assert_eq!(get_duration(NoteValue{base: Half, dotted: Some(Dotted), tuplet: None}), "2.");
+    assert_eq!(get_duration(NoteValue{base: Sixteenth, dotted: Some(DoubleDotted), tuplet: None}), "16..");
+}
+
+#[test]
+fn test_sheet_to_lilypond() {
+    use Mode::Aeolian;
+    use NoteValueBase::Half;
+    let rhythm_pattern = vec![NoteValue{base: Half, dotted: None, tuplet: None}];
+    let chord_progression = ChordProgression::from_scale_and_str(Scale::Diatonic(Aeolian), PianoKey::from_str("A3").unwrap(), "i-iv-v");
+    let lilypond = sheet_to_lilypond(
+        &get_test_sheet(),
+        Some((&chord_progression, &rhythm_pattern)),
+        Key::new(Scale::Diatonic(Aeolian), PianoKey::from_str("A4").unwrap(), 1),
+        Tempo::from(80)
+    );
+    assert!(lilypond.starts_with("\\version"));
+    assert!(lilypond.contains("\\chordmode {\n    a2:m d2:m\n  }"));
+    assert!(lilypond.contains("\\key a \\minor"));
+    assert!(lilypond.contains("\\tempo 4 = 80"));
+    assert!(lilypond.contains("% Pattern 0\n    \\time 4/4\n    c'2 e'4 g'4 |"));
+}
+
+#[test]
+fn test_get_note_values() {
+    use NoteValueBase::*;
+    use NoteValueDotted::*;
+    assert_eq!(get_note_values(Rational32::new(1, 4)), vec![NoteValue::default()]);
+    assert_eq!(get_note_values(Rational32::new(7, 16)), vec![NoteValue{base: Quarter, dotted: Some(DoubleDotted), tuplet: None}]);
+    assert_eq!(get_note_values(Rational32::new(5, 4)), vec![NoteValue{base: Whole, dotted: None, tuplet: None}, NoteValue{base: Quarter, dotted: None, tuplet: None}]);
+    assert_eq!(get_note_values(Rational32::new(3, 128)), vec![NoteValue{base: SixtyFourth, dotted: Some(Dotted), tuplet: None}]);
+}
+
+#[test]
+fn test_tuplets() {
+    use NoteValueBase::*;
+    let note_value = |s: &str| NoteValue::from_str(s).unwrap();
+    assert_eq!(Tuplet::from_str("3").unwrap(), Tuplet::triplet());
+    assert_eq!(Tuplet::from_str("5").unwrap(), Tuplet::new(5, 4).unwrap());
+    assert_eq!(Tuplet::from_str("2").unwrap(), Tuplet::new(2, 3).unwrap());
+    assert_eq!(Tuplet::from_str("7:8").unwrap().to_string(), "7:8");
+    assert_eq!(Tuplet::from_str("7:4").unwrap().to_string(), "7");
+    assert!(Tuplet::from_str("1").is_err());
+    assert!(Tuplet::from_str("3:3").is_err());
+    assert!(Tuplet::from_str("17").is_err());
+    assert!(Tuplet::from_str("255:254").is_err());
+    assert_eq!(Tuplet::from_str("16:15").unwrap(), Tuplet::new(16, 15).unwrap());
+
+    assert_eq!(note_value("e:3"), NoteValue{base: Eighth, dotted: None, tuplet: Some(Tuplet::triplet())});
+    assert_eq!(note_value("e:3").get_relative_duration(), Rational32::new(1, 12));
+    assert_eq!(note_value("e:3").get_written_duration(), Rational32::new(1, 8));
+    assert_eq!(note_value("s:5").get_relative_duration(), Rational32::new(1, 20));
+    assert_eq!(note_value("q.:3").get_relative_duration(), Rational32::new(1, 4));
+    assert_eq!(note_value("64").get_relative_duration(), Rational32::new(1, 64));
+    assert_eq!(note_value("32..").get_relative_duration(), Rational32::new(7, 128));
+    assert_eq!(note_value("s:7:8").to_ascii(), "s:7:8");
+    assert_eq!(note_value("64.").to_ascii(), "64.");
+    assert_eq!(note_value("e:3").to_string(), format!("{}:3", Eighth));
+    assert!(NoteValue::from_str("e:1").is_err());
+
+    // Triplets, swing, quintuplets, thirty-seconds and sixty-fourths fill the measure exactly
+    let measure = Measure::from_str("C4e:3 D4e:3 E4e:3 G4q:3 A4e:3 C4s:5 D4s:5 E4s:5 F4s:5 G4s:5 C432 D432 E432 F432 C464 D464 E464 F464 G464 A464 B464 C564").unwrap();
+    assert!(measure.is_measure_complete());
+    assert!(Measure::from_str("C4h C4q C4e:3 C4e:3 C4e:3 C4e:3").is_err());
+    let mut measure = Measure::new(TimeSignature::default());
+    (0..12).for_each(|_| measure.add_note(PianoKey::default(), note_value("e:3")));
+    assert!(measure.is_measure_complete());
+}
+
+#[test]
+fn test_tuplet_export() {
+    let measure = Measure::from_str("C4e:3 D4e:3 E4e:3 F4q C4s:5 D4s:5 E4s:5 F4s:5 G4s:5 C432 D432 E432 F432 C464 D464 E464 F464 G464 A464 B464 C564").unwrap();
+    let mut pattern = Pattern::new(String::new());
+    pattern.add_measure(measure.clone());
+    let mut sheet = Sheet::new();
+    sheet.add_pattern(pattern);
+    let key = Key::new(Scale::default(), PianoKey::from_str("C4").unwrap(), 1);
+
+    assert_eq!(get_ticks(NoteValue::from_str("e:3").unwrap()), 160);
+    assert_eq!(get_ticks(NoteValue::from_str("s:5").unwrap()), 96);
+    assert_eq!(get_ticks(NoteValue::from_str("64").unwrap()), 30);
+
+    let lilypond = sheet_to_lilypond(&sheet, None, key, Tempo::from(60));
+    assert!(lilypond.contains("\\tuplet 3/2 { c'8 d'8 e'8 } f'4 \\tuplet 5/4 { c'16 d'16 e'16 f'16 g'16 } c'32"));
+
+    // 480 divisions per quarter so that the triplets and the quintuplets last a whole number of divisions
+    let musicxml = sheet_to_musicxml(&sheet, None, key, Tempo::from(60));
+    assert!(musicxml.contains("<divisions>480</divisions>"));
+    assert!(musicxml.contains("<duration>160</duration>"));
+    assert!(musicxml.contains("<actual-notes>5</actual-notes>\n          <normal-notes>4</normal-notes>"));
+    assert_eq!(musicxml.matches("<tuplet type=\"start\"").count(), 2);
+    assert_eq!(musicxml.matches("<tuplet type=\"stop\"/>").count(), 2);
+
+    let abc = sheet_to_abc(&sheet, None, key, Tempo::from(60));
+    assert!(abc.contains("(3:2:3C D E F2 (5:4:5C/ D/ E/ F/ G/ C/4"));
+    let (abc_sheet, _) = sheet_from_abc(&abc).unwrap();
+    assert_eq!(abc_sheet.patterns[0].measures[0].to_ascii(), measure.to_ascii());
+    let (abc_sheet, _) = sheet_from_abc("X:1\nL:1/8\nM:4/4\nK:C\n(3CDE (3CzD F4|\n").unwrap();
+    assert_eq!(abc_sheet.patterns[0].measures[0].to_ascii(), "C4e:3 D4e:3 E4e:3 C4q:3 D4e:3 F4h");
+}
+
+// Test for ABC notation
+#[test]
+fn test_grouped_time_signature_export() {
+    use NoteValueBase::*;
+    let time_signature = TimeSignature::from_str("3+2+2/8").unwrap();
+    let mut measure = Measure::new(time_signature);
+    [(Quarter, Some(NoteValueDotted::Dotted)), (Eighth, None), (Eighth, None), (Quarter, None)].iter().for_each(|(base, dotted)| {
+        measure.add_note(PianoKey::from_str("C4").unwrap(), NoteValue{base: *base, dotted: *dotted, tuplet: None});
+    });
+    let mut pattern = Pattern::new(String::new());
+    pattern.add_measure(measure);
+    let mut sheet = Sheet::new();
+    sheet.add_pattern(pattern);
+
+    let key = Key::new(Scale::default(), PianoKey::from_str("C4").unwrap(), 1);
+    assert!(sheet_to_lilypond(&sheet, None, key, Tempo::from(60)).contains("\\time 3,2,2 7/8"));
+    assert!(sheet_to_musicxml(&sheet, None, key, Tempo::from(60)).contains("<beats>3+2+2</beats>\n          <beat-type>8</beat-type>"));
+    let abc = sheet_to_abc(&sheet, None, key, Tempo::from(60));
+    assert!(abc.contains("M:3+2+2/8"));
+    assert_eq!(sheet_from_abc(&abc).unwrap().0.patterns[0].measures[0].time_signature, time_signature);
+    // Velocity of the downbeat, the medium beats on the 4th and 6th eighths, then the weak beat
+    let midi = sheet_to_midi(&sheet, None, Tempo::from(60), time_signature);
+    let velocities = midi.windows(3).filter(|w| w[0] == 0x90 && w[1] == 60).map(|w| w[2]).collect::<Vec<u8>>();
+    assert_eq!(velocities, vec![100, 90, 80, 90]);
+}
+
+#[test]
+fn test_sheet_to_abc() {
+    use NoteValueBase::*;
+    let rhythm_pattern = vec![NoteValue{base: Half, dotted: None, tuplet: None}];
+    let mut measure = Measure::new(TimeSignature::default());
+    measure.add_note(PianoKey::from_str("F4").unwrap(), NoteValue{base: Half, dotted: None, tuplet: None});
+    measure.add_note(PianoKey::from_str("A#4").unwrap(), NoteValue{base: Eighth, dotted: None, tuplet: None});
+    measure.add_note(PianoKey::from_str("A#4").unwrap(), NoteValue{base: Eighth, dotted: None, tuplet: None});
+    measure.add_note(PianoKey::from_str("B4").unwrap(), NoteValue{base: Sixteenth, dotted: None, tuplet: None});
+    measure.add_note(PianoKey::from_str("C5").unwrap(), NoteValue{base: Eighth, dotted: Some(NoteValueDotted::Dotted), tuplet: None});
+    let mut pattern = Pattern::new(String::from("A"));
+    pattern.add_measure(measure);
+    let mut sheet = Sheet::new();
+    sheet.add_pattern(pattern);
+
+    let abc = sheet_to_abc(
+        &sheet,
+        Some((&ChordProgression::from_scale_and_str(Scale::default(), PianoKey::from_str("F3").unwrap(), "I-V"), &rhythm_pattern)),
+        Key::new(Scale::default(), PianoKey::from_str("F4").unwrap(), 1),
+        Tempo::from(90)
+    );
+    assert_eq!(abc, "X:1\nT:pmusic\nM:4/4\nL:1/8\nQ:1/4=90\nK:F\nP:A\n\"F\"F4 \"C\"B B =B/ c3/2 |]\n");
+}
+
+#[test]
+fn test_sheet_from_abc() {
+    let abc = "X:1\nT:Test\nM:3/4\nL:1/4\nQ:1/4=100\nK:G\n\"G\"G A/B/ c | d>e f | [M:2/4] ^c2 :|\n";
+    let (sheet, tempo) = sheet_from_abc(abc).unwrap();
+    assert_eq!(tempo, Tempo::from(100));
+    let measures = &sheet.patterns[0].measures;
+    assert_eq!(measures.len(), 3);
+    assert_eq!(measures[0].notes.len(), 4);
+    assert_eq!(measures[0].notes[1].value, NoteValue{base: NoteValueBase::Eighth, dotted: None, tuplet: None});
+    assert_eq!(measures[0].notes[3].note, PianoKey::from_str("C5").unwrap());
+    // key of G: f is F#, broken rhythm d>e is a dotted d and a shortened e
+    assert_eq!(measures[1].notes[0].value, NoteValue{base: NoteValueBase::Quarter, dotted: Some(NoteValueDotted::Dotted), tuplet: None});
+    assert_eq!(measures[1].notes[1].value, NoteValue{base: NoteValueBase::Eighth, dotted: None, tuplet: None});
+    assert_eq!(measures[1].notes[2].note, PianoKey::from_str("F#5").unwrap());
+    assert!(measures.iter().take(2).all(|m| m.is_measure_complete()));
+    assert_eq!(measures[2].time_signature, TimeSignature::from_str("2/4").unwrap());
+    assert_eq!(measures[2].notes[0].note, PianoKey::from_str("C#5").unwrap());
+}
+
+#[test]
+fn test_sheet_from_abc_rests() {
+    let abc = "X:1\nM:3/4\nL:1/4\nK:C\nz C D | z E F | E z2 | z3 |\n";
+    let (sheet, _) = sheet_from_abc(abc).unwrap();
+    let measures = &sheet.patterns[0].measures;
+    assert_eq!(measures.len(), 4);
+    assert!(measures.iter().all(|m| m.is_measure_complete()));
+    // The leading rest goes to the first note, the others hold the previous note across the barline
+    assert_eq!(measures[0].notes[0].note, PianoKey::from_str("C4").unwrap());
+    assert_eq!(measures[0].notes[0].value, NoteValue{base: NoteValueBase::Half, dotted: None, tuplet: None});
+    assert_eq!(measures[1].notes[0].note, PianoKey::from_str("D4").unwrap());
+    assert_eq!(measures[3].notes[0].note, PianoKey::from_str("E4").unwrap());
+    assert_eq!(measures[3].notes[0].value, NoteValue{base: NoteValueBase::Half, dotted: Some(NoteValueDotted::Dotted), tuplet: None});
+}
+
+#[test]
+fn test_abc_round_trip() {
+    let sheet = get_test_sheet();
+    let abc = sheet_to_abc(&sheet, None, Key::new(Scale::default(), PianoKey::from_str("A4").unwrap(), 1), Tempo::from(60));
+    let (imported_sheet, tempo) = sheet_from_abc(&abc).unwrap();
+    assert_eq!(tempo, Tempo::from(60));
+    assert_eq!(imported_sheet.patterns[0].name, "Pattern 0");
+    assert_eq!(imported_sheet.to_string(), sheet.to_string());
+}
+
+#[test]
+fn test_sheet_from_invalid_abc() {
+    let error = sheet_from_abc("X:1\nM:2/4\nL:1/4\nK:C\nC2 D |\n").unwrap_err();
+    assert_eq!(error.to_string(), "Invalid ABC at line 5: measure is longer than the meter");
+    assert!(sheet_from_abc("X:1\nK:H\nC").is_err());
+    // Inputs which used to panic
+    assert!(sheet_from_abc("X:1\nK:\u{c9}maj\nC").is_err());
+    assert!(sheet_from_abc("X:1\nL:1/4\nK:C\n[]: C|\n").is_ok());
+    assert!(sheet_from_abc(&format!("X:1\nK:C\nC{}|\n", "/".repeat(40))).is_err());
+    assert!(sheet_from_abc("X:1\nQ:1/4=4000000000\nK:C\nC|\n").is_err());
+    assert!(sheet_from_abc("X:1\nL:1000/1\nK:C\nC10000000|\n").is_err());
+    assert!(sheet_from_abc("X:1\nK:C\nC5000000/268435399|\n").is_err());
+    assert!(sheet_from_abc("X:1\nK:C\nC>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>>D|\n").is_err());
+}
+
+// Test for the ASCII sheet notation
+#[test]
+fn test_note_value_from_ascii() {
+    use NoteValueBase::*;
+    use NoteValueDotted::*;
+    assert_eq!(NoteValue::from_str("q").unwrap(), NoteValue::default());
+    assert_eq!(NoteValue::from_str("e.").unwrap(), NoteValue{base: Eighth, dotted: Some(Dotted), tuplet: None});
+    assert_eq!(NoteValue::from_str("16..").unwrap(), NoteValue{base: Sixteenth, dotted: Some(DoubleDotted), tuplet: None});
+    assert_eq!(NoteValue{base: Whole, dotted: Some(TripleDotted), tuplet: None}.to_ascii(), "w...");
+    assert!(NoteValue::from_str("t").is_err());
+    assert!(NoteValue::from_str("q....").is_err());
+}
+
+#[test]
+fn test_sheet_note_from_ascii() {
+    use crate::musictheory::sheet_note::SheetNote;
+    let sheet_note = SheetNote::from_str("D#4e.").unwrap();
+    assert_eq!(sheet_note.note, PianoKey::from_str("D#4").unwrap());
+    assert_eq!(sheet_note.value, NoteValue{base: NoteValueBase::Eighth, dotted: Some(NoteValueDotted::Dotted), tuplet: None});
+    assert_eq!(SheetNote::from_str("Bb38").unwrap().to_ascii(), "Bb3e");
+    assert!(SheetNote::from_str("r8").is_err());
+    assert!(SheetNote::from_str("C4").is_err());
+    // Double accidentals of the key-aware spelling
+    assert_eq!(SheetNote::from_str("Fx4q").unwrap().note, PianoKey::from_str("Fx4").unwrap());
+    assert_eq!(SheetNote::from_str("Cbb4q").unwrap().note, PianoKey::from_str("Cbb4").unwrap());
+    let sheet_note = SheetNote::from_str("F##4e.").unwrap();
+    assert_eq!(sheet_note.note, PianoKey::from_str("Fx4").unwrap());
+    assert_eq!(sheet_note.value, NoteValue{base: NoteValueBase::Eighth, dotted: Some(NoteValueDotted::Dotted), tuplet: None});
+    assert_eq!(SheetNote::from_str("Cbb4q").unwrap().to_ascii(), "Cbb4q");
+}
+
+#[test]
+fn test_measure_from_ascii() {
+    let measure = Measure::from_str("3/4 C4h D4q").unwrap();
+    assert_eq!(measure.time_signature, TimeSignature::from_str("3/4").unwrap());
+    assert!(measure.is_measure_complete());
+    assert_eq!(measure.to_ascii(), "C4h D4q");
+    assert!(Measure::from_str("C4w D4q").is_err());
+}
+
+#[test]
+fn test_sheet_ascii_round_trip() {
+    let text = "Pattern 0: C4h E4q G4q | 3/4 A4h. |\nPattern 1: Bb3e. C#5s D4h |\n";
+    let sheet = Sheet::from_str(text).unwrap();
+    assert_eq!(sheet.patterns.len(), 2);
+    assert_eq!(sheet.patterns[1].name, "Pattern 1");
+    assert_eq!(sheet.patterns[1].measures[0].time_signature, TimeSignature::from_str("3/4").unwrap());
+    assert_eq!(sheet.to_ascii(), text);
+
+    let sheet = get_test_sheet();
+    assert_eq!(Sheet::from_str(&sheet.to_ascii()).unwrap().to_string(), sheet.to_string());
+
+    // G# major is spelled with Fx
+    use rand::{rngs::SmallRng, SeedableRng};
+    let sheet = sheet_generation(PianoKey::from_str("G#4").unwrap(), Scale::default(), 1, TimeSignature::default(), 8, false, &mut SmallRng::seed_from_u64(0));
+    assert!(sheet.to_ascii().contains("Fx"));
+    assert_eq!(Sheet::from_str(&sheet.to_ascii()).unwrap().to_ascii(), sheet.to_ascii());
+
+    let text = "C4e:3 D4e:3 E4e:3 F4e:5 G4e:5 A4e:5 B4e:5 C5e:5 D5q |\nTriplets: C4q:3 D4e:3 C4q:3 D4e:3 E4h |\n";
+    let sheet = Sheet::from_str(text).unwrap();
+    assert_eq!(sheet.patterns[1].name, "Triplets");
+    assert_eq!(sheet.to_ascii(), text);
+}
+
+#[test]
+fn test_sheet_from_invalid_ascii() {
+    let error = Sheet::from_str("# comment\nC4q D4q |\nC4w E4q |").unwrap_err();
+    assert_eq!(error.to_string(), "line 3: E4q overflows the 4/4 measure");
+    let error = Sheet::from_str("A: C4w |\nB:").unwrap_err();
+    assert_eq!(error.to_string(), "line 2: the pattern B has no measures");
+    let error = Sheet::from_str("C4q D4q E4q F4q | 3/4 |").unwrap_err();
+    assert_eq!(error.to_string(), "line 1: the 3/4 measure has no notes");
+}
+
+// Test for serde serialization
+#[cfg(feature = "serde")]
+#[test]
+fn test_serialize_sheet_note() {
+    use NoteValueBase::Half;
+    use NoteValueDotted::Dotted;
+    let sheet_note = crate::musictheory::sheet_note::SheetNote {
+        note: PianoKey::from_str("C#4").unwrap(),
+        value: NoteValue{base: Half, dotted: Some(Dotted), tuplet: None},
+    };
+    assert_eq!(
+        serde_json::to_string(&sheet_note).unwrap(),
+        r#"{"note":"C#4","value":{"base":"half","dotted":"dotted"}}"#
+    );
+    assert_eq!(serde_json::to_string(&NoteValue::default()).unwrap(), r#"{"base":"quarter"}"#);
+    assert!(serde_json::from_str::<PianoKey>(r#""Q4""#).is_err());
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serialize_sheet_round_trip() {
+    let sheet = get_test_sheet();
+    let json = serde_json::to_string_pretty(&sheet).unwrap();
+    assert!(json.contains(r#""time_signature": "4/4""#));
+    let deserialized_sheet: Sheet = serde_json::from_str(&json).unwrap();
+    assert_eq!(deserialized_sheet.to_string(), sheet.to_string());
+    assert_eq!(deserialized_sheet.patterns[0].name, "Pattern 0");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serialize_chord_progression_and_key() {
+    use Mode::Dorian;
+    let chord_progression = ChordProgression::default();
+    let json = serde_json::to_string(&chord_progression.chords[0]).unwrap();
+    assert_eq!(json, r#"{"base_note":"C4","chord_type":"major_triad","inversion":"root"}"#);
+    let deserialized: ChordProgression = serde_json::from_str(&serde_json::to_string(&chord_progression).unwrap()).unwrap();
+    assert_eq!(deserialized, chord_progression);
+
+    let key = Key::new(Scale::Diatonic(Dorian), PianoKey::from_str("D4").unwrap(), 2);
+    let json = serde_json::to_string(&key).unwrap();
+    assert_eq!(json, r#"{"base_note":"D4","octaves":2,"scale":{"diatonic":"dorian"}}"#);
+    assert_eq!(serde_json::from_str::<Key>(&json).unwrap(), key);
+}
+
+#[test]
+fn test_wav_header() {
+    let samples = [0.0, 0.5, -0.5, 1.0];
+    let wav = samples_to_wav(&samples, WavConfig::new(WavSampleFormat::Int24, 1, 48_000)).unwrap();
+    assert_eq!(&wav[0..4], b"RIFF");
+    assert_eq!(&wav[8..12], b"WAVE");
+    assert_eq!(u16::from_le_bytes([wav[20], wav[21]]), 1); // PCM
+    assert_eq!(u16::from_le_bytes([wav[22], wav[23]]), 1); // mono
+    assert_eq!(u32::from_le_bytes([wav[24], wav[25], wav[26], wav[27]]), 48_000);
+    assert_eq!(u16::from_le_bytes([wav[34], wav[35]]), 24);
+    assert_eq!(u32::from_le_bytes([wav[40], wav[41], wav[42], wav[43]]), 12);
+    assert_eq!(wav.len(), 44 + 12);
+
+    let wav = samples_to_wav(&samples, WavConfig::default()).unwrap();
+    assert_eq!(u16::from_le_bytes([wav[20], wav[21]]), 3); // IEEE float
+    assert_eq!(&wav[48..52], &0.5f32.to_le_bytes());
+}
+
+#[test]
+fn test_wav_quantize() {
+    use rand::{rngs::SmallRng, SeedableRng};
+    let mut rng = SmallRng::seed_from_u64(0);
+    assert_eq!(quantize(1.0, 16, false, &mut rng), i16::MAX as i32);
+    assert_eq!(quantize(-2.0, 16, false, &mut rng), i16::MIN as i32);
+    assert_eq!(quantize(0.5, 24, false, &mut rng), 4_194_304);
+    // TPDF dither stays within one LSB of the undithered value
+    (0..1000).for_each(|_| {
+        let value = quantize(0.25, 16, true, &mut rng);
+        assert!((value - 8192).abs() <= 1);
+    });
+}
+
+#[test]
+fn test_wav_config_errors() {
+    assert_eq!(WavSampleFormat::from_str("24").unwrap(), WavSampleFormat::Int24);
+    assert_eq!(WavSampleFormat::from_str("float").unwrap(), WavSampleFormat::Float32);
+    assert!(WavSampleFormat::from_str("12").is_err());
+    assert!(WavConfig::default().set_channels(6).validate().is_err());
+    assert!(samples_to_wav(&[0.0], WavConfig::default().set_sample_rate(1_000)).is_err());
+}
+
+fn get_test_samples(nb_samples: usize, channels: usize) -> Vec<f32> {
+    (0..nb_samples * channels)
+        .map(|i| {
+            let t = (i / channels) as f32 / 44_100.0;
+            0.3 * (2.0 * std::f32::consts::PI * 440.0 * t).sin() + 0.1 * (2.0 * std::f32::consts::PI * 1234.5 * t * (1 + i % channels) as f32).sin()
+        })
+        .collect()
+}
+
+#[test]
+fn test_flac_is_bit_exact() {
+    [(WavSampleFormat::Int16, 2), (WavSampleFormat::Int24, 1)].iter().for_each(|(sample_format, channels)| {
+        // More than one block, the last one being shorter
+        let samples = get_test_samples(FLAC_BLOCK_SIZE * 2 + 123, *channels as usize);
+        let config = WavConfig::new(*sample_format, *channels, 44_100);
+        let flac = samples_to_flac(&samples, config, &[]).unwrap();
+        let mut reader = claxon::FlacReader::new(std::io::Cursor::new(flac.clone())).unwrap();
+        assert_eq!(reader.streaminfo().channels, *channels as u32);
+        assert_eq!(reader.streaminfo().bits_per_sample, sample_format.get_bits_per_sample() as u32);
+        assert_eq!(reader.streaminfo().samples, Some((FLAC_BLOCK_SIZE * 2 + 123) as u64));
+        let decoded = reader.samples().map(|s| s.unwrap()).collect::<Vec<i32>>();
+        assert_eq!(decoded, quantize_samples(&samples, config));
+        // Lossless, but smaller than the WAV
+        assert!(flac.len() < samples_to_wav(&samples, config).unwrap().len());
+    });
+}
+
+#[test]
+fn test_flac_tags_and_silence() {
+    let tags = vec![(String::from("seed"), String::from("42")), (String::from("TEMPO"), String::from("60"))];
+    let flac = samples_to_flac(&[0.0; 100], WavConfig::new(WavSampleFormat::Int16, 1, 48_000).set_dither(false), &tags).unwrap();
+    let mut reader = claxon::FlacReader::new(std::io::Cursor::new(flac)).unwrap();
+    assert_eq!(reader.get_tag("SEED").collect::<Vec<&str>>(), vec!["42"]);
+    assert_eq!(reader.get_tag("tempo").collect::<Vec<&str>>(), vec!["60"]);
+    assert_eq!(reader.streaminfo().sample_rate, 48_000);
+    assert!(reader.samples().all(|s| s.unwrap() == 0));
+
+    assert!(samples_to_flac(&[0.0], WavConfig::default(), &[]).is_err());
+}
+
+#[test]
+fn test_render_stems() {
+    use rodio::{source::SineWave, Source};
+    let melody = render_source(SineWave::new(440.0).take_duration(std::time::Duration::from_millis(500)), 2, 44_100);
+    let chords = render_source(SineWave::new(220.0).take_duration(std::time::Duration::from_millis(250)), 2, 44_100);
+    assert_eq!(melody.len(), 44_100);
+    assert_eq!(chords.len(), 22_050);
+
+    let mut stems = vec![melody.clone(), chords.clone()];
+    let mixdown = align_and_mix_stems(&mut stems);
+    assert!(stems.iter().all(|stem| stem.len() == mixdown.len()));
+    assert_eq!(mixdown[100], melody[100] + chords[100]);
+    assert_eq!(mixdown[30_000], melody[30_000]);
+    assert!(align_and_mix_stems(&mut []).is_empty());
+}
+
+#[test]
+fn test_get_stem_path() {
+    assert_eq!(get_stem_path("./output/output.wav", "melody"), "./output/output_melody.wav");
+    assert_eq!(get_stem_path("./my.dir/output", "chords"), "./my.dir/output_chords");
+    assert_eq!(get_stem_path("song.v2.wav", "chords"), "song.v2_chords.wav");
+}
+
+#[test]
+fn test_pcm_stream_matches_wav_data() {
+    let samples = get_test_samples(PCM_CHUNK_SIZE + 10, 2);
+    [WavSampleFormat::Int16, WavSampleFormat::Int24, WavSampleFormat::Float32].iter().for_each(|sample_format| {
+        let config = WavConfig::default().set_sample_format(*sample_format);
+        let mut pcm = Vec::new();
+        write_pcm_stream(&mut pcm, samples.iter().copied(), config).unwrap();
+        assert_eq!(pcm, samples_to_wav(&samples, config).unwrap()[44..]);
+    });
+    assert!(write_pcm_stream(&mut Vec::new(), samples.into_iter(), WavConfig::default().set_channels(0)).is_err());
+}
+
+#[test]
+fn test_piano_roll_svg() {
+    let mut sheet = get_test_sheet();
+    let mut pattern = Pattern::new(String::from("B & <C>"));
+    pattern.add_measure(Measure::parse("C5w", TimeSignature::default()).unwrap());
+    sheet.patterns.push(pattern);
+    let svg = sheet_to_svg(&sheet, None);
+    assert!(svg.starts_with("<svg"));
+    assert_eq!(svg.matches("class=\"note\"").count(), 4);
+    assert_eq!(svg.matches("class=\"pattern\"").count(), 2);
+    assert!(svg.contains("B &amp; &lt;C&gt;"));
+    assert!(!svg.contains("class=\"chord\""));
+    // C4 to C5: 13 rows, two whole notes long
+    assert!(svg.contains(&format!("height=\"{}\"", 40.0 + 13.0 * KEY_HEIGHT + 1.0)));
+    assert!(svg.contains(&format!("width=\"{}\"", 40.0 + 2.0 * WHOLE_NOTE_WIDTH + 1.0)));
+    // The highest note is on the top row, a half note at the start
+    assert!(svg.contains(&format!("x=\"40\" y=\"{}\" width=\"{}\"", 40.0 + 12.0 * KEY_HEIGHT, WHOLE_NOTE_WIDTH / 2.0)));
+}
+
+#[test]
+fn test_piano_roll_chord_bands() {
+    let sheet = get_test_sheet();
+    let chord_progression = ChordProgression::default();
+    let rhythm_pattern = vec![NoteValue{base: NoteValueBase::Half, dotted: None, tuplet: None}];
+    let svg = sheet_to_svg(&sheet, Some((&chord_progression, &rhythm_pattern)));
+    // One measure, so only the first two chords are drawn
+    assert_eq!(svg.matches("class=\"chord\"").count(), 2);
+    assert!(svg.contains(&chord_progression.chords[0].to_string()));
+    assert!(!sheet_to_svg(&Sheet::new(), None).contains("class=\"note\""));
+}
+
+#[test]
+fn test_waveform_peaks_and_clipping() {
+    let mut samples = get_test_samples(44_100, 2);
+    let peaks = get_waveform_peaks(&samples, 2, 100);
+    assert_eq!(peaks.len(), 100);
+    assert!(peaks.iter().all(|(min, max)| *min >= -0.4 && *max <= 0.4 && min < max));
+    assert!(!waveform_to_svg(&samples, 2).contains("class=\"clip\""));
+
+    samples[1000] = 1.5;
+    let svg = waveform_to_svg(&samples, 2);
+    assert_eq!(svg.matches("class=\"clip\"").count(), 1);
+
+    let png = waveform_to_png(&samples, 2).unwrap();
+    let reader = png::Decoder::new(std::io::Cursor::new(png)).read_info().unwrap();
+    assert_eq!(reader.info().width as usize, WAVEFORM_WIDTH);
+    assert_eq!(reader.info().height as usize, WAVEFORM_HEIGHT);
+}
+
+#[test]
+fn test_spectrogram() {
+    // 1 kHz full scale sine: bin 1000 * 1024 / 44100 = 23.2
+    let samples = (0..44_100).map(|i| (2.0 * std::f32::consts::PI * 1000.0 * i as f32 / 44_100.0).sin()).collect::<Vec<f32>>();
+    let spectrogram = get_spectrogram(&samples, 1, FFT_SIZE);
+    assert_eq!(spectrogram.len(), (44_100 - FFT_SIZE) / (FFT_SIZE / 2) + 1);
+    spectrogram.iter().for_each(|frame| {
+        assert_eq!(frame.len(), FFT_SIZE / 2);
+        let (peak_bin, peak) = frame.iter().enumerate().fold((0, f32::MIN), |best, (i, db)| if *db > best.1 { (i, *db) } else { best });
+        assert_eq!(peak_bin, 23);
+        assert!(peak > -3.0 && peak < 0.5);
+    });
+    assert!(get_spectrogram(&samples[..100], 1, FFT_SIZE).is_empty());
+
+    let png = spectrogram_to_png(&spectrogram).unwrap();
+    let reader = png::Decoder::new(std::io::Cursor::new(png)).read_info().unwrap();
+    assert_eq!(reader.info().width as usize, spectrogram.len());
+    assert_eq!(reader.info().height as usize, FFT_SIZE / 2);
+    let svg = spectrogram_to_svg(&spectrogram, 44_100).unwrap();
+    assert!(svg.contains("data:image/png;base64,iVBORw0KGgo"));
+    assert!(svg.contains("22050 Hz"));
+
+    assert_eq!(ImageFormat::from_path("out/render.PNG").unwrap(), ImageFormat::Png);
+    assert!(ImageFormat::from_path("render.jpg").is_err());
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn test_tui_sheet_position() {
+    // 60 bpm: the half note lasts 2 seconds, the quarter notes 1 second, the whole sheet 4 seconds
+    let mut sheet = get_test_sheet();
+    let tempo = Tempo::from(60);
+    assert_eq!(get_sheet_position(&sheet, tempo, 0.5), Some(SheetPosition{pattern: 0, measure: 0, note: 0}));
+    assert_eq!(get_sheet_position(&sheet, tempo, 2.5), Some(SheetPosition{pattern: 0, measure: 0, note: 1}));
+    assert_eq!(get_sheet_position(&sheet, tempo, 3.5), Some(SheetPosition{pattern: 0, measure: 0, note: 2}));
+    assert_eq!(get_sheet_position(&sheet, tempo, 4.5), Some(SheetPosition{pattern: 0, measure: 0, note: 0}));
+    assert_eq!(get_sheet_position(&Sheet::new(), tempo, 1.0), None);
+
+    let mut pattern = Pattern::new(String::from("Pattern 1"));
+    pattern.add_measure(Measure::parse("C5w", TimeSignature::default()).unwrap());
+    sheet.patterns.push(pattern);
+    assert_eq!(get_sheet_position(&sheet, tempo, 5.0), Some(SheetPosition{pattern: 1, measure: 0, note: 0}));
+    assert_eq!(get_next_pattern_start(&sheet, tempo, 1.0), 4.0);
+    assert_eq!(get_next_pattern_start(&sheet, tempo, 5.0), 8.0);
+    assert_eq!(get_next_pattern_start(&sheet, tempo, 9.0), 12.0);
+}
+
+#[cfg(feature = "tui")]
+#[test]
+fn test_tui_chord_and_piano_roll() {
+    use NoteValueBase::{Half, Quarter};
+    let tempo = Tempo::from(60);
+    let rhythm_pattern = vec![NoteValue{base: Half, dotted: None, tuplet: None}, NoteValue{base: Quarter, dotted: None, tuplet: None}];
+    assert_eq!(get_chord_index(&rhythm_pattern, 3, tempo, 0.5), Some(0));
+    assert_eq!(get_chord_index(&rhythm_pattern, 3, tempo, 2.5), Some(1));
+    assert_eq!(get_chord_index(&rhythm_pattern, 3, tempo, 3.5), Some(2));
+    assert_eq!(get_chord_index(&rhythm_pattern, 3, tempo, 5.5), Some(0));
+    assert_eq!(get_chord_index(&[], 3, tempo, 1.0), None);
+
+    // From G4 down to C4, the playhead at a quarter of the width
+    let lines = get_piano_roll_lines(&get_test_sheet(), tempo, 0.0, 20);
+    assert_eq!(lines.len(), 8);
+    assert!(lines[0].starts_with("G4"));
+    assert!(lines[7].starts_with("C4"));
+    assert!(lines.iter().all(|line| line.chars().count() == 24));
+    assert_eq!(lines[7].chars().nth(4 + 5), Some('▐'));
+    assert_eq!(lines[7].chars().nth(4 + 6), Some('█'));
+    assert_eq!(lines[1].chars().nth(4 + 5), Some('│'));
+}
@@ -0,0 +1,307 @@
+// Terminal UI during playback: current pattern/measure/note, current chord and elapsed time,
+// with a piano roll scrolling under a fixed playhead
+// The position is computed from the elapsed time, the same way the music makers go through the sheet
+
+use std::{io::{self, stdout, Error, Stdout, Write}, time::{Duration, Instant}};
+
+use crossterm::{
+    cursor::{Hide, MoveTo, Show}, event::{self, Event, KeyCode, KeyEventKind, KeyModifiers}, execute, queue,
+    style::{Attribute, Print, SetAttribute}, terminal::{self, disable_raw_mode, enable_raw_mode, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen}
+};
+use num::{rational::Rational32, ToPrimitive, Zero};
+use rodio::{dynamic_mixer, OutputStreamHandle, Sink, Source};
+
+use crate::{
+    musicfile::midi::{get_midi_note_number, get_piano_key},
+    musictheory::{chord_progression::ChordProgression, note_value::NoteValue, sheet::Sheet, tempo::Tempo}
+};
+
+// Build the source of a track from the start, every skip restarts the tracks at the new position
+pub type TrackFactory = Box<dyn Fn() -> Box<dyn Source<Item = f32> + Send>>;
+
+// Sixteenth notes per column of the piano roll
+const ROLL_RESOLUTION: f32 = 16.0;
+const REFRESH_DELAY: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TuiAction {
+    Finished,
+    Quit,
+    Regenerate,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SheetPosition {
+    pub pattern: usize,
+    pub measure: usize,
+    pub note: usize,
+}
+
+pub struct TuiPlayback<'a> {
+    pub header: String,
+    pub sheet: &'a Sheet,
+    pub chord_part: Option<(&'a ChordProgression, &'a [NoteValue])>,
+    pub tempo: Tempo,
+    pub duration: Duration,
+    pub channels: u16,
+    pub sample_rate: u32,
+}
+
+pub fn get_sheet_duration(sheet: &Sheet, tempo: Tempo) -> f32 {
+    sheet.patterns.iter()
+        .flat_map(|p| p.measures.iter())
+        .flat_map(|m| m.notes.iter())
+        .fold(0.0, |sum, sn| sum + sn.value.get_duration_for_tempo(tempo))
+}
+
+// The sheet loops, like in the SheetMusicMaker
+pub fn get_sheet_position(sheet: &Sheet, tempo: Tempo, elapsed: f32) -> Option<SheetPosition> {
+    let sheet_duration = get_sheet_duration(sheet, tempo);
+    if sheet_duration <= 0.0 {
+        return None;
+    }
+    let mut remaining = elapsed.rem_euclid(sheet_duration);
+    let mut last_position = None;
+    for (pattern, p) in sheet.patterns.iter().enumerate() {
+        for (measure, m) in p.measures.iter().enumerate() {
+            for (note, sn) in m.notes.iter().enumerate() {
+                last_position = Some(SheetPosition { pattern, measure, note });
+                remaining -= sn.value.get_duration_for_tempo(tempo);
+                if remaining < 0.0 {
+                    return last_position;
+                }
+            }
+        }
+    }
+    // Rounding error at the very end of the loop
+    last_position
+}
+
+// Chords and rhythm pattern cycle independently, like in the ChordMusicMaker
+pub fn get_chord_index(rhythm_pattern: &[NoteValue], nb_chords: usize, tempo: Tempo, elapsed: f32) -> Option<usize> {
+    let rhythm_duration = rhythm_pattern.iter().fold(0.0, |sum, nv| sum + nv.get_duration_for_tempo(tempo));
+    if rhythm_duration <= 0.0 || nb_chords == 0 {
+        return None;
+    }
+    let mut step = (elapsed / rhythm_duration).floor() as usize * rhythm_pattern.len();
+    let mut remaining = elapsed.rem_euclid(rhythm_duration);
+    for note_value in rhythm_pattern.iter() {
+        remaining -= note_value.get_duration_for_tempo(tempo);
+        if remaining < 0.0 {
+            break;
+        }
+        step += 1;
+    }
+    Some(step % nb_chords)
+}
+
+// Time (from the start of the playback) where the next pattern begins
+pub fn get_next_pattern_start(sheet: &Sheet, tempo: Tempo, elapsed: f32) -> f32 {
+    let sheet_duration = get_sheet_duration(sheet, tempo);
+    if sheet_duration <= 0.0 {
+        return elapsed;
+    }
+    let loop_start = (elapsed / sheet_duration).floor() * sheet_duration;
+    let mut pattern_start = loop_start;
+    for pattern in sheet.patterns.iter() {
+        pattern_start += pattern.measures.iter()
+            .flat_map(|m| m.notes.iter())
+            .fold(0.0, |sum, sn| sum + sn.value.get_duration_for_tempo(tempo));
+        if pattern_start > elapsed + 1e-3 {
+            return pattern_start;
+        }
+    }
+    loop_start + sheet_duration
+}
+
+// One line per key from the highest note to the lowest, the playhead is the column at width / 4
+pub fn get_piano_roll_lines(sheet: &Sheet, tempo: Tempo, elapsed: f32, width: usize) -> Vec<String> {
+    let mut columns: Vec<Option<(u8, bool)>> = Vec::new();
+    let mut position = Rational32::zero();
+    let get_column = |position: Rational32| (position.to_f32().unwrap_or_default() * ROLL_RESOLUTION).round() as usize;
+    sheet.patterns.iter()
+        .flat_map(|p| p.measures.iter())
+        .flat_map(|m| m.notes.iter())
+        .for_each(|sn| {
+            let start = get_column(position);
+            position += sn.value.get_relative_duration();
+            let end = get_column(position).max(start + 1);
+            columns.resize(end, None);
+            (start..end).for_each(|i| columns[i] = Some((get_midi_note_number(sn.note), i == start)));
+        });
+    if columns.is_empty() {
+        return Vec::new();
+    }
+    let lowest = columns.iter().flatten().map(|(n, _)| *n).min().unwrap_or(60);
+    let highest = columns.iter().flatten().map(|(n, _)| *n).max().unwrap_or(72);
+
+    // A whole note lasts 4 beats
+    let playhead = (elapsed * tempo.get_bps() / 4.0 * ROLL_RESOLUTION).floor() as i64;
+    let first_column = playhead - (width / 4) as i64;
+    (lowest..=highest).rev()
+        .map(|midi_note_number| {
+            let mut line = format!("{:<4}", get_piano_key(midi_note_number).to_string());
+            (0..width as i64).for_each(|i| {
+                let column = columns[(first_column + i).rem_euclid(columns.len() as i64) as usize];
+                line.push(match column {
+                    Some((n, true)) if n == midi_note_number => '▐',
+                    Some((n, false)) if n == midi_note_number => '█',
+                    _ if first_column + i == playhead => '│',
+                    _ => if midi_note_number % 12 == 0 { '·' } else { ' ' },
+                });
+            });
+            line
+        })
+        .collect()
+}
+
+pub fn run_playback_tui(playback: TuiPlayback, stream_handle: &OutputStreamHandle, sink: Sink, tracks: &[TrackFactory]) -> Result<TuiAction, Error> {
+    let _terminal = TerminalGuard::new()?;
+    let mut out = stdout();
+    let mut sink = sink;
+    append_tracks(&sink, tracks, &playback, Duration::ZERO);
+    let mut offset = Duration::ZERO;
+    let mut started_at = Instant::now();
+    let mut paused = false;
+
+    loop {
+        let elapsed = if paused { offset } else { offset + started_at.elapsed() };
+        if elapsed >= playback.duration || sink.empty() {
+            return Ok(TuiAction::Finished);
+        }
+        draw(&mut out, &playback, elapsed, paused)?;
+
+        if !event::poll(REFRESH_DELAY)? {
+            continue;
+        }
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Char('q') | KeyCode::Esc => return Ok(TuiAction::Quit),
+                KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return Ok(TuiAction::Quit),
+                KeyCode::Char('r') => return Ok(TuiAction::Regenerate),
+                KeyCode::Char(' ') | KeyCode::Char('p') => {
+                    if paused {
+                        sink.play();
+                        started_at = Instant::now();
+                    } else {
+                        sink.pause();
+                        offset += started_at.elapsed();
+                    }
+                    paused = !paused;
+                },
+                KeyCode::Char('n') => {
+                    let next_pattern_start = get_next_pattern_start(playback.sheet, playback.tempo, elapsed.as_secs_f32());
+                    offset = Duration::from_secs_f32(next_pattern_start);
+                    if offset >= playback.duration {
+                        return Ok(TuiAction::Finished);
+                    }
+                    // Dropping the sink stops the tracks
+                    sink = Sink::try_new(stream_handle).map_err(|e| io::Error::other(e.to_string()))?;
+                    append_tracks(&sink, tracks, &playback, offset);
+                    if paused {
+                        sink.pause();
+                    }
+                    started_at = Instant::now();
+                },
+                _ => {},
+            }
+        }
+    }
+}
+
+fn append_tracks(sink: &Sink, tracks: &[TrackFactory], playback: &TuiPlayback, offset: Duration) {
+    let (controller, mixer) = dynamic_mixer::mixer::<f32>(playback.channels, playback.sample_rate);
+    tracks.iter().for_each(|track| {
+        controller.add(track().skip_duration(offset).take_duration(playback.duration.saturating_sub(offset)));
+    });
+    sink.append(mixer);
+}
+
+fn draw(out: &mut Stdout, playback: &TuiPlayback, elapsed: Duration, paused: bool) -> Result<(), Error> {
+    let (width, _) = terminal::size()?;
+    let seconds = elapsed.as_secs_f32();
+    let mut lines = vec![
+        playback.header.clone(),
+        format!(
+            "{} / {}  {}",
+            format_duration(elapsed),
+            format_duration(playback.duration),
+            if paused { "PAUSED" } else { "PLAYING" }
+        ),
+    ];
+    if let Some((chord_progression, rhythm_pattern)) = playback.chord_part {
+        if let Some(i) = get_chord_index(rhythm_pattern, chord_progression.chords.len(), playback.tempo, seconds) {
+            lines.push(format!("Chord: {}  ({})", chord_progression.chords[i], chord_progression.progression));
+        }
+    }
+    // Lines are overwritten in place, clearing the whole screen on each refresh flickers
+    lines.push(String::new());
+    lines.iter().enumerate().try_for_each(|(i, line)| print_line(out, i as u16, line))?;
+    let mut row = lines.len() as u16;
+
+    if let Some(position) = get_sheet_position(playback.sheet, playback.tempo, seconds) {
+        let pattern = &playback.sheet.patterns[position.pattern];
+        print_line(out, row, &format!(
+            "{} ({}/{})  measure {}/{}",
+            pattern.name,
+            position.pattern + 1,
+            playback.sheet.patterns.len(),
+            position.measure + 1,
+            pattern.measures.len()
+        ))?;
+        row += 1;
+        // The current measure, with the note being played highlighted
+        queue!(out, MoveTo(0, row))?;
+        pattern.measures[position.measure].notes.iter().enumerate().try_for_each(|(i, sn)| {
+            if i == position.note {
+                queue!(out, SetAttribute(Attribute::Reverse), Print(sn.to_ascii()), SetAttribute(Attribute::Reset), Print(" "))
+            } else {
+                queue!(out, Print(format!("{} ", sn.to_ascii())))
+            }
+        })?;
+        queue!(out, Clear(ClearType::UntilNewLine))?;
+        print_line(out, row + 1, "")?;
+        row += 2;
+    }
+
+    get_piano_roll_lines(playback.sheet, playback.tempo, seconds, (width as usize).saturating_sub(5))
+        .iter()
+        .try_for_each(|line| {
+            print_line(out, row, line)?;
+            row += 1;
+            Ok::<(), Error>(())
+        })?;
+    print_line(out, row, "")?;
+    print_line(out, row + 1, "[space] pause/resume  [n] next pattern  [r] regenerate  [q] quit")?;
+    queue!(out, MoveTo(0, row + 2), Clear(ClearType::FromCursorDown))?;
+    out.flush()
+}
+
+fn print_line(out: &mut Stdout, row: u16, line: &str) -> Result<(), Error> {
+    queue!(out, MoveTo(0, row), Print(line), Clear(ClearType::UntilNewLine))
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:02}:{:02}", duration.as_secs() / 60, duration.as_secs() % 60)
+}
+
+// Restores the terminal even when the playback stops on an error
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn new() -> Result<Self, Error> {
+        enable_raw_mode()?;
+        execute!(stdout(), EnterAlternateScreen, Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = execute!(stdout(), Show, LeaveAlternateScreen);
+        let _ = disable_raw_mode();
+    }
+}